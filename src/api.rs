@@ -0,0 +1,519 @@
+//! Broker API behind the client.
+//!
+//! [`InvestApi`] lists the requests [`crate::client::TinkoffInvestment`] makes.
+//! [`GrpcApi`] sends them to the Tinkoff Invest API, while
+//! [`crate::fake::FakeApi`] answers them from fixture files.
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError, RwLock};
+use tinkoff_invest_api::{
+    DefaultInterceptor, TinkoffInvestService,
+    tcs::{
+        Account, Coupon, Currency as CurrencyInstrument, Dividend, FindInstrumentRequest,
+        GetAccountsRequest, GetBondCouponsRequest, GetDividendsRequest, GetLastPricesRequest,
+        GetOperationsByCursorRequest, InstrumentIdType, InstrumentRequest, InstrumentShort,
+        InstrumentStatus, InstrumentType, InstrumentsRequest, LastPrice, Operation, OperationItem,
+        OperationState, OperationType, PortfolioPosition, PortfolioRequest,
+        instruments_service_client::InstrumentsServiceClient,
+        market_data_service_client::MarketDataServiceClient,
+        operations_service_client::OperationsServiceClient, portfolio_request::CurrencyRequest,
+        users_service_client::UsersServiceClient,
+    },
+};
+use tokio::sync::Mutex;
+use tonic::{Code, Status, codegen::InterceptedService, transport::Channel};
+
+use crate::{
+    client::InstrumentCatalog,
+    domain::{Instrument, Period, Ticker},
+    retry::{ApiError, api_error},
+    to_datetime_utc, to_timestamp,
+};
+
+/// Operations requested at once, the largest page the API allows
+const OPERATIONS_PAGE_SIZE: i32 = 1000;
+
+/// Future returned by [`InvestApi`] requests
+pub type ApiFuture<'a, T> = Pin<Box<dyn Future<Output = color_eyre::Result<T>> + Send + 'a>>;
+
+/// Requests to the broker the client is built on.
+///
+/// Requests are made once, retrying is up to the caller.
+pub trait InvestApi: Send + Sync {
+    /// Every account of the user, including closed ones
+    fn accounts(&self) -> ApiFuture<'_, Vec<Account>>;
+
+    /// Positions of the account, valued in rubles
+    fn portfolio(&self, account_id: String) -> ApiFuture<'_, Vec<PortfolioPosition>>;
+
+    /// Page of executed operations matching the query, newest first.
+    /// `cursor` is `None` for the first page and [`OperationsPage::next_cursor`] of the previous one after that.
+    fn operations(
+        &self,
+        query: OperationsQuery,
+        cursor: Option<String>,
+    ) -> ApiFuture<'_, OperationsPage>;
+
+    /// Name and ticker of the instrument, `None` if there is no such instrument
+    fn instrument_by_figi(&self, figi: String) -> ApiFuture<'_, Option<Instrument>>;
+
+    /// Whole instrument catalog by FIGI
+    fn instruments(&self, catalog: InstrumentCatalog)
+    -> ApiFuture<'_, HashMap<String, Instrument>>;
+
+    /// Currencies catalog with the details needed for exchange rates
+    fn currencies(&self) -> ApiFuture<'_, Vec<CurrencyInstrument>>;
+
+    /// Instruments matching the query (ticker, name, ISIN or FIGI)
+    fn find_instruments(&self, query: String) -> ApiFuture<'_, Vec<InstrumentShort>>;
+
+    /// Last prices of the instruments
+    fn last_prices(&self, figis: Vec<String>) -> ApiFuture<'_, Vec<LastPrice>>;
+
+    /// Dividends of the share or fund
+    fn dividends(&self, figi: String) -> ApiFuture<'_, Vec<Dividend>>;
+
+    /// Coupons of the bond
+    fn coupons(&self, figi: String) -> ApiFuture<'_, Vec<Coupon>>;
+}
+
+/// Executed operations of an account to request
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OperationsQuery {
+    pub account_id: String,
+    /// Operations with this instrument only, all operations of the account if `None`
+    pub figi: Option<String>,
+    /// Operations made at or after this moment
+    pub from: Option<DateTime<Utc>>,
+    /// Operations made before this moment
+    pub to: Option<DateTime<Utc>>,
+    /// Operations of these types only, any type if empty
+    pub types: Vec<OperationType>,
+}
+
+impl OperationsQuery {
+    #[must_use]
+    pub fn new(account_id: impl Into<String>) -> Self {
+        Self {
+            account_id: account_id.into(),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_figi(mut self, figi: impl Into<String>) -> Self {
+        self.figi = Some(figi.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_from(mut self, from: DateTime<Utc>) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    #[must_use]
+    pub fn with_to(mut self, to: DateTime<Utc>) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    /// Operations made within `period`
+    #[must_use]
+    pub fn with_period(mut self, period: Period) -> Self {
+        self.from = period.from;
+        self.to = period.to;
+        self
+    }
+
+    #[must_use]
+    pub fn with_types(mut self, types: impl IntoIterator<Item = OperationType>) -> Self {
+        self.types = types.into_iter().collect();
+        self
+    }
+
+    /// Whether `op` satisfies the instrument, date and type filters of the query
+    #[must_use]
+    pub fn matches(&self, op: &Operation) -> bool {
+        let date = to_datetime_utc(op.date.as_ref());
+        self.figi.as_ref().is_none_or(|figi| *figi == op.figi)
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date < to)
+            && (self.types.is_empty() || self.types.contains(&op.operation_type()))
+    }
+}
+
+/// One page of operations
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OperationsPage {
+    pub operations: Vec<Operation>,
+    /// Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Operations returned by the cursor endpoint have the same meaning as legacy ones
+fn to_operation(item: OperationItem) -> Operation {
+    Operation {
+        id: item.id,
+        parent_operation_id: item.parent_operation_id,
+        currency: item
+            .payment
+            .as_ref()
+            .map(|p| p.currency.clone())
+            .unwrap_or_default(),
+        payment: item.payment,
+        price: item.price,
+        state: item.state,
+        quantity: item.quantity,
+        quantity_rest: item.quantity_rest,
+        figi: item.figi,
+        instrument_type: item.instrument_type,
+        date: item.date,
+        r#type: item.name,
+        operation_type: item.r#type,
+        trades: Vec::new(),
+        asset_uid: item.asset_uid,
+        position_uid: item.position_uid,
+        instrument_uid: item.instrument_uid,
+    }
+}
+
+type ServiceChannel = InterceptedService<Channel, DefaultInterceptor>;
+
+/// Lazily opened gRPC channel shared by all clones of a client.
+///
+/// The channel is created on first use and dropped when the server
+/// turns out to be unreachable, so that the next call connects again.
+/// Typed service clients are thin wrappers over it and are created per call
+/// because the API interceptor cannot be cloned.
+#[derive(Default)]
+struct Connection {
+    channel: RwLock<Option<Channel>>,
+    /// Serializes connecting so that parallel calls open a single channel
+    connecting: Mutex<()>,
+}
+
+impl Connection {
+    async fn channel(&self, service: &TinkoffInvestService) -> color_eyre::Result<Channel> {
+        if let Some(channel) = self.current() {
+            return Ok(channel);
+        }
+        let _connecting = self.connecting.lock().await;
+        if let Some(channel) = self.current() {
+            return Ok(channel);
+        }
+        let channel = service
+            .create_channel()
+            .await
+            .map_err(|e| api_error("Failed to create channel", e))?;
+        *self.channel.write().unwrap_or_else(PoisonError::into_inner) = Some(channel.clone());
+        Ok(channel)
+    }
+
+    fn current(&self) -> Option<Channel> {
+        self.channel
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn reset(&self) {
+        *self.channel.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+macro_rules! collect {
+    ($response:ident) => {{
+        $response
+            .into_inner()
+            .instruments
+            .into_iter()
+            .map(|x| {
+                (
+                    x.figi,
+                    Instrument {
+                        name: x.name,
+                        ticker: Ticker::new(x.ticker),
+                        country: x.country_of_risk,
+                    },
+                )
+            })
+            .collect::<HashMap<String, Instrument>>()
+    }};
+}
+
+/// Tinkoff Invest API over gRPC
+#[derive(Clone)]
+pub struct GrpcApi {
+    service: Arc<TinkoffInvestService>,
+    connection: Arc<Connection>,
+}
+
+impl GrpcApi {
+    #[must_use]
+    pub fn new(token: String) -> Self {
+        Self {
+            service: Arc::new(TinkoffInvestService::new(token)),
+            connection: Arc::new(Connection::default()),
+        }
+    }
+
+    async fn instruments_service(
+        &self,
+    ) -> color_eyre::Result<InstrumentsServiceClient<ServiceChannel>> {
+        let channel = self.connection.channel(&self.service).await?;
+        self.service
+            .instruments(channel)
+            .await
+            .map_err(|e| api_error("Failed to get instruments service", e))
+    }
+
+    async fn operations_service(
+        &self,
+    ) -> color_eyre::Result<OperationsServiceClient<ServiceChannel>> {
+        let channel = self.connection.channel(&self.service).await?;
+        self.service
+            .operations(channel)
+            .await
+            .map_err(|e| api_error("Failed to get operations service", e))
+    }
+
+    async fn users_service(&self) -> color_eyre::Result<UsersServiceClient<ServiceChannel>> {
+        let channel = self.connection.channel(&self.service).await?;
+        self.service
+            .users(channel)
+            .await
+            .map_err(|e| api_error("Failed to get users service", e))
+    }
+
+    async fn marketdata_service(
+        &self,
+    ) -> color_eyre::Result<MarketDataServiceClient<ServiceChannel>> {
+        let channel = self.connection.channel(&self.service).await?;
+        self.service
+            .marketdata(channel)
+            .await
+            .map_err(|e| api_error("Failed to get market data service", e))
+    }
+
+    /// Turns a failed call into an error.
+    /// Drops the shared connection if the server is unreachable so that the next call reconnects.
+    fn call_failed(&self, context: &str, status: Status) -> eyre::Report {
+        if status.code() == Code::Unavailable {
+            self.connection.reset();
+        }
+        ApiError::new(context, &status).into()
+    }
+}
+
+impl InvestApi for GrpcApi {
+    fn accounts(&self) -> ApiFuture<'_, Vec<Account>> {
+        Box::pin(async move {
+            let mut users = self.users_service().await?;
+            let response = users
+                .get_accounts(GetAccountsRequest {})
+                .await
+                .map_err(|e| self.call_failed("Failed to get accounts", e))?;
+            Ok(response.into_inner().accounts)
+        })
+    }
+
+    fn portfolio(&self, account_id: String) -> ApiFuture<'_, Vec<PortfolioPosition>> {
+        Box::pin(async move {
+            let mut operations = self.operations_service().await?;
+            let response = operations
+                .get_portfolio(PortfolioRequest {
+                    account_id,
+                    currency: Some(CurrencyRequest::Rub as i32),
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to get portfolio", e))?;
+            Ok(response.into_inner().positions)
+        })
+    }
+
+    fn operations(
+        &self,
+        query: OperationsQuery,
+        cursor: Option<String>,
+    ) -> ApiFuture<'_, OperationsPage> {
+        Box::pin(async move {
+            let mut operations = self.operations_service().await?;
+            let response = operations
+                .get_operations_by_cursor(GetOperationsByCursorRequest {
+                    account_id: query.account_id,
+                    instrument_id: query.figi,
+                    from: query.from.map(to_timestamp),
+                    to: query.to.map(to_timestamp),
+                    cursor,
+                    limit: Some(OPERATIONS_PAGE_SIZE),
+                    operation_types: query.types.into_iter().map(|t| t as i32).collect(),
+                    state: Some(OperationState::Executed as i32),
+                    without_trades: Some(true),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to get operations", e))?
+                .into_inner();
+            Ok(OperationsPage {
+                operations: response.items.into_iter().map(to_operation).collect(),
+                next_cursor: response.has_next.then_some(response.next_cursor),
+            })
+        })
+    }
+
+    fn instrument_by_figi(&self, figi: String) -> ApiFuture<'_, Option<Instrument>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+            let response = match instruments
+                .get_instrument_by(InstrumentRequest {
+                    id_type: InstrumentIdType::Figi as i32,
+                    class_code: None,
+                    id: figi,
+                })
+                .await
+            {
+                Ok(response) => response,
+                // Unknown FIGI, any other failure is an error worth reporting
+                Err(status) if status.code() == Code::NotFound => return Ok(None),
+                Err(status) => return Err(self.call_failed("Failed to get instrument", status)),
+            };
+            Ok(response.into_inner().instrument.map(|x| Instrument {
+                name: x.name,
+                ticker: Ticker::new(x.ticker),
+                country: x.country_of_risk,
+            }))
+        })
+    }
+
+    fn instruments(
+        &self,
+        catalog: InstrumentCatalog,
+    ) -> ApiFuture<'_, HashMap<String, Instrument>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+
+            let request = InstrumentsRequest {
+                instrument_status: Some(InstrumentStatus::All as i32),
+                instrument_exchange: None,
+            };
+
+            match catalog {
+                InstrumentCatalog::Bonds => {
+                    let resp = instruments
+                        .bonds(request)
+                        .await
+                        .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+                    Ok(collect!(resp))
+                }
+                InstrumentCatalog::Shares => {
+                    let resp = instruments
+                        .shares(request)
+                        .await
+                        .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+                    Ok(collect!(resp))
+                }
+                InstrumentCatalog::Etfs => {
+                    let resp = instruments
+                        .etfs(request)
+                        .await
+                        .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+                    Ok(collect!(resp))
+                }
+                InstrumentCatalog::Futures => {
+                    let resp = instruments
+                        .futures(request)
+                        .await
+                        .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+                    Ok(collect!(resp))
+                }
+                InstrumentCatalog::Currencies => {
+                    let resp = instruments
+                        .currencies(request)
+                        .await
+                        .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+                    Ok(collect!(resp))
+                }
+            }
+        })
+    }
+
+    fn currencies(&self) -> ApiFuture<'_, Vec<CurrencyInstrument>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+            let resp = instruments
+                .currencies(InstrumentsRequest {
+                    instrument_status: Some(InstrumentStatus::All as i32),
+                    instrument_exchange: None,
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to fetch instruments", e))?;
+            Ok(resp.into_inner().instruments)
+        })
+    }
+
+    fn find_instruments(&self, query: String) -> ApiFuture<'_, Vec<InstrumentShort>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+            let response = instruments
+                .find_instrument(FindInstrumentRequest {
+                    instrument_kind: Some(InstrumentType::Unspecified.into()),
+                    query,
+                    api_trade_available_flag: Some(false),
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to find instruments", e))?;
+            Ok(response.into_inner().instruments)
+        })
+    }
+
+    fn last_prices(&self, figis: Vec<String>) -> ApiFuture<'_, Vec<LastPrice>> {
+        Box::pin(async move {
+            let mut marketdata = self.marketdata_service().await?;
+            let response = marketdata
+                .get_last_prices(GetLastPricesRequest {
+                    instrument_id: figis,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to get last prices", e))?;
+            Ok(response.into_inner().last_prices)
+        })
+    }
+
+    fn dividends(&self, figi: String) -> ApiFuture<'_, Vec<Dividend>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+            let response = instruments
+                .get_dividends(GetDividendsRequest {
+                    instrument_id: figi,
+                    from: None,
+                    to: None,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to get dividends", e))?;
+            Ok(response.into_inner().dividends)
+        })
+    }
+
+    fn coupons(&self, figi: String) -> ApiFuture<'_, Vec<Coupon>> {
+        Box::pin(async move {
+            let mut instruments = self.instruments_service().await?;
+            let response = instruments
+                .get_bond_coupons(GetBondCouponsRequest {
+                    instrument_id: figi,
+                    from: None,
+                    to: None,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| self.call_failed("Failed to get coupons", e))?;
+            Ok(response.into_inner().events)
+        })
+    }
+}
@@ -0,0 +1,209 @@
+//! On-disk cache of instrument catalogs.
+//!
+//! Each catalog is kept in its own JSON file together with the time it was
+//! downloaded, so routine runs can skip the Instruments API until the entry
+//! gets older than the configured TTL.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::{self, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::domain::Instrument;
+
+/// How long a downloaded catalog is used before it is requested again
+pub const DEFAULT_CATALOG_TTL: TimeDelta = TimeDelta::hours(24);
+
+const APP_DIR: &str = "tinkoff";
+
+/// Instrument catalog saved to disk
+#[derive(Serialize, Deserialize)]
+pub struct CachedCatalog {
+    pub fetched_at: DateTime<Utc>,
+    pub instruments: HashMap<String, Instrument>,
+}
+
+impl CachedCatalog {
+    #[must_use]
+    pub fn new(instruments: HashMap<String, Instrument>) -> Self {
+        Self {
+            fetched_at: Utc::now(),
+            instruments,
+        }
+    }
+
+    /// Whether the catalog is younger than `ttl` at `now`
+    #[must_use]
+    pub fn is_fresh(&self, ttl: TimeDelta, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(self.fetched_at) < ttl
+    }
+}
+
+/// Directory with cached instrument catalogs
+pub struct CatalogCache {
+    dir: PathBuf,
+    ttl: TimeDelta,
+    refresh: bool,
+}
+
+impl CatalogCache {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>, ttl: TimeDelta) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+            refresh: false,
+        }
+    }
+
+    /// Cache directory of the application inside the user's cache directory
+    /// (for example `~/.cache/tinkoff` on Linux)
+    #[must_use]
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|d| d.join(APP_DIR))
+    }
+
+    /// Ignore fresh entries and download catalogs again.
+    /// Cached entries are still used when the download fails.
+    #[must_use]
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Returns the catalog `name` if it is cached and not expired.
+    /// Always `None` in refresh mode.
+    #[must_use]
+    pub fn fresh(&self, name: &str) -> Option<CachedCatalog> {
+        if self.refresh {
+            return None;
+        }
+        self.load(name).filter(|c| c.is_fresh(self.ttl, Utc::now()))
+    }
+
+    /// Returns the catalog `name` regardless of its age.
+    /// Missing or unreadable files are treated as absent.
+    #[must_use]
+    pub fn load(&self, name: &str) -> Option<CachedCatalog> {
+        let content = fs::read(self.path(name)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Saves the catalog `name`, replacing the previous entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory or file cannot be written.
+    pub fn store(&self, name: &str, catalog: &CachedCatalog) -> color_eyre::Result<()> {
+        let content = serde_json::to_vec(catalog)?;
+        write_atomically(&self.path(name), &content)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("instruments-{name}.json"))
+    }
+}
+
+/// Writes `content` to `path`, creating missing directories.
+///
+/// The file is written aside and renamed so that concurrent readers
+/// never see a half written file.
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> color_eyre::Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, content).wrap_err_with(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        eyre::eyre!("Failed to write {}: {e}", path.display())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Ticker;
+
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tinkoff-cache-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn catalog(fetched_at: DateTime<Utc>) -> CachedCatalog {
+        let mut instruments = HashMap::new();
+        instruments.insert(
+            "BBG004730N88".to_owned(),
+            Instrument {
+                name: "Сбер Банк".to_owned(),
+                ticker: Ticker::new("SBER"),
+                country: "RU".to_owned(),
+            },
+        );
+        CachedCatalog {
+            fetched_at,
+            instruments,
+        }
+    }
+
+    #[test]
+    fn store_and_load_round_trip() {
+        let dir = cache_dir("round-trip");
+        let cache = CatalogCache::new(&dir, DEFAULT_CATALOG_TTL);
+
+        cache.store("shares", &catalog(Utc::now())).unwrap();
+        let loaded = cache.fresh("shares").unwrap();
+
+        let sber = &loaded.instruments["BBG004730N88"];
+        assert_eq!(sber.ticker.as_str(), "SBER");
+        assert_eq!(sber.name, "Сбер Банк");
+        assert!(cache.load("bonds").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expired_entry_is_only_loaded_as_stale() {
+        let dir = cache_dir("expired");
+        let cache = CatalogCache::new(&dir, DEFAULT_CATALOG_TTL);
+        let old = Utc::now() - DEFAULT_CATALOG_TTL - TimeDelta::minutes(1);
+
+        cache.store("bonds", &catalog(old)).unwrap();
+
+        assert!(cache.fresh("bonds").is_none());
+        assert!(cache.load("bonds").is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refresh_ignores_fresh_entries() {
+        let dir = cache_dir("refresh");
+        let cache = CatalogCache::new(&dir, DEFAULT_CATALOG_TTL).refresh(true);
+
+        cache.store("etfs", &catalog(Utc::now())).unwrap();
+
+        assert!(cache.fresh("etfs").is_none());
+        assert!(cache.load("etfs").is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn is_fresh_compares_age_with_ttl() {
+        let now = Utc::now();
+        let cached = catalog(now - TimeDelta::hours(2));
+
+        assert!(cached.is_fresh(TimeDelta::hours(3), now));
+        assert!(!cached.is_fresh(TimeDelta::hours(1), now));
+    }
+}
@@ -1,38 +1,48 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Months, Utc};
 use color_eyre::eyre;
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
 use iso_currency::Currency;
-use std::collections::HashMap;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::future::Future;
+use std::ops::Deref;
+use std::pin::pin;
 use std::sync::Arc;
-use tinkoff_invest_api::{
-    TinkoffInvestService,
-    tcs::{
-        Account, AccountType, Coupon, Dividend, FindInstrumentRequest, GetAccountsRequest,
-        GetAccountsResponse, GetBondCouponsRequest, GetDividendsRequest, InstrumentShort,
-        InstrumentStatus, InstrumentType, InstrumentsRequest, Operation, OperationState,
-        OperationType, OperationsRequest, PortfolioPosition, PortfolioRequest,
-        portfolio_request::CurrencyRequest,
-    },
+use tinkoff_invest_api::tcs::{
+    Account, AccountStatus, AccountType, Currency as CurrencyInstrument, InstrumentShort,
+    Operation, OperationType, PortfolioPosition,
 };
-use tokio::sync::Semaphore;
+use tokio::sync::{OnceCell, Semaphore};
 use tokio::task::JoinSet;
-use tokio::time::{Duration, sleep};
 
 use crate::{
+    account_type_name,
+    api::{GrpcApi, InvestApi, OperationsQuery},
+    cache::{CachedCatalog, CatalogCache},
     client::InstrumentCatalog::{Bonds, Currencies, Etfs, Futures, Shares},
     domain::{
-        CouponCalendar, CouponPayment, CouponProfit, DividendCalendar, DividendPayment,
-        DividendProfit, Figi, Instrument, LoadedPaper, Money, NoneProfit, Paper, Portfolio,
-        Position, Profit, Ticker, Totals,
+        AccountOperations, CouponCalendar, CouponPayment, CouponProfit, DividendCalendar,
+        DividendPayment, DividendProfit, Figi, FxRates, Instrument, LdvPosition, LdvReport,
+        LoadedPaper, Money, MoneyBag, NoneProfit, OperationKind, Paper, Portfolio, Position,
+        Profit, Returns, SkippedPosition, TaxLots, Ticker, Totals,
         calendar::{CalendarPayment, CombinedCalendar, CombinedPayment},
+        fx::BASE_CURRENCY,
+        skipped::extend_unique,
     },
     progress::Progress,
+    retry::RetryPolicy,
     to_currency, to_datetime_utc, to_decimal, to_money,
 };
 
 /// Maximum number of concurrent API requests when loading portfolio positions or calendars.
 pub const MAX_CONCURRENT_REQUESTS: usize = 10;
 
+/// Number of positions from which [`TinkoffInvestment::build_portfolio`] loads
+/// operations of the whole account at once instead of requesting them per position.
+pub const ACCOUNT_OPERATIONS_THRESHOLD: usize = 20;
+
 /// Builder for calendar queries with fluent API.
 pub struct CalendarBuilder<'a> {
     client: &'a TinkoffInvestment,
@@ -80,7 +90,7 @@ impl<'a> CalendarBuilder<'a> {
     pub async fn fetch(
         self,
         portfolio: &AccountPortfolio,
-        instruments: Arc<HashMap<String, Instrument>>,
+        instruments: Arc<Instruments>,
     ) -> color_eyre::Result<CombinedCalendar> {
         let now = if self.filter_future_dates {
             Some(chrono::Utc::now())
@@ -89,6 +99,7 @@ impl<'a> CalendarBuilder<'a> {
         };
 
         let mut payments = Vec::new();
+        let mut skipped = Vec::new();
 
         if self.include_dividends {
             let dividend_calendar = self
@@ -98,6 +109,7 @@ impl<'a> CalendarBuilder<'a> {
             for dividend in dividend_calendar.upcoming {
                 payments.push(CombinedPayment::Dividend(dividend));
             }
+            extend_unique(&mut skipped, &dividend_calendar.skipped);
         }
 
         if self.include_coupons {
@@ -108,10 +120,14 @@ impl<'a> CalendarBuilder<'a> {
             for coupon in coupon_calendar.upcoming {
                 payments.push(CombinedPayment::Coupon(coupon));
             }
+            extend_unique(&mut skipped, &coupon_calendar.skipped);
         }
 
         payments.sort_by_key(CalendarPayment::payment_date);
-        Ok(CombinedCalendar { upcoming: payments })
+        Ok(CombinedCalendar {
+            upcoming: payments,
+            skipped,
+        })
     }
 }
 
@@ -126,6 +142,18 @@ pub enum InstrumentCatalog {
 }
 
 impl InstrumentCatalog {
+    /// Catalog name used for cache entries
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Bonds => "bonds",
+            Self::Shares => "shares",
+            Self::Etfs => "etfs",
+            Self::Futures => "futures",
+            Self::Currencies => "currencies",
+        }
+    }
+
     #[must_use]
     pub const fn instrument_type(self) -> &'static str {
         match self {
@@ -141,19 +169,166 @@ impl InstrumentCatalog {
         self,
         client: &TinkoffInvestment,
     ) -> color_eyre::Result<HashMap<String, Instrument>> {
-        with_retry(|| client.get_instruments(self)).await
+        let Some(cache) = client.catalog_cache.as_deref() else {
+            return client.retry.run(|| client.get_instruments(self)).await;
+        };
+        if let Some(cached) = cache.fresh(self.name()) {
+            return Ok(cached.instruments);
+        }
+        match client.retry.run(|| client.get_instruments(self)).await {
+            Ok(instruments) => {
+                let catalog = CachedCatalog::new(instruments);
+                if let Err(e) = cache.store(self.name(), &catalog) {
+                    eprintln!("Failed to cache {} catalog: {e}", self.name());
+                }
+                Ok(catalog.instruments)
+            }
+            Err(e) => {
+                let Some(stale) = cache.load(self.name()) else {
+                    return Err(e);
+                };
+                eprintln!(
+                    "Failed to fetch {} catalog, using cached copy from {}: {e}",
+                    self.name(),
+                    stale.fetched_at.format("%Y-%m-%d %H:%M UTC"),
+                );
+                Ok(stale.instruments)
+            }
+        }
     }
 }
 
+/// Where instrument names and tickers of portfolio positions come from.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InstrumentSource {
+    /// Look up only instruments held in the portfolio, one request per FIGI
+    #[default]
+    Positions,
+    /// Download whole instrument catalogs (cached on disk when a cache is set)
+    Catalogs,
+}
+
+/// Which accounts of the user a command works with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AccountSelector {
+    /// The first account of the given type
+    /// (or the first account of the user if there is none of this type)
+    Type(AccountType),
+    /// The account with the given id
+    Id(String),
+    /// Every account that is not closed
+    All,
+}
+
+impl AccountSelector {
+    /// Whether several accounts may be selected
+    #[must_use]
+    pub const fn is_all(&self) -> bool {
+        matches!(self, Self::All)
+    }
+
+    /// Picks the selected accounts from all accounts of the user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no account with the requested id.
+    pub fn select(&self, accounts: Vec<Account>) -> color_eyre::Result<SelectedAccounts> {
+        match self {
+            Self::Type(account_type) => {
+                let of_type = accounts.iter().position(|a| a.r#type() == *account_type);
+                let fallback = of_type.is_none() && !accounts.is_empty();
+                let index = of_type.unwrap_or_default();
+                Ok(SelectedAccounts {
+                    accounts: accounts.into_iter().skip(index).take(1).collect(),
+                    fallback,
+                })
+            }
+            Self::Id(id) => accounts
+                .into_iter()
+                .find(|a| a.id == *id)
+                .map(|a| SelectedAccounts {
+                    accounts: vec![a],
+                    fallback: false,
+                })
+                .ok_or_else(|| eyre::eyre!("Account '{id}' not found")),
+            Self::All => Ok(SelectedAccounts {
+                accounts: accounts
+                    .into_iter()
+                    .filter(|a| a.status() != AccountStatus::Closed)
+                    .collect(),
+                fallback: false,
+            }),
+        }
+    }
+}
+
+/// Accounts picked by an [`AccountSelector`]
+#[derive(Debug, Default)]
+pub struct SelectedAccounts {
+    pub accounts: Vec<Account>,
+    /// Set when there is no account of the requested type
+    /// and the first account of the user is read instead
+    pub fallback: bool,
+}
+
 #[derive(Default)]
 pub struct AccountPortfolio {
     pub account_id: String,
+    pub account_name: String,
     pub positions: Vec<PortfolioPosition>,
 }
 
+/// Instruments by FIGI along with the errors lookups of other FIGIs failed with
+#[derive(Clone, Default)]
+pub struct Instruments {
+    found: HashMap<String, Instrument>,
+    /// Lookup errors by FIGI
+    failed: HashMap<String, String>,
+}
+
+impl Instruments {
+    /// Instrument of `figi`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instrument is unknown or its lookup failed.
+    pub fn require(&self, figi: &str) -> color_eyre::Result<&Instrument> {
+        self.found
+            .get(figi)
+            .ok_or_else(|| match self.failed.get(figi) {
+                Some(error) => eyre::eyre!("Instrument lookup failed: {error}"),
+                None => eyre::eyre!("Instrument not found"),
+            })
+    }
+}
+
+impl From<HashMap<String, Instrument>> for Instruments {
+    fn from(found: HashMap<String, Instrument>) -> Self {
+        Self {
+            found,
+            failed: HashMap::new(),
+        }
+    }
+}
+
+impl Deref for Instruments {
+    type Target = HashMap<String, Instrument>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.found
+    }
+}
+
 #[derive(Clone)]
 pub struct TinkoffInvestment {
-    service: Arc<TinkoffInvestService>,
+    api: Arc<dyn InvestApi>,
+    retry: RetryPolicy,
+    /// Currencies catalog, loaded once and shared by instrument lookups and exchange rates
+    currencies: Arc<OnceCell<Vec<CurrencyInstrument>>>,
+    /// On-disk cache of instrument catalogs, if enabled
+    catalog_cache: Option<Arc<CatalogCache>>,
+    instrument_source: InstrumentSource,
+    account_operations_threshold: usize,
 }
 
 enum OperationInfluence {
@@ -161,36 +336,102 @@ enum OperationInfluence {
     /// Including negative values like dividend tax etc. to calculate pure income<br/>
     /// without taxes.
     PureIncome,
-    /// Comissions and other losses
+    /// Comissions and other losses, operations of [`OperationKind::Fee`]
     Fees,
     Unspecified,
 }
 
+/// Operation types of [`OperationInfluence::PureIncome`]
+const PURE_INCOME_OPERATIONS: [OperationType; 10] = [
+    OperationType::DividendTax,
+    OperationType::DividendTaxProgressive,
+    OperationType::BondTax,
+    OperationType::BondTaxProgressive,
+    OperationType::Coupon,
+    OperationType::BenefitTax,
+    OperationType::BenefitTaxProgressive,
+    OperationType::Overnight,
+    OperationType::Tax,
+    OperationType::Dividend,
+];
+
 #[must_use]
 fn to_influence(op: OperationType) -> OperationInfluence {
-    match op {
-        tinkoff_invest_api::tcs::OperationType::DividendTax
-        | tinkoff_invest_api::tcs::OperationType::DividendTaxProgressive
-        | tinkoff_invest_api::tcs::OperationType::BondTax
-        | tinkoff_invest_api::tcs::OperationType::BondTaxProgressive
-        | tinkoff_invest_api::tcs::OperationType::Coupon
-        | tinkoff_invest_api::tcs::OperationType::BenefitTax
-        | tinkoff_invest_api::tcs::OperationType::BenefitTaxProgressive
-        | tinkoff_invest_api::tcs::OperationType::Overnight
-        | tinkoff_invest_api::tcs::OperationType::Tax
-        | tinkoff_invest_api::tcs::OperationType::Dividend => OperationInfluence::PureIncome,
-        tinkoff_invest_api::tcs::OperationType::ServiceFee
-        | tinkoff_invest_api::tcs::OperationType::MarginFee
-        | tinkoff_invest_api::tcs::OperationType::BrokerFee
-        | tinkoff_invest_api::tcs::OperationType::SuccessFee
-        | tinkoff_invest_api::tcs::OperationType::TrackMfee
-        | tinkoff_invest_api::tcs::OperationType::TrackPfee
-        | tinkoff_invest_api::tcs::OperationType::CashFee
-        | tinkoff_invest_api::tcs::OperationType::OutFee
-        | tinkoff_invest_api::tcs::OperationType::OutStampDuty
-        | tinkoff_invest_api::tcs::OperationType::AdviceFee
-        | tinkoff_invest_api::tcs::OperationType::OutputPenalty => OperationInfluence::Fees,
-        _ => OperationInfluence::Unspecified,
+    if PURE_INCOME_OPERATIONS.contains(&op) {
+        OperationInfluence::PureIncome
+    } else if OperationKind::of(op) == OperationKind::Fee {
+        OperationInfluence::Fees
+    } else {
+        OperationInfluence::Unspecified
+    }
+}
+
+/// Operations of the account that affect [`Totals`]
+fn totals_query(account_id: impl Into<String>) -> OperationsQuery {
+    let kinds = [OperationKind::Fee, OperationKind::Buy, OperationKind::Sell];
+    OperationsQuery::new(account_id).with_types(
+        PURE_INCOME_OPERATIONS.into_iter().chain(
+            kinds
+                .into_iter()
+                .flat_map(|kind| kind.operation_types().iter().copied()),
+        ),
+    )
+}
+
+/// Income and fees summed operation by operation, operations kept to match sales
+/// with purchases and to compute returns
+#[derive(Default)]
+struct TotalsBuilder {
+    fees: MoneyBag,
+    additional_profit: MoneyBag,
+    operations: Vec<Operation>,
+}
+
+impl TotalsBuilder {
+    fn add(&mut self, op: &Operation) {
+        let Some(payment) = to_money(op.payment.as_ref()) else {
+            return;
+        };
+        match to_influence(op.operation_type()) {
+            OperationInfluence::PureIncome => {
+                self.additional_profit += payment;
+            }
+            OperationInfluence::Fees => {
+                self.fees += payment;
+            }
+            OperationInfluence::Unspecified => {}
+        }
+        self.operations.push(op.clone());
+    }
+
+    /// Totals in the `fx` reporting currency, returns up to the `current` value of the position
+    /// and income over the 12 months before `now`.
+    /// Payments in currencies without a known rate are left out.
+    fn build(&self, fx: &FxRates, current: Money, now: DateTime<Utc>) -> Totals {
+        let year_ago = now.checked_sub_months(Months::new(12)).unwrap_or(now);
+        let trailing_income: MoneyBag = self
+            .operations
+            .iter()
+            .filter(|op| {
+                matches!(
+                    to_influence(op.operation_type()),
+                    OperationInfluence::PureIncome
+                )
+            })
+            .filter(|op| to_datetime_utc(op.date.as_ref()) > year_ago)
+            .filter_map(|op| to_money(op.payment.as_ref()))
+            .collect();
+        let lots = TaxLots::new(&self.operations);
+        Totals {
+            additional_profit: fx.total(&self.additional_profit),
+            fees: fx.total(&self.fees),
+            realized: fx.total(&lots.realized()),
+            returns: Returns::of_position(&self.operations, current, fx, now),
+            // Units sold out before are not held any longer
+            first_bought: lots.open.first().map(|lot| lot.acquired),
+            trailing_income: fx.total(&trailing_income),
+            today: now,
+        }
     }
 }
 
@@ -218,58 +459,54 @@ impl TryFrom<&PortfolioPosition> for Position {
     }
 }
 
-macro_rules! collect {
-    ($response:ident) => {{
-        $response
-            .into_inner()
-            .instruments
-            .into_iter()
-            .map(|x| {
-                (
-                    x.figi.clone(),
-                    Instrument {
-                        name: x.name.clone(),
-                        ticker: Ticker::new(x.ticker.clone()),
-                    },
-                )
-            })
-            .collect::<HashMap<String, Instrument>>()
-    }};
-}
-
-/// Executes a future with exponential backoff retry logic.
-///
-/// Retries up to 5 times with delays: 100ms, 200ms, 400ms, 800ms, 1600ms.
-/// Returns the error from the last attempt if all retries fail.
-async fn with_retry<T, F, Fut>(f: F) -> color_eyre::Result<T>
-where
-    F: Fn() -> Fut,
-    Fut: std::future::Future<Output = color_eyre::Result<T>>,
-{
-    let mut delay = Duration::from_millis(100);
-    for attempt in 1..=5 {
-        match f().await {
-            Ok(v) => return Ok(v),
-            Err(e) if attempt == 5 => {
-                return Err(eyre::eyre!("Operation failed after 5 attempts: {e:?}"));
-            }
-            Err(_) => {
-                sleep(delay).await;
-                delay *= 2;
-            }
-        }
-    }
-    unreachable!()
-}
-
 impl TinkoffInvestment {
     #[must_use]
     pub fn new(token: String) -> Self {
+        Self::with_api(Arc::new(GrpcApi::new(token)))
+    }
+
+    /// Creates a client reading data from `api` instead of the Tinkoff Invest API.
+    #[must_use]
+    pub fn with_api(api: Arc<dyn InvestApi>) -> Self {
         Self {
-            service: Arc::new(TinkoffInvestService::new(token)),
+            api,
+            retry: RetryPolicy::default(),
+            currencies: Arc::new(OnceCell::new()),
+            catalog_cache: None,
+            instrument_source: InstrumentSource::default(),
+            account_operations_threshold: ACCOUNT_OPERATIONS_THRESHOLD,
         }
     }
 
+    /// Sets how failed API calls are retried.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets where instruments of portfolio positions are loaded from.
+    #[must_use]
+    pub fn with_instrument_source(mut self, source: InstrumentSource) -> Self {
+        self.instrument_source = source;
+        self
+    }
+
+    /// Sets the number of positions from which portfolio operations are loaded
+    /// for the whole account at once, see [`ACCOUNT_OPERATIONS_THRESHOLD`].
+    #[must_use]
+    pub fn with_account_operations_threshold(mut self, threshold: usize) -> Self {
+        self.account_operations_threshold = threshold;
+        self
+    }
+
+    /// Reads instrument catalogs through `cache`.
+    #[must_use]
+    pub fn with_catalog_cache(mut self, cache: CatalogCache) -> Self {
+        self.catalog_cache = Some(Arc::new(cache));
+        self
+    }
+
     /// Fetches all instrument catalogs in parallel and merges them by FIGI.
     ///
     /// # Errors
@@ -298,107 +535,217 @@ impl TinkoffInvestment {
         &self,
         catalog: InstrumentCatalog,
     ) -> color_eyre::Result<HashMap<String, Instrument>> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("Failed to create channel: {e:?}"))?;
-        let mut instruments = self
-            .service
-            .instruments(channel)
-            .await
-            .map_err(|e| eyre::eyre!("Failed to get instruments service: {e:?}"))?;
+        match catalog {
+            Currencies => Ok(self
+                .currency_instruments()
+                .await?
+                .iter()
+                .map(|x| {
+                    (
+                        x.figi.clone(),
+                        Instrument {
+                            name: x.name.clone(),
+                            ticker: Ticker::new(x.ticker.clone()),
+                            country: x.country_of_risk.clone(),
+                        },
+                    )
+                })
+                .collect()),
+            _ => self.api.instruments(catalog).await,
+        }
+    }
 
-        let request = InstrumentsRequest {
-            instrument_status: Some(InstrumentStatus::All as i32),
-            instrument_exchange: None,
-        };
+    /// Returns the currencies catalog, loading it on first use.
+    async fn currency_instruments(&self) -> color_eyre::Result<&[CurrencyInstrument]> {
+        let currencies = self
+            .currencies
+            .get_or_try_init(|| self.api.currencies())
+            .await?;
+        Ok(currencies)
+    }
 
-        match catalog {
-            Bonds => {
-                let resp = instruments
-                    .bonds(request)
-                    .await
-                    .map_err(|e| eyre::eyre!("Failed to fetch instruments: {e:?}"))?;
-                Ok(collect!(resp))
-            }
-            Shares => {
-                let resp = instruments
-                    .shares(request)
-                    .await
-                    .map_err(|e| eyre::eyre!("Failed to fetch instruments: {e:?}"))?;
-                Ok(collect!(resp))
-            }
-            Etfs => {
-                let resp = instruments
-                    .etfs(request)
-                    .await
-                    .map_err(|e| eyre::eyre!("Failed to fetch instruments: {e:?}"))?;
-                Ok(collect!(resp))
-            }
-            Futures => {
-                let resp = instruments
-                    .futures(request)
-                    .await
-                    .map_err(|e| eyre::eyre!("Failed to fetch instruments: {e:?}"))?;
-                Ok(collect!(resp))
-            }
-            Currencies => {
-                let resp = instruments
-                    .currencies(request)
-                    .await
-                    .map_err(|e| eyre::eyre!("Failed to fetch instruments: {e:?}"))?;
-                Ok(collect!(resp))
+    /// Loads exchange rates needed to convert `positions` into `reporting_currency`.
+    ///
+    /// Rates are last prices of ruble-settled currency instruments
+    /// from the currencies catalog. Nothing is requested when all positions
+    /// and the reporting currency are rubles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the catalog or last prices cannot be loaded after retries.
+    pub async fn get_fx_rates(
+        &self,
+        positions: &[PortfolioPosition],
+        reporting_currency: Currency,
+    ) -> color_eyre::Result<FxRates> {
+        let mut fx = FxRates::new(reporting_currency);
+
+        let mut needed: HashSet<Currency> = positions
+            .iter()
+            .filter_map(|p| to_currency(&p.current_price))
+            .collect();
+        needed.insert(reporting_currency);
+        needed.remove(&BASE_CURRENCY);
+        if needed.is_empty() {
+            return Ok(fx);
+        }
+
+        let currencies = self.retry.run(|| self.currency_instruments()).await?;
+        let instruments = fx_instruments(currencies, &needed);
+        if instruments.is_empty() {
+            return Ok(fx);
+        }
+
+        let figis = instruments.keys().cloned().collect::<Vec<_>>();
+        let prices = self
+            .retry
+            .run(|| self.api.last_prices(figis.clone()))
+            .await?;
+        for price in prices {
+            let Some((currency, nominal)) = instruments.get(&price.figi) else {
+                continue;
+            };
+            let price = to_decimal(price.price.as_ref());
+            if !nominal.is_zero() {
+                fx.insert(*currency, price / nominal);
             }
         }
+        Ok(fx)
     }
 
-    /// Loads the portfolio and all instrument catalogs concurrently.
+    /// Loads portfolios of the selected accounts and instruments of their positions.
+    ///
+    /// Depending on the [`InstrumentSource`] of the client, either only held
+    /// instruments are looked up once portfolios are loaded or all instrument
+    /// catalogs are downloaded concurrently with portfolios.
     ///
     /// # Errors
     ///
-    /// Returns an error if the portfolio or any catalog request fails after retries.
+    /// Returns an error if a portfolio or any catalog request fails after retries.
     pub async fn get_portfolio_and_instruments(
         &self,
-        account: AccountType,
-    ) -> color_eyre::Result<(AccountPortfolio, HashMap<String, Instrument>)> {
+        accounts: &AccountSelector,
+    ) -> color_eyre::Result<(Vec<AccountPortfolio>, Instruments)> {
         Box::pin(async {
-            let (instruments, portfolio) = tokio::join!(
-                self.get_all_instruments_until_done(),
-                self.get_portfolio_until_done(account),
-            );
-            Ok((portfolio?, instruments?))
+            match self.instrument_source {
+                InstrumentSource::Positions => {
+                    let portfolios = self.get_portfolios_until_done(accounts).await?;
+                    let instruments = self.get_held_instruments(&portfolios, |_| true).await;
+                    Ok((portfolios, instruments))
+                }
+                InstrumentSource::Catalogs => {
+                    let (instruments, portfolios) = tokio::join!(
+                        self.get_all_instruments_until_done(),
+                        self.get_portfolios_until_done(accounts),
+                    );
+                    Ok((portfolios?, instruments?.into()))
+                }
+            }
         })
         .await
     }
 
-    /// Loads the portfolio and one instrument catalog concurrently.
+    /// Loads portfolios of the selected accounts and instruments of one catalog.
+    ///
+    /// Depending on the [`InstrumentSource`] of the client, either only held
+    /// instruments of the catalog type are looked up or the whole catalog is downloaded.
     ///
     /// # Errors
     ///
-    /// Returns an error if the portfolio or catalog request fails after retries.
+    /// Returns an error if a portfolio or catalog request fails after retries.
     pub async fn get_portfolio_and_catalog(
         &self,
-        account: AccountType,
+        accounts: &AccountSelector,
         catalog: InstrumentCatalog,
-    ) -> color_eyre::Result<(AccountPortfolio, HashMap<String, Instrument>)> {
-        let (instruments, portfolio) = tokio::join!(
-            catalog.fetch_until_done(self),
-            self.get_portfolio_until_done(account),
-        );
-        Ok((portfolio?, instruments?))
+    ) -> color_eyre::Result<(Vec<AccountPortfolio>, Instruments)> {
+        match self.instrument_source {
+            InstrumentSource::Positions => {
+                let portfolios = self.get_portfolios_until_done(accounts).await?;
+                let instruments = self
+                    .get_held_instruments(&portfolios, |p| {
+                        p.instrument_type == catalog.instrument_type()
+                    })
+                    .await;
+                Ok((portfolios, instruments))
+            }
+            InstrumentSource::Catalogs => {
+                let (instruments, portfolios) = tokio::join!(
+                    catalog.fetch_until_done(self),
+                    self.get_portfolios_until_done(accounts),
+                );
+                Ok((portfolios?, instruments?.into()))
+            }
+        }
+    }
+
+    /// Looks up instruments of positions matching `filter` in all `portfolios`
+    /// with [`TinkoffInvestment::get_instruments_by_figi`].
+    async fn get_held_instruments<F>(
+        &self,
+        portfolios: &[AccountPortfolio],
+        filter: F,
+    ) -> Instruments
+    where
+        F: Fn(&PortfolioPosition) -> bool,
+    {
+        let figis = portfolios
+            .iter()
+            .flat_map(|p| &p.positions)
+            .filter(|p| filter(p))
+            .map(|p| p.figi.clone())
+            .collect_vec();
+        self.get_instruments_by_figi(figis).await
+    }
+
+    /// Looks up instruments by FIGI.
+    ///
+    /// Each FIGI is requested once, in parallel under [`MAX_CONCURRENT_REQUESTS`].
+    /// Instruments that cannot be found are left out, lookups that fail after retries
+    /// are kept with their error.
+    pub async fn get_instruments_by_figi(
+        &self,
+        figis: impl IntoIterator<Item = String>,
+    ) -> Instruments {
+        let figis = figis
+            .into_iter()
+            .filter(|figi| !figi.is_empty())
+            .unique()
+            .collect_vec();
+
+        let lookups = self
+            .parallel_for(&figis, None, |client, figi| async move {
+                let instrument = client
+                    .retry
+                    .run(|| client.api.instrument_by_figi(figi.clone()))
+                    .await;
+                (figi, instrument)
+            })
+            .await;
+        let mut instruments = Instruments::default();
+        for (figi, instrument) in lookups {
+            match instrument {
+                Ok(Some(instrument)) => {
+                    instruments.found.insert(figi, instrument);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    instruments.failed.insert(figi, format!("{e:#}"));
+                }
+            }
+        }
+        instruments
     }
 
     /// Fetches data for each position in parallel,
     /// limiting concurrent requests with a semaphore.
     ///
-    /// Returns pairs of (position, list of items). Positions with errors
-    /// are skipped (empty vector), and task panics are logged to stderr.
+    /// Returns pairs of (position, list of items or the error it failed with).
+    /// Task panics are logged to stderr.
     async fn fetch_parallel<T, F, Fut>(
         &self,
         positions: &[PortfolioPosition],
         fetch: F,
-    ) -> Vec<(PortfolioPosition, Vec<T>)>
+    ) -> Vec<(PortfolioPosition, color_eyre::Result<Vec<T>>)>
     where
         T: Send + 'static,
         F: Fn(Self, String) -> Fut + Send + Sync + 'static,
@@ -410,7 +757,7 @@ impl TinkoffInvestment {
                 let figi = position.figi.clone();
                 let fetch = Arc::clone(&fetch);
                 async move {
-                    let items = fetch(client, figi).await.unwrap_or_default();
+                    let items = fetch(client, figi).await;
                     (position, items)
                 }
             }
@@ -432,12 +779,31 @@ impl TinkoffInvestment {
         T: Send + 'static,
         F: Fn(TinkoffInvestment, PortfolioPosition) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = T> + Send + 'static,
+    {
+        self.parallel_for(positions, progress, task).await
+    }
+
+    /// Runs `task` for each item concurrently, limited by [`MAX_CONCURRENT_REQUESTS`].
+    ///
+    /// Task panics are logged to stderr; failed permit acquisition skips the item.
+    /// When `progress` is set, it is incremented once per completed task.
+    async fn parallel_for<I, T, F, Fut>(
+        &self,
+        items: &[I],
+        progress: Option<Arc<dyn Progress>>,
+        task: F,
+    ) -> Vec<T>
+    where
+        I: Clone + Send + 'static,
+        T: Send + 'static,
+        F: Fn(TinkoffInvestment, I) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
     {
         let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
         let task = Arc::new(task);
         let mut set = JoinSet::new();
 
-        for position in positions {
+        for item in items {
             let client = self.clone();
             let permit = match semaphore.clone().acquire_owned().await {
                 Ok(p) => p,
@@ -446,13 +812,13 @@ impl TinkoffInvestment {
                     continue;
                 }
             };
-            let position = position.clone();
+            let item = item.clone();
             let task = Arc::clone(&task);
             let progress = progress.clone();
 
             set.spawn(async move {
                 let _permit = permit;
-                let result = task(client, position).await;
+                let result = task(client, item).await;
                 if let Some(p) = &progress {
                     p.progress();
                 }
@@ -470,27 +836,77 @@ impl TinkoffInvestment {
         results
     }
 
-    /// Builds a [`Portfolio`] by loading papers for each position in parallel.
+    /// Builds a [`Portfolio`] of the `account` positions by loading papers for each of them
+    /// in parallel. Portfolio totals are converted using `fx` rates.
+    /// Positions that fail to load are recorded in [`Portfolio::skipped`].
+    ///
+    /// With `account_returns`, when the positions are all positions of the account, every
+    /// operation of the account is loaded once for totals of papers and [`Portfolio::returns`]
+    /// alike. Otherwise from [`ACCOUNT_OPERATIONS_THRESHOLD`] positions on operations of the
+    /// account are loaded in one pass. If that fails they are requested per position.
     pub async fn build_portfolio(
         &self,
-        instruments: Arc<HashMap<String, Instrument>>,
-        positions: &[PortfolioPosition],
-        account_id: &str,
+        instruments: Arc<Instruments>,
+        account: &AccountPortfolio,
         output_papers: bool,
+        account_returns: bool,
+        fx: FxRates,
         progress: Option<Arc<dyn Progress>>,
     ) -> Portfolio {
-        let account_id = account_id.to_string();
+        let account_id = account.account_id.clone();
+        let positions = &account.positions;
+        let fx = Arc::new(fx);
+        let requested_per_position = |e: color_eyre::Report| {
+            eprintln!(
+                "Failed to load operations of account {account_id}, requesting them per position: {e:#}"
+            );
+            (None, None)
+        };
+        let (all_operations, operations) = if account_returns {
+            match self
+                .get_operations(OperationsQuery::new(account_id.clone()))
+                .await
+            {
+                Ok(all) => {
+                    let types = totals_query(&account_id).types;
+                    let operations: AccountOperations = all
+                        .iter()
+                        .filter(|op| types.contains(&op.operation_type()))
+                        .cloned()
+                        .collect();
+                    (Some(all), Some(Arc::new(operations)))
+                }
+                Err(e) => requested_per_position(e),
+            }
+        } else if positions.len() >= self.account_operations_threshold {
+            match self.get_account_operations(totals_query(&account_id)).await {
+                Ok(operations) => (None, Some(Arc::new(operations))),
+                Err(e) => requested_per_position(e),
+            }
+        } else {
+            (None, None)
+        };
 
         let papers = self
             .parallel_for_positions(positions, progress.clone(), {
                 let account_id = account_id.clone();
+                let fx = fx.clone();
                 move |client, position| {
                     let instruments = instruments.clone();
                     let account_id = account_id.clone();
+                    let fx = fx.clone();
+                    let operations = operations.clone();
                     async move {
                         client
-                            .paper_for_position(&instruments, &account_id, &position)
+                            .paper_for_position(
+                                &instruments,
+                                &account_id,
+                                &position,
+                                &fx,
+                                operations.as_deref(),
+                            )
                             .await
+                            .map_err(|e| skipped_position(&position, &instruments, e))
                     }
                 }
             })
@@ -500,25 +916,37 @@ impl TinkoffInvestment {
             p.finish();
         }
 
-        let mut portfolio = Portfolio::new(output_papers);
-        for paper in papers.into_iter().flatten() {
-            portfolio.add_loaded_paper(paper);
+        let fx = Arc::try_unwrap(fx).unwrap_or_else(|fx| (*fx).clone());
+        let mut portfolio = Portfolio::with_fx_rates(output_papers, fx);
+        for paper in papers {
+            match paper {
+                Ok(paper) => portfolio.add_loaded_paper(paper),
+                Err(skipped) => portfolio.add_skipped(skipped),
+            }
+        }
+        // Returns of the account are left unknown if its operations cannot be loaded
+        if let Some(operations) = all_operations {
+            portfolio.add_operations(operations);
         }
         portfolio
     }
 
     async fn paper_for_position(
         &self,
-        instruments: &HashMap<String, Instrument>,
+        instruments: &Instruments,
         account_id: &str,
         position: &PortfolioPosition,
-    ) -> Option<LoadedPaper> {
+        fx: &FxRates,
+        operations: Option<&AccountOperations>,
+    ) -> color_eyre::Result<LoadedPaper> {
         match position.instrument_type.as_str() {
             "bond" => self
                 .create_paper_from_position(
                     instruments,
                     account_id.to_string(),
                     position,
+                    fx,
+                    operations,
                     CouponProfit,
                 )
                 .await
@@ -528,6 +956,8 @@ impl TinkoffInvestment {
                     instruments,
                     account_id.to_string(),
                     position,
+                    fx,
+                    operations,
                     DividendProfit,
                 )
                 .await
@@ -537,6 +967,8 @@ impl TinkoffInvestment {
                     instruments,
                     account_id.to_string(),
                     position,
+                    fx,
+                    operations,
                     DividendProfit,
                 )
                 .await
@@ -546,6 +978,8 @@ impl TinkoffInvestment {
                     instruments,
                     account_id.to_string(),
                     position,
+                    fx,
+                    operations,
                     NoneProfit,
                 )
                 .await
@@ -555,84 +989,71 @@ impl TinkoffInvestment {
                     instruments,
                     account_id.to_string(),
                     position,
+                    fx,
+                    operations,
                     NoneProfit,
                 )
                 .await
                 .map(LoadedPaper::Future),
-            _ => None,
+            other => Err(eyre::eyre!("Unsupported instrument type '{other}'")),
         }
     }
 
-    async fn get_portfolio(&self, account: AccountType) -> color_eyre::Result<AccountPortfolio> {
-        let (accounts_res, ops_res) = tokio::join!(self.get_accounts_response(), async {
-            let ch = self
-                .service
-                .create_channel()
-                .await
-                .map_err(|e| eyre::eyre!("Failed to create channel: {e:?}"))?;
-            self.service
-                .operations(ch)
-                .await
-                .map_err(|e| eyre::eyre!("Failed to get operations service: {e:?}"))
-        },);
-
-        let accounts_res = accounts_res?;
-        let mut operations = ops_res?;
-
-        let Some(account) = accounts_res.accounts.iter().find(|a| a.r#type() == account) else {
-            return Ok(AccountPortfolio::default());
-        };
-
-        let portfolio = operations
-            .get_portfolio(PortfolioRequest {
-                account_id: account.id.clone(),
-                currency: Some(CurrencyRequest::Rub as i32),
-            })
-            .await
-            .map_err(|e| eyre::eyre!("Failed to get portfolio: {e:?}"))?;
+    async fn get_portfolio(&self, account: &Account) -> color_eyre::Result<AccountPortfolio> {
+        let positions = self.api.portfolio(account.id.clone()).await?;
         Ok(AccountPortfolio {
             account_id: account.id.clone(),
-            positions: portfolio.into_inner().positions,
+            account_name: account.name.clone(),
+            positions,
         })
     }
 
-    /// Get accounts response from API.
+    /// Get the selected accounts. Prints a warning if the first account
+    /// is read because there is no account of the requested type.
     ///
     /// # Errors
     ///
-    /// This function will return an error if accounts cannot be retrieved.
-    async fn get_accounts_response(&self) -> color_eyre::Result<GetAccountsResponse> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let mut users = self
-            .service
-            .users(channel)
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let accounts = users
-            .get_accounts(GetAccountsRequest {})
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        Ok(accounts.into_inner())
+    /// This function will return an error if accounts cannot be retrieved after multiple retries
+    /// or there is no account with the requested id.
+    pub async fn get_accounts(
+        &self,
+        accounts: &AccountSelector,
+    ) -> color_eyre::Result<Vec<Account>> {
+        let selected = accounts.select(self.get_all_accounts().await?)?;
+        if let (AccountSelector::Type(account_type), true, Some(account)) =
+            (accounts, selected.fallback, selected.accounts.first())
+        {
+            eprintln!(
+                "No {} account found, reading account {} ({}) instead",
+                account_type_name(*account_type),
+                account.name,
+                account.id
+            );
+        }
+        Ok(selected.accounts)
     }
 
-    /// Get an account by type.
+    /// Get an account by type. If there is no account of this type
+    /// the first account is returned and a warning is printed.
     ///
     /// # Errors
     ///
     /// This function will return an error if account cannot be get.
     pub async fn get_account(&self, account_type: AccountType) -> color_eyre::Result<Account> {
-        let accounts = &self.get_accounts_response().await?;
-        let account = accounts
-            .accounts
-            .iter()
-            .find(|a| a.r#type() == account_type)
-            .or_else(|| accounts.accounts.first())
-            .ok_or_else(|| eyre::eyre!("No accounts found"))?;
-        Ok(account.clone())
+        self.get_accounts(&AccountSelector::Type(account_type))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("No accounts found"))
+    }
+
+    /// Get every account the token can reach, including closed ones.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if accounts cannot be retrieved after multiple retries.
+    pub async fn get_all_accounts(&self) -> color_eyre::Result<Vec<Account>> {
+        self.retry.run(|| self.api.accounts()).await
     }
 
     /// Search instruments by ticker.
@@ -644,68 +1065,91 @@ impl TinkoffInvestment {
         &self,
         ticker: String,
     ) -> color_eyre::Result<Vec<InstrumentShort>> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let mut instruments = self
-            .service
-            .instruments(channel)
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let instrument = instruments
-            .find_instrument(FindInstrumentRequest {
-                instrument_kind: Some(InstrumentType::Unspecified.into()),
-                query: ticker,
-                api_trade_available_flag: Some(false),
-            })
-            .await?;
-        Ok(instrument.get_ref().instruments.clone())
+        self.api.find_instruments(ticker).await
     }
 
-    /// Get portfolio until done with retry logic.
+    /// Get portfolios of the selected accounts with retry logic.
+    /// If the user has no accounts the single portfolio is empty.
     ///
     /// # Errors
     ///
-    /// This function will return an error if portfolio cannot be retrieved after multiple retries.
-    pub async fn get_portfolio_until_done(
+    /// This function will return an error if a portfolio cannot be retrieved after multiple retries.
+    pub async fn get_portfolios_until_done(
         &self,
-        account: AccountType,
-    ) -> color_eyre::Result<AccountPortfolio> {
-        with_retry(|| self.get_portfolio(account)).await
+        accounts: &AccountSelector,
+    ) -> color_eyre::Result<Vec<AccountPortfolio>> {
+        let accounts = self.get_accounts(accounts).await?;
+        if accounts.is_empty() {
+            return Ok(vec![AccountPortfolio::default()]);
+        }
+        let mut portfolios = Vec::with_capacity(accounts.len());
+        for account in &accounts {
+            portfolios.push(self.retry.run(|| self.get_portfolio(account)).await?);
+        }
+        Ok(portfolios)
     }
 
-    async fn get_operations(
+    /// Executed operations matching `query`, newest first.
+    ///
+    /// Operations are requested page by page as the stream is polled,
+    /// every page with retry logic.
+    pub fn operations(
         &self,
-        account_id: String,
-        figi: String,
-    ) -> color_eyre::Result<Vec<Operation>> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("Failed to create channel: {e:?}"))?;
-        let mut operations = self
-            .service
-            .operations(channel)
-            .await
-            .map_err(|e| eyre::eyre!("Failed to get operations service: {e:?}"))?;
-        let operations = operations
-            .get_operations(OperationsRequest {
-                account_id,
-                from: None,
-                to: None,
-                state: Some(OperationState::Executed as i32),
-                figi: Some(figi),
-            })
-            .await
-            .map_err(|e| eyre::eyre!("Failed to get operations: {e:?}"))?;
+        query: OperationsQuery,
+    ) -> impl Stream<Item = color_eyre::Result<Operation>> + Send + '_ {
+        // The state is the cursor of the page to request next, `None` after the last page
+        stream::try_unfold(Some(None), move |cursor: Option<Option<String>>| {
+            let query = query.clone();
+            async move {
+                let Some(cursor) = cursor else {
+                    return Ok::<_, eyre::Report>(None);
+                };
+                let page = self
+                    .retry
+                    .run(|| self.api.operations(query.clone(), cursor.clone()))
+                    .await?;
+                let operations = stream::iter(page.operations).map(Ok);
+                // An empty or repeated cursor would request the same page forever
+                let next = page
+                    .next_cursor
+                    .filter(|next| !next.is_empty() && cursor.as_ref() != Some(next));
+                Ok(Some((operations, next.map(Some))))
+            }
+        })
+        .try_flatten()
+    }
 
-        Ok(operations.into_inner().operations)
+    /// Executed operations of the account matching `query` grouped by instrument,
+    /// loaded page by page in a single pass.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a page cannot be retrieved after multiple retries.
+    pub async fn get_account_operations(
+        &self,
+        query: OperationsQuery,
+    ) -> color_eyre::Result<AccountOperations> {
+        let mut operations = pin!(self.operations(query));
+        let mut account_operations = AccountOperations::new();
+        while let Some(op) = operations.try_next().await? {
+            account_operations.add(op);
+        }
+        Ok(account_operations)
     }
 
-    /// Get operations until done with retry logic.
+    /// Get all executed operations matching `query`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if operations cannot be retrieved after multiple retries.
+    pub async fn get_operations(
+        &self,
+        query: OperationsQuery,
+    ) -> color_eyre::Result<Vec<Operation>> {
+        self.operations(query).try_collect().await
+    }
+
+    /// Get all executed operations of the account with the instrument.
     ///
     /// # Errors
     ///
@@ -715,27 +1159,46 @@ impl TinkoffInvestment {
         account_id: String,
         figi: String,
     ) -> color_eyre::Result<Vec<Operation>> {
-        with_retry(|| self.get_operations(account_id.clone(), figi.clone())).await
+        self.get_operations(OperationsQuery::new(account_id).with_figi(figi))
+            .await
     }
 
+    /// Creates a paper of the position. Its totals are computed from `operations`
+    /// if operations of the account are preloaded, otherwise they are requested.
     pub async fn create_paper_from_position<P: Profit>(
         &self,
-        instruments: &HashMap<String, Instrument>,
+        instruments: &Instruments,
         account_id: String,
         portfolio_position: &PortfolioPosition,
+        fx: &FxRates,
+        operations: Option<&AccountOperations>,
         profit: P,
-    ) -> Option<Paper<P>> {
-        let position = Position::try_from(portfolio_position).ok()?;
-
-        let executed_ops = self
-            .get_operations_until_done(account_id, portfolio_position.figi.clone())
-            .await;
-        let executed_ops = executed_ops.ok()?;
-
-        let totals = Self::reduce(&executed_ops, position.currency);
+    ) -> color_eyre::Result<Paper<P>> {
+        let instrument = instruments.require(&portfolio_position.figi)?;
+        let position = Position::try_from(portfolio_position)?;
+
+        let mut totals = TotalsBuilder::default();
+        if let Some(operations) = operations {
+            operations
+                .get(&portfolio_position.figi)
+                .iter()
+                .for_each(|op| totals.add(op));
+        } else {
+            // Only income, fees and trades affect totals
+            let query = totals_query(account_id).with_figi(portfolio_position.figi.clone());
+            let mut operations = pin!(self.operations(query));
+            while let Some(op) = operations.try_next().await? {
+                totals.add(&op);
+            }
+        }
+        let current = position.current_instrument_price * position.quantity;
+        let totals = totals.build(
+            &fx.with_reporting_currency(position.currency),
+            current,
+            Utc::now(),
+        );
 
-        let instrument = instruments.get(&portfolio_position.figi)?;
-        Some(Paper {
+        Ok(Paper {
             name: instrument.name.clone(),
             ticker: instrument.ticker.clone(),
             figi: Figi::new(portfolio_position.figi.clone()),
@@ -745,30 +1208,6 @@ impl TinkoffInvestment {
         })
     }
 
-    #[must_use]
-    fn reduce(operations: &[Operation], currency: iso_currency::Currency) -> Totals {
-        let mut fees = Money::zero(currency);
-        let mut additional_profit = Money::zero(currency);
-        for op in operations {
-            let Some(payment) = crate::to_money(op.payment.as_ref()) else {
-                continue;
-            };
-            match to_influence(op.operation_type()) {
-                OperationInfluence::PureIncome => {
-                    additional_profit += payment;
-                }
-                OperationInfluence::Fees => {
-                    fees += payment;
-                }
-                OperationInfluence::Unspecified => {}
-            }
-        }
-        Totals {
-            additional_profit,
-            fees,
-        }
-    }
-
     /// Creates a new calendar builder for fluent API.
     #[must_use]
     pub fn calendar(&self) -> CalendarBuilder<'_> {
@@ -779,28 +1218,48 @@ impl TinkoffInvestment {
     async fn get_dividend_calendar(
         &self,
         portfolio: &AccountPortfolio,
-        instruments: Arc<HashMap<String, Instrument>>,
+        instruments: Arc<Instruments>,
         filter_after: Option<DateTime<Utc>>,
     ) -> color_eyre::Result<DividendCalendar> {
         let instruments = instruments.clone();
 
+        // Only shares and funds pay dividends
+        let dividend_positions: Vec<PortfolioPosition> = portfolio
+            .positions
+            .iter()
+            .filter(|p| p.instrument_type == "share" || p.instrument_type == "etf")
+            .cloned()
+            .collect();
+
         let pairs = self
-            .fetch_parallel(&portfolio.positions, |client, figi| async move {
-                client.get_dividends_for_figi(figi).await
+            .fetch_parallel(&dividend_positions, |client, figi| async move {
+                client.api.dividends(figi).await
             })
             .await;
 
         let mut upcoming = Vec::new();
+        let mut skipped = Vec::new();
         for (position, dividends) in pairs {
-            let Some(instrument) = instruments.get(&position.figi) else {
-                continue;
+            let instrument = match instruments.require(&position.figi) {
+                Ok(instrument) => instrument,
+                Err(e) => {
+                    skipped.push(skipped_position(&position, &instruments, e));
+                    continue;
+                }
+            };
+            let dividends = match dividends {
+                Ok(dividends) => dividends,
+                Err(e) => {
+                    skipped.push(skipped_position(&position, &instruments, e));
+                    continue;
+                }
             };
             for dividend in dividends {
                 let dividend_per_share = dividend
                     .dividend_net
                     .as_ref()
                     .and_then(|d| to_money(Some(d)))
-                    .unwrap_or_else(|| Money::zero(Currency::RUB));
+                    .unwrap_or_else(|| Money::zero(position_currency(&position)));
 
                 let payment_date = dividend
                     .payment_date
@@ -835,37 +1294,14 @@ impl TinkoffInvestment {
         }
 
         upcoming.sort_by_key(|a| a.ex_dividend_date);
-        Ok(DividendCalendar { upcoming })
-    }
-
-    async fn get_dividends_for_figi(&self, figi: String) -> color_eyre::Result<Vec<Dividend>> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let mut instruments = self
-            .service
-            .instruments(channel)
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let response = instruments
-            .get_dividends(GetDividendsRequest {
-                instrument_id: figi,
-                from: None,
-                to: None,
-                ..Default::default()
-            })
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        Ok(response.into_inner().dividends)
+        Ok(DividendCalendar { upcoming, skipped })
     }
 
     /// Internal method for fetching coupon calendar with optional date filtering.
     async fn get_coupon_calendar(
         &self,
         portfolio: &AccountPortfolio,
-        instruments: Arc<HashMap<String, Instrument>>,
+        instruments: Arc<Instruments>,
         filter_after: Option<DateTime<Utc>>,
     ) -> color_eyre::Result<CouponCalendar> {
         let instruments = instruments.clone();
@@ -880,21 +1316,33 @@ impl TinkoffInvestment {
 
         let pairs = self
             .fetch_parallel(&bond_positions, |client, figi| async move {
-                client.get_coupons_for_figi(figi).await
+                client.api.coupons(figi).await
             })
             .await;
 
         let mut upcoming = Vec::new();
+        let mut skipped = Vec::new();
         for (position, coupons) in pairs {
-            let Some(instrument) = instruments.get(&position.figi) else {
-                continue;
+            let instrument = match instruments.require(&position.figi) {
+                Ok(instrument) => instrument,
+                Err(e) => {
+                    skipped.push(skipped_position(&position, &instruments, e));
+                    continue;
+                }
+            };
+            let coupons = match coupons {
+                Ok(coupons) => coupons,
+                Err(e) => {
+                    skipped.push(skipped_position(&position, &instruments, e));
+                    continue;
+                }
             };
             for coupon in coupons {
                 let coupon_value = coupon
                     .pay_one_bond
                     .as_ref()
                     .and_then(|d| to_money(Some(d)))
-                    .unwrap_or_else(|| Money::zero(Currency::RUB));
+                    .unwrap_or_else(|| Money::zero(position_currency(&position)));
 
                 let coupon_date = coupon
                     .coupon_date
@@ -923,31 +1371,139 @@ impl TinkoffInvestment {
         }
 
         upcoming.sort_by_key(|a| a.coupon_date);
-        Ok(CouponCalendar { upcoming })
+        Ok(CouponCalendar { upcoming, skipped })
     }
+}
 
-    async fn get_coupons_for_figi(&self, figi: String) -> color_eyre::Result<Vec<Coupon>> {
-        let channel = self
-            .service
-            .create_channel()
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let mut instruments = self
-            .service
-            .instruments(channel)
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        let response = instruments
-            .get_bond_coupons(GetBondCouponsRequest {
-                instrument_id: figi,
-                from: None,
-                to: None,
-                ..Default::default()
+/// Security types long-term ownership benefit applies to
+const LDV_INSTRUMENT_TYPES: [&str; 3] = ["share", "bond", "etf"];
+
+impl TinkoffInvestment {
+    /// Long-term ownership benefit state of securities held in `portfolio` at `today`.
+    /// Lots are reconstructed from buys and sells of the account loaded in one pass.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if operations cannot be retrieved after multiple retries.
+    pub async fn get_ldv_report(
+        &self,
+        portfolio: &AccountPortfolio,
+        instruments: &Instruments,
+        today: DateTime<Utc>,
+    ) -> color_eyre::Result<LdvReport> {
+        let kinds = [OperationKind::Buy, OperationKind::Sell];
+        let query = OperationsQuery::new(portfolio.account_id.clone()).with_types(
+            kinds
+                .into_iter()
+                .flat_map(|kind| kind.operation_types().iter().copied()),
+        );
+        let operations = self.get_account_operations(query).await?;
+        // The yearly cap of the exempt gain is set in rubles
+        let fx = self
+            .get_fx_rates(&portfolio.positions, Currency::RUB)
+            .await?;
+
+        let mut positions = Vec::new();
+        let mut skipped = Vec::new();
+        for position in portfolio
+            .positions
+            .iter()
+            .filter(|p| LDV_INSTRUMENT_TYPES.contains(&p.instrument_type.as_str()))
+        {
+            let instrument = match instruments.require(&position.figi) {
+                Ok(instrument) => instrument,
+                Err(e) => {
+                    skipped.push(skipped_position(position, instruments, e));
+                    continue;
+                }
+            };
+            let held = match Position::try_from(position) {
+                Ok(held) => held,
+                Err(e) => {
+                    skipped.push(skipped_position(position, instruments, e));
+                    continue;
+                }
+            };
+            positions.push(
+                LdvPosition::new(
+                    instrument.name.clone(),
+                    instrument.ticker.to_string(),
+                    position.figi.clone(),
+                    held.quantity,
+                    held.current_instrument_price,
+                    &TaxLots::new(operations.get(&position.figi)),
+                    today,
+                )
+                .with_fx(fx.clone()),
+            );
+        }
+        Ok(LdvReport {
+            today,
+            positions,
+            skipped,
+        })
+    }
+}
+
+/// Describes a position left out of a report because of `reason`
+fn skipped_position(
+    position: &PortfolioPosition,
+    instruments: &HashMap<String, Instrument>,
+    reason: impl fmt::Display,
+) -> SkippedPosition {
+    SkippedPosition::new(
+        Figi::new(position.figi.clone()),
+        instruments.get(&position.figi).map(|i| i.ticker.clone()),
+        position.instrument_type.clone(),
+        format!("{reason:#}"),
+    )
+}
+
+/// Currency the position is quoted in, rubles if the API did not report it
+fn position_currency(position: &PortfolioPosition) -> Currency {
+    to_currency(&position.current_price).unwrap_or(BASE_CURRENCY)
+}
+
+/// Picks one ruble-settled instrument per needed currency, preferring `TOM` settlement.
+///
+/// Returns FIGI mapped to the currency and the nominal its price is quoted for.
+fn fx_instruments(
+    currencies: &[CurrencyInstrument],
+    needed: &HashSet<Currency>,
+) -> HashMap<String, (Currency, Decimal)> {
+    let mut chosen: HashMap<Currency, &CurrencyInstrument> = HashMap::new();
+    for instrument in currencies {
+        if !instrument
+            .currency
+            .eq_ignore_ascii_case(BASE_CURRENCY.code())
+        {
+            continue;
+        }
+        let Some(currency) =
+            Currency::from_code(&instrument.iso_currency_name.to_ascii_uppercase())
+        else {
+            continue;
+        };
+        if !needed.contains(&currency) {
+            continue;
+        }
+        let is_tom = instrument.ticker.ends_with("TOM");
+        chosen
+            .entry(currency)
+            .and_modify(|c| {
+                if is_tom && !c.ticker.ends_with("TOM") {
+                    *c = instrument;
+                }
             })
-            .await
-            .map_err(|e| eyre::eyre!("{e:?}"))?;
-        Ok(response.into_inner().events)
+            .or_insert(instrument);
     }
+    chosen
+        .into_iter()
+        .map(|(currency, instrument)| {
+            let nominal = to_money(instrument.nominal.as_ref()).map_or(Decimal::ONE, |n| n.value);
+            (instrument.figi.clone(), (currency, nominal))
+        })
+        .collect()
 }
 
 #[must_use]
@@ -963,3 +1519,474 @@ fn coupon_type_to_str(coupon_type: tinkoff_invest_api::tcs::CouponType) -> &'sta
         tinkoff_invest_api::tcs::CouponType::Other => "Other",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(id: &str, account_type: AccountType, status: AccountStatus) -> Account {
+        Account {
+            id: id.to_string(),
+            r#type: account_type as i32,
+            status: status as i32,
+            ..Default::default()
+        }
+    }
+
+    fn accounts() -> Vec<Account> {
+        vec![
+            account("1", AccountType::Tinkoff, AccountStatus::Open),
+            account("2", AccountType::TinkoffIis, AccountStatus::Open),
+            account("3", AccountType::Tinkoff, AccountStatus::Open),
+            account("4", AccountType::Tinkoff, AccountStatus::Closed),
+        ]
+    }
+
+    fn ids(selected: &SelectedAccounts) -> Vec<&str> {
+        selected.accounts.iter().map(|a| a.id.as_str()).collect()
+    }
+
+    #[test]
+    fn select_by_type_takes_first_account() {
+        let selected = AccountSelector::Type(AccountType::Tinkoff)
+            .select(accounts())
+            .unwrap();
+
+        assert_eq!(ids(&selected), ["1"]);
+        assert!(!selected.fallback);
+    }
+
+    #[test]
+    fn select_by_missing_type_falls_back_to_first_account() {
+        let selected = AccountSelector::Type(AccountType::InvestBox)
+            .select(accounts())
+            .unwrap();
+
+        assert_eq!(ids(&selected), ["1"]);
+        assert!(selected.fallback);
+
+        let none = AccountSelector::Type(AccountType::InvestBox)
+            .select(vec![])
+            .unwrap();
+        assert!(none.accounts.is_empty());
+        assert!(!none.fallback);
+    }
+
+    #[test]
+    fn select_by_id() {
+        let selected = AccountSelector::Id("3".to_string())
+            .select(accounts())
+            .unwrap();
+
+        assert_eq!(ids(&selected), ["3"]);
+        assert!(
+            AccountSelector::Id("5".to_string())
+                .select(accounts())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn select_all_skips_closed_accounts() {
+        let selected = AccountSelector::All.select(accounts()).unwrap();
+
+        assert_eq!(ids(&selected), ["1", "2", "3"]);
+    }
+
+    fn paged_client() -> TinkoffInvestment {
+        let fixture: crate::fake::Fixture = serde_json::from_str(
+            r#"{
+                "operations": {"1": [
+                    {"id": "a", "figi": "BBG004730N88", "type": "buy", "date": "2023-05-10T10:00:00Z", "payment": "-2500", "price": "250", "quantity": 10},
+                    {"id": "b", "figi": "BBG004730N88", "type": "broker-fee", "date": "2023-05-10T10:00:00Z", "payment": "-7.5"},
+                    {"id": "c", "figi": "BBG004730N88", "type": "dividend", "date": "2024-07-10T10:00:00Z", "payment": "330"},
+                    {"id": "d", "figi": "BBG004730N88", "type": "dividend-tax", "date": "2024-07-10T10:00:00Z", "payment": "-43"},
+                    {"id": "e", "figi": "BBG00R0SHYW6", "type": "coupon", "date": "2024-08-01T10:00:00Z", "payment": "35.4"}
+                ]}
+            }"#,
+        )
+        .unwrap();
+        let api = crate::fake::FakeApi::new(fixture)
+            .unwrap()
+            .with_page_size(2);
+        TinkoffInvestment::with_api(Arc::new(api))
+    }
+
+    async fn operation_ids(client: &TinkoffInvestment, query: OperationsQuery) -> Vec<String> {
+        client
+            .operations(query)
+            .map_ok(|op| op.id)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn operations_stream_reads_every_page() {
+        let client = paged_client();
+
+        let all = operation_ids(&client, OperationsQuery::new("1")).await;
+        let sber = client
+            .get_operations_until_done("1".to_string(), "BBG004730N88".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(all, ["a", "b", "c", "d", "e"]);
+        assert_eq!(sber.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn operations_stream_stops_on_empty_or_repeated_cursor() {
+        let dir = std::env::temp_dir().join(format!("tinkoff-cursor-{}", std::process::id()));
+        let page = |id: &str, next_cursor: &str| {
+            format!(
+                r#"{{"operations": [{{"id": "{id}", "figi": "BBG004730N88", "type": "dividend", "date": "2024-07-10T10:00:00Z", "payment": "330"}}], "next_cursor": "{next_cursor}"}}"#
+            )
+        };
+        for (path, content) in [
+            ("operations/1/all/first.json", page("a", "x")),
+            ("operations/1/all/cursor-x.json", page("b", "x")),
+            ("operations/2/all/first.json", page("c", "")),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let api = crate::record::ReplayApi::new(&dir).unwrap();
+        let client = TinkoffInvestment::with_api(Arc::new(api));
+
+        let repeated = operation_ids(&client, OperationsQuery::new("1")).await;
+        let empty = operation_ids(&client, OperationsQuery::new("2")).await;
+
+        assert_eq!(repeated, ["a", "b"]);
+        assert_eq!(empty, ["c"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn operations_stream_applies_bounds_and_types() {
+        let client = paged_client();
+        let year_2024 = OperationsQuery::new("1")
+            .with_from("2024-01-01T00:00:00Z".parse().unwrap())
+            .with_to("2025-01-01T00:00:00Z".parse().unwrap());
+
+        let in_year = operation_ids(&client, year_2024.clone()).await;
+        let income = operation_ids(
+            &client,
+            year_2024.with_types([OperationType::Dividend, OperationType::Coupon]),
+        )
+        .await;
+
+        assert_eq!(in_year, ["c", "d", "e"]);
+        assert_eq!(income, ["c", "e"]);
+    }
+
+    #[test]
+    fn totals_sum_income_and_fees() {
+        let op = |operation_type: OperationType, payment: Decimal| Operation {
+            operation_type: operation_type as i32,
+            payment: Some(crate::to_money_value(payment, "rub")),
+            ..Default::default()
+        };
+        let mut totals = TotalsBuilder::default();
+
+        totals.add(&op(OperationType::Buy, Decimal::new(-2500, 0)));
+        totals.add(&op(OperationType::BrokerFee, Decimal::new(-75, 1)));
+        totals.add(&op(OperationType::Dividend, Decimal::new(330, 0)));
+        totals.add(&op(OperationType::DividendTax, Decimal::new(-43, 0)));
+        let totals = totals.build(&FxRates::default(), Money::zero(Currency::RUB), Utc::now());
+
+        assert_eq!(totals.additional_profit.value, Decimal::new(287, 0));
+        assert_eq!(totals.fees.value, Decimal::new(-75, 1));
+    }
+
+    #[test]
+    fn totals_keep_oldest_open_lot_and_income_of_last_year() {
+        let op =
+            |id: &str, operation_type: OperationType, date: &str, payment: Decimal| Operation {
+                id: id.to_string(),
+                currency: "rub".to_string(),
+                operation_type: operation_type as i32,
+                date: Some(crate::to_timestamp(date.parse().unwrap())),
+                payment: Some(crate::to_money_value(payment, "rub")),
+                ..Default::default()
+            };
+        let trade = |id, operation_type, date, payment, quantity| Operation {
+            quantity,
+            ..op(id, operation_type, date, payment)
+        };
+        let mut totals = TotalsBuilder::default();
+
+        totals.add(&trade(
+            "1",
+            OperationType::Buy,
+            "2024-03-01T10:00:00Z",
+            Decimal::new(-1500, 0),
+            5,
+        ));
+        totals.add(&trade(
+            "2",
+            OperationType::Buy,
+            "2021-02-01T10:00:00Z",
+            Decimal::new(-900, 0),
+            3,
+        ));
+        totals.add(&trade(
+            "3",
+            OperationType::Sell,
+            "2021-09-01T10:00:00Z",
+            Decimal::new(1000, 0),
+            3,
+        ));
+        totals.add(&trade(
+            "4",
+            OperationType::Buy,
+            "2022-05-01T10:00:00Z",
+            Decimal::new(-1000, 0),
+            4,
+        ));
+        totals.add(&op(
+            "5",
+            OperationType::Dividend,
+            "2024-07-10T10:00:00Z",
+            Decimal::new(200, 0),
+        ));
+        totals.add(&op(
+            "6",
+            OperationType::Dividend,
+            "2025-07-10T10:00:00Z",
+            Decimal::new(300, 0),
+        ));
+        totals.add(&op(
+            "7",
+            OperationType::DividendTax,
+            "2025-07-10T10:00:00Z",
+            Decimal::new(-39, 0),
+        ));
+        let totals = totals.build(
+            &FxRates::default(),
+            Money::zero(Currency::RUB),
+            "2026-01-01T00:00:00Z".parse().unwrap(),
+        );
+
+        // Units bought in 2021 were sold out, the holding period starts with the next purchase
+        assert_eq!(
+            totals.first_bought,
+            Some("2022-05-01T10:00:00Z".parse().unwrap())
+        );
+        assert_eq!(totals.trailing_income.value, Decimal::new(261, 0));
+        assert_eq!(totals.additional_profit.value, Decimal::new(461, 0));
+    }
+
+    #[tokio::test]
+    async fn portfolio_totals_are_the_same_with_account_operations() {
+        let fixture: crate::fake::Fixture = serde_json::from_str(
+            r#"{
+                "portfolios": {"1": [
+                    {"figi": "BBG004730N88", "instrument_type": "share", "quantity": "10", "average_price": "250", "current_price": "280"},
+                    {"figi": "BBG004731032", "instrument_type": "share", "quantity": "2", "average_price": "7000", "current_price": "7100"}
+                ]},
+                "operations": {"1": [
+                    {"id": "f", "figi": "", "type": "input", "date": "2023-05-01T10:00:00Z", "payment": "20000"},
+                    {"id": "a", "figi": "BBG004730N88", "type": "buy", "date": "2023-05-10T10:00:00Z", "payment": "-2500", "price": "250", "quantity": 10},
+                    {"id": "b", "figi": "BBG004730N88", "type": "broker-fee", "date": "2023-05-10T10:00:00Z", "payment": "-7.5"},
+                    {"id": "c", "figi": "BBG004730N88", "type": "dividend", "date": "2024-07-10T10:00:00Z", "payment": "330"},
+                    {"id": "d", "figi": "BBG004731032", "type": "dividend", "date": "2024-07-20T10:00:00Z", "payment": "996"},
+                    {"id": "e", "figi": "BBG004731032", "type": "dividend-tax", "date": "2024-07-20T10:00:00Z", "payment": "-129"}
+                ]}
+            }"#,
+        )
+        .unwrap();
+        let api: Arc<dyn InvestApi> = Arc::new(
+            crate::fake::FakeApi::new(fixture)
+                .unwrap()
+                .with_page_size(2),
+        );
+        let positions = api.portfolio("1".to_string()).await.unwrap();
+        let instruments = Arc::new(Instruments::from(HashMap::from([
+            (
+                "BBG004730N88".to_string(),
+                Instrument {
+                    name: "Сбер Банк".to_string(),
+                    ticker: Ticker::new("SBER".to_string()),
+                    country: "RU".to_string(),
+                },
+            ),
+            (
+                "BBG004731032".to_string(),
+                Instrument {
+                    name: "ЛУКОЙЛ".to_string(),
+                    ticker: Ticker::new("LKOH".to_string()),
+                    country: "RU".to_string(),
+                },
+            ),
+        ])));
+        let build = |threshold, account_returns| {
+            let client = TinkoffInvestment::with_api(Arc::clone(&api))
+                .with_account_operations_threshold(threshold);
+            let instruments = Arc::clone(&instruments);
+            let account = AccountPortfolio {
+                account_id: "1".to_string(),
+                account_name: String::new(),
+                positions: positions.clone(),
+            };
+            async move {
+                client
+                    .build_portfolio(
+                        instruments,
+                        &account,
+                        true,
+                        account_returns,
+                        FxRates::default(),
+                        None,
+                    )
+                    .await
+            }
+        };
+
+        let per_position = build(usize::MAX, false).await;
+        let per_account = build(1, false).await;
+        let whole_account = build(usize::MAX, true).await;
+
+        let totals = |portfolio: &Portfolio| {
+            portfolio
+                .shares
+                .papers()
+                .iter()
+                .map(|p| {
+                    (
+                        p.figi.to_string(),
+                        p.totals.additional_profit.value,
+                        p.totals.fees.value,
+                    )
+                })
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(totals(&per_account), totals(&per_position));
+        assert_eq!(totals(&whole_account), totals(&per_position));
+        assert_eq!(per_account.shares.dividends().value, Decimal::new(1197, 0));
+        assert!(per_account.skipped().is_empty());
+        assert!(per_account.returns().is_none());
+        assert!(whole_account.returns().is_some_and(|r| r.xirr.is_some()));
+    }
+
+    /// Fixture API whose instrument lookups of `failing` FIGIs are rejected
+    struct FailingLookups {
+        inner: crate::fake::FakeApi,
+        failing: &'static str,
+    }
+
+    impl InvestApi for FailingLookups {
+        fn accounts(&self) -> crate::api::ApiFuture<'_, Vec<Account>> {
+            self.inner.accounts()
+        }
+
+        fn portfolio(
+            &self,
+            account_id: String,
+        ) -> crate::api::ApiFuture<'_, Vec<PortfolioPosition>> {
+            self.inner.portfolio(account_id)
+        }
+
+        fn operations(
+            &self,
+            query: OperationsQuery,
+            cursor: Option<String>,
+        ) -> crate::api::ApiFuture<'_, crate::api::OperationsPage> {
+            self.inner.operations(query, cursor)
+        }
+
+        fn instrument_by_figi(
+            &self,
+            figi: String,
+        ) -> crate::api::ApiFuture<'_, Option<Instrument>> {
+            if figi == self.failing {
+                let status = tonic::Status::new(tonic::Code::PermissionDenied, "token revoked");
+                return Box::pin(async move {
+                    Err(crate::retry::ApiError::new("Failed to get instrument", &status).into())
+                });
+            }
+            self.inner.instrument_by_figi(figi)
+        }
+
+        fn instruments(
+            &self,
+            catalog: InstrumentCatalog,
+        ) -> crate::api::ApiFuture<'_, HashMap<String, Instrument>> {
+            self.inner.instruments(catalog)
+        }
+
+        fn currencies(&self) -> crate::api::ApiFuture<'_, Vec<CurrencyInstrument>> {
+            self.inner.currencies()
+        }
+
+        fn find_instruments(
+            &self,
+            query: String,
+        ) -> crate::api::ApiFuture<'_, Vec<InstrumentShort>> {
+            self.inner.find_instruments(query)
+        }
+
+        fn last_prices(
+            &self,
+            figis: Vec<String>,
+        ) -> crate::api::ApiFuture<'_, Vec<tinkoff_invest_api::tcs::LastPrice>> {
+            self.inner.last_prices(figis)
+        }
+
+        fn dividends(
+            &self,
+            figi: String,
+        ) -> crate::api::ApiFuture<'_, Vec<tinkoff_invest_api::tcs::Dividend>> {
+            self.inner.dividends(figi)
+        }
+
+        fn coupons(
+            &self,
+            figi: String,
+        ) -> crate::api::ApiFuture<'_, Vec<tinkoff_invest_api::tcs::Coupon>> {
+            self.inner.coupons(figi)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_instrument_lookup_is_not_taken_for_unknown_instrument() {
+        let fixture: crate::fake::Fixture = serde_json::from_str(
+            r#"{
+                "instruments": [
+                    {"figi": "BBG004730N88", "ticker": "SBER", "name": "Сбер Банк", "instrument_type": "share"},
+                    {"figi": "BBG004731032", "ticker": "LKOH", "name": "ЛУКОЙЛ", "instrument_type": "share"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let api = FailingLookups {
+            inner: crate::fake::FakeApi::new(fixture).unwrap(),
+            failing: "BBG004731032",
+        };
+        let client = TinkoffInvestment::with_api(Arc::new(api));
+
+        let instruments = client
+            .get_instruments_by_figi(
+                ["BBG004730N88", "BBG004731032", "BBG000UNKNOWN"].map(String::from),
+            )
+            .await;
+
+        assert_eq!(instruments.len(), 1);
+        assert!(instruments.require("BBG004730N88").is_ok());
+        let failed = instruments
+            .require("BBG004731032")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(failed.starts_with("Instrument lookup failed"), "{failed}");
+        assert!(failed.contains("token revoked"), "{failed}");
+        let unknown = instruments
+            .require("BBG000UNKNOWN")
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(unknown, "Instrument not found");
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tinkoff_invest_api::tcs::{AccessLevel, Account, AccountStatus};
+
+use crate::{account_type_name, to_datetime_utc};
+
+/// Account of the user as the API reports it
+#[derive(Serialize)]
+pub struct AccountInfo {
+    pub id: String,
+    pub name: String,
+    pub account_type: &'static str,
+    pub status: &'static str,
+    pub opened: Option<DateTime<Utc>>,
+    pub closed: Option<DateTime<Utc>>,
+    pub access_level: &'static str,
+    /// Whether other commands read this account with the current options
+    pub selected: bool,
+}
+
+/// Every account the token can reach
+#[derive(Serialize)]
+pub struct AccountList {
+    pub accounts: Vec<AccountInfo>,
+    /// Set when there is no account of the requested type
+    /// and the first account is read instead
+    pub fallback: bool,
+}
+
+/// Result of a command for one account of the user
+#[derive(Serialize)]
+pub struct AccountSection<T> {
+    pub account_id: String,
+    pub account_name: String,
+    pub data: T,
+}
+
+/// Results of a command for several accounts together with their consolidated total
+#[derive(Serialize)]
+pub struct Consolidated<T> {
+    pub accounts: Vec<AccountSection<T>>,
+    /// The same result computed over all accounts at once
+    pub total: T,
+}
+
+/// Result of a command either for a single account or for all accounts of the user
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum AccountsView<T> {
+    Single(T),
+    All(Consolidated<T>),
+}
+
+/// Date the account was opened, `None` if the API does not report it
+#[must_use]
+pub fn account_opened(account: &Account) -> Option<DateTime<Utc>> {
+    date(account.opened_date.as_ref())
+}
+
+/// Unset timestamps of an account come as zero
+fn date(timestamp: Option<&prost_types::Timestamp>) -> Option<DateTime<Utc>> {
+    timestamp
+        .filter(|t| t.seconds > 0)
+        .map(|t| to_datetime_utc(Some(t)))
+}
+
+impl AccountInfo {
+    #[must_use]
+    pub fn new(account: &Account, selected: bool) -> Self {
+        let status = match account.status() {
+            AccountStatus::New => "new",
+            AccountStatus::Open => "open",
+            AccountStatus::Closed => "closed",
+            AccountStatus::Unspecified => "unspecified",
+        };
+        let access_level = match account.access_level() {
+            AccessLevel::AccountAccessLevelFullAccess => "full",
+            AccessLevel::AccountAccessLevelReadOnly => "read-only",
+            AccessLevel::AccountAccessLevelNoAccess => "none",
+            AccessLevel::AccountAccessLevelUnspecified => "unspecified",
+        };
+        Self {
+            id: account.id.clone(),
+            name: account.name.clone(),
+            account_type: account_type_name(account.r#type()),
+            status,
+            opened: account_opened(account),
+            closed: date(account.closed_date.as_ref()),
+            access_level,
+            selected,
+        }
+    }
+}
+
+impl<T> AccountSection<T> {
+    #[must_use]
+    pub fn new(account_id: impl Into<String>, account_name: impl Into<String>, data: T) -> Self {
+        Self {
+            account_id: account_id.into(),
+            account_name: account_name.into(),
+            data,
+        }
+    }
+
+    /// Creates a section of the same account with other data
+    #[must_use]
+    pub fn map<U, F>(&self, f: F) -> AccountSection<U>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        AccountSection {
+            account_id: self.account_id.clone(),
+            account_name: self.account_name.clone(),
+            data: f(&self.data),
+        }
+    }
+}
+
+impl<T> Consolidated<T> {
+    /// Creates the view, computing the total from all account sections
+    #[must_use]
+    pub fn new<F>(accounts: Vec<AccountSection<T>>, total: F) -> Self
+    where
+        F: FnOnce(&[AccountSection<T>]) -> T,
+    {
+        let total = total(&accounts);
+        Self { accounts, total }
+    }
+}
+
+impl<T> AccountsView<T> {
+    /// Consolidates `accounts` if `all` is set, otherwise takes the only account.
+    /// Returns `None` if there are no accounts in single account mode.
+    #[must_use]
+    pub fn new<F>(mut accounts: Vec<AccountSection<T>>, all: bool, total: F) -> Option<Self>
+    where
+        F: FnOnce(&[AccountSection<T>]) -> T,
+    {
+        if all {
+            Some(Self::All(Consolidated::new(accounts, total)))
+        } else {
+            accounts.pop().map(|a| Self::Single(a.data))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Timestamp;
+    use tinkoff_invest_api::tcs::AccountType;
+
+    use super::*;
+
+    #[test]
+    fn account_info_from_api_account() {
+        let account = Account {
+            id: "2000000000".to_string(),
+            r#type: AccountType::TinkoffIis as i32,
+            name: "IIS".to_string(),
+            status: AccountStatus::Open as i32,
+            opened_date: Some(Timestamp {
+                seconds: 1_600_000_000,
+                nanos: 0,
+            }),
+            closed_date: Some(Timestamp::default()),
+            access_level: AccessLevel::AccountAccessLevelReadOnly as i32,
+        };
+
+        let info = AccountInfo::new(&account, true);
+
+        assert_eq!(info.account_type, "iis");
+        assert_eq!(info.status, "open");
+        assert_eq!(info.access_level, "read-only");
+        assert!(info.opened.is_some());
+        assert!(info.closed.is_none());
+        assert!(info.selected);
+    }
+
+    #[test]
+    fn single_view_takes_account_data() {
+        let accounts = vec![AccountSection::new("1", "Broker", 10)];
+
+        let view = AccountsView::new(accounts, false, |_| unreachable!());
+
+        assert!(matches!(view, Some(AccountsView::Single(10))));
+    }
+
+    #[test]
+    fn all_view_computes_total() {
+        let accounts = vec![
+            AccountSection::new("1", "Broker", 10),
+            AccountSection::new("2", "IIS", 5),
+        ];
+
+        let view = AccountsView::new(accounts, true, |a| a.iter().map(|s| s.data).sum());
+
+        let Some(AccountsView::All(consolidated)) = view else {
+            panic!("expected consolidated view");
+        };
+        assert_eq!(consolidated.total, 15);
+        assert_eq!(consolidated.accounts[1].map(|d| d * 2).data, 10);
+    }
+}
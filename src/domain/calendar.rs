@@ -3,12 +3,14 @@ use std::fmt::Display;
 use chrono::{DateTime, Utc};
 use iso_currency::Currency;
 use rust_decimal::Decimal;
+use serde::Serialize;
 
 use super::money::Money;
 use super::paper::{Figi, Ticker};
+use super::skipped::{SkippedPosition, extend_unique};
 
 /// Dividend payment information
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct DividendPayment {
     pub figi: Figi,
     pub ticker: Ticker,
@@ -23,12 +25,16 @@ pub struct DividendPayment {
 }
 
 /// Dividend calendar with upcoming payments
+#[derive(Default, Serialize)]
 pub struct DividendCalendar {
     pub upcoming: Vec<DividendPayment>,
+    /// Positions whose payments failed to load
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedPosition>,
 }
 
 /// Coupon payment information
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct CouponPayment {
     pub figi: Figi,
     pub ticker: Ticker,
@@ -42,8 +48,42 @@ pub struct CouponPayment {
 }
 
 /// Coupon calendar with upcoming payments
+#[derive(Default, Serialize)]
 pub struct CouponCalendar {
     pub upcoming: Vec<CouponPayment>,
+    /// Positions whose payments failed to load
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedPosition>,
+}
+
+impl DividendCalendar {
+    /// Merges calendars (e.g. of several accounts) ordered by ex-dividend date
+    #[must_use]
+    pub fn merge<'a>(calendars: impl IntoIterator<Item = &'a Self>) -> Self {
+        let mut upcoming: Vec<DividendPayment> = Vec::new();
+        let mut skipped = Vec::new();
+        for calendar in calendars {
+            upcoming.extend(calendar.upcoming.iter().cloned());
+            extend_unique(&mut skipped, &calendar.skipped);
+        }
+        upcoming.sort_by_key(|p| p.ex_dividend_date);
+        Self { upcoming, skipped }
+    }
+}
+
+impl CouponCalendar {
+    /// Merges calendars (e.g. of several accounts) ordered by coupon date
+    #[must_use]
+    pub fn merge<'a>(calendars: impl IntoIterator<Item = &'a Self>) -> Self {
+        let mut upcoming: Vec<CouponPayment> = Vec::new();
+        let mut skipped = Vec::new();
+        for calendar in calendars {
+            upcoming.extend(calendar.upcoming.iter().cloned());
+            extend_unique(&mut skipped, &calendar.skipped);
+        }
+        upcoming.sort_by_key(|p| p.coupon_date);
+        Self { upcoming, skipped }
+    }
 }
 
 /// Trait for calendar payment items (dividends, coupons, etc.)
@@ -205,15 +245,69 @@ impl Display for DividendPayment {
 }
 
 /// Combined payment type for merged dividend and coupon calendar
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum CombinedPayment {
     Dividend(DividendPayment),
     Coupon(CouponPayment),
 }
 
 /// Combined calendar with both dividend and coupon payments
+#[derive(Default, Serialize)]
 pub struct CombinedCalendar {
     pub upcoming: Vec<CombinedPayment>,
+    /// Positions whose payments failed to load
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedPosition>,
+}
+
+impl CombinedCalendar {
+    /// Merges calendars (e.g. of several accounts) ordered by payment date
+    #[must_use]
+    pub fn merge<'a>(calendars: impl IntoIterator<Item = &'a Self>) -> Self {
+        let mut upcoming: Vec<CombinedPayment> = Vec::new();
+        let mut skipped = Vec::new();
+        for calendar in calendars {
+            upcoming.extend(calendar.upcoming.iter().cloned());
+            extend_unique(&mut skipped, &calendar.skipped);
+        }
+        upcoming.sort_by_key(CalendarPayment::payment_date);
+        Self { upcoming, skipped }
+    }
+
+    /// Dividend payments only
+    #[must_use]
+    pub fn dividends(&self) -> DividendCalendar {
+        let upcoming = self
+            .upcoming
+            .iter()
+            .filter_map(|p| match p {
+                CombinedPayment::Dividend(d) => Some(d.clone()),
+                CombinedPayment::Coupon(_) => None,
+            })
+            .collect();
+        DividendCalendar {
+            upcoming,
+            skipped: self.skipped.clone(),
+        }
+    }
+
+    /// Coupon payments only
+    #[must_use]
+    pub fn coupons(&self) -> CouponCalendar {
+        let upcoming = self
+            .upcoming
+            .iter()
+            .filter_map(|p| match p {
+                CombinedPayment::Coupon(c) => Some(c.clone()),
+                CombinedPayment::Dividend(_) => None,
+            })
+            .collect();
+        CouponCalendar {
+            upcoming,
+            skipped: self.skipped.clone(),
+        }
+    }
 }
 
 impl CalendarPayment for CombinedPayment {
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use iso_currency::Currency;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::{to_datetime_utc, to_money};
+
+use super::fx::FxRates;
+use super::journal::OperationKind;
+use super::money::{Money, MoneyBag};
+use super::operations::unique_operations;
+use super::skipped::{SkippedPosition, extend_unique};
+
+/// Operations moving money in and out of an account, or earning and costing it
+const CASHFLOW_KINDS: [OperationKind; 6] = [
+    OperationKind::Deposit,
+    OperationKind::Withdrawal,
+    OperationKind::Dividend,
+    OperationKind::Coupon,
+    OperationKind::Fee,
+    OperationKind::Tax,
+];
+
+/// Length of the periods cash flows are summed over
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Month,
+    Year,
+}
+
+/// Money moved in and out of an account within a month or a year.
+/// Amounts are payments: money leaving the account is negative.
+#[derive(Clone, Default)]
+pub struct CashFlowRow {
+    /// `YYYY-MM` or `YYYY`
+    pub period: String,
+    pub deposits: MoneyBag,
+    pub withdrawals: MoneyBag,
+    /// Dividends and coupons
+    pub income: MoneyBag,
+    /// Fees and taxes
+    pub costs: MoneyBag,
+}
+
+/// Cash flows of an account compared with its current value
+#[derive(Clone)]
+pub struct CashFlow {
+    pub interval: Interval,
+    /// Rows in chronological order
+    pub rows: Vec<CashFlowRow>,
+    /// Current value of the account in the reporting currency, `None` if unknown
+    pub current: Option<Money>,
+    /// Positions left out of the current value
+    pub skipped: Vec<SkippedPosition>,
+    fx: FxRates,
+}
+
+impl Interval {
+    fn label(self, datetime: DateTime<Utc>) -> String {
+        match self {
+            Self::Month => datetime.format("%Y-%m").to_string(),
+            Self::Year => datetime.format("%Y").to_string(),
+        }
+    }
+}
+
+impl CashFlowRow {
+    /// Deposits less withdrawals
+    #[must_use]
+    pub fn net_contributions(&self) -> MoneyBag {
+        self.deposits
+            .iter()
+            .chain(self.withdrawals.iter())
+            .collect()
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.deposits.extend(other.deposits.iter());
+        self.withdrawals.extend(other.withdrawals.iter());
+        self.income.extend(other.income.iter());
+        self.costs.extend(other.costs.iter());
+    }
+}
+
+impl CashFlow {
+    /// Cash flows of `operations` per `interval`, each operation once.
+    /// Trades and other operations moving money within the account are ignored.
+    #[must_use]
+    pub fn new(operations: &[Operation], interval: Interval) -> Self {
+        let mut rows: BTreeMap<String, CashFlowRow> = BTreeMap::new();
+        for op in unique_operations(operations) {
+            let Some(payment) = to_money(op.payment.as_ref()) else {
+                continue;
+            };
+            let kind = OperationKind::of(op.operation_type());
+            if !CASHFLOW_KINDS.contains(&kind) {
+                continue;
+            }
+            let period = interval.label(to_datetime_utc(op.date.as_ref()));
+            let row = rows.entry(period.clone()).or_insert_with(|| CashFlowRow {
+                period,
+                ..CashFlowRow::default()
+            });
+            let bag = match kind {
+                OperationKind::Deposit => &mut row.deposits,
+                OperationKind::Withdrawal => &mut row.withdrawals,
+                OperationKind::Dividend | OperationKind::Coupon => &mut row.income,
+                _ => &mut row.costs,
+            };
+            *bag += payment;
+        }
+        Self {
+            interval,
+            rows: rows.into_values().collect(),
+            current: None,
+            skipped: Vec::new(),
+            fx: FxRates::default(),
+        }
+    }
+
+    /// Sets the current value of the account and the rates to bring contributions
+    /// into its currency
+    #[must_use]
+    pub fn with_current(mut self, current: Money, fx: FxRates) -> Self {
+        self.current = Some(current);
+        self.fx = fx;
+        self
+    }
+
+    /// Sets the positions left out of the current value
+    #[must_use]
+    pub fn with_skipped(mut self, skipped: Vec<SkippedPosition>) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// Cash flows of several accounts together, rows of the same period summed
+    #[must_use]
+    pub fn merge<'a>(
+        interval: Interval,
+        fx: FxRates,
+        flows: impl IntoIterator<Item = &'a CashFlow>,
+    ) -> Self {
+        let mut rows: BTreeMap<String, CashFlowRow> = BTreeMap::new();
+        let mut current = Some(Money::zero(fx.reporting_currency()));
+        let mut skipped = Vec::new();
+        for flow in flows {
+            extend_unique(&mut skipped, &flow.skipped);
+            for row in &flow.rows {
+                rows.entry(row.period.clone())
+                    .or_insert_with(|| CashFlowRow {
+                        period: row.period.clone(),
+                        ..CashFlowRow::default()
+                    })
+                    .add(row);
+            }
+            // Unknown once any account value is unknown or in another currency
+            current = current
+                .zip(flow.current)
+                .and_then(|(sum, value)| sum.checked_add(value).ok());
+        }
+        Self {
+            interval,
+            rows: rows.into_values().collect(),
+            current,
+            skipped,
+            fx,
+        }
+    }
+
+    /// Sums of all rows
+    #[must_use]
+    pub fn totals(&self) -> CashFlowRow {
+        let mut totals = CashFlowRow::default();
+        for row in &self.rows {
+            totals.add(row);
+        }
+        totals
+    }
+
+    /// Net contributions in the reporting currency, converted at current rates.
+    /// `None` if some of them cannot be converted, see [`CashFlow::unconverted`]
+    #[must_use]
+    pub fn net_contributions(&self) -> Option<Money> {
+        let contributions = self.totals().net_contributions();
+        if self.fx.unconvertible(contributions.currencies()).is_empty() {
+            Some(self.fx.total(&contributions))
+        } else {
+            None
+        }
+    }
+
+    /// Currencies of contributions without a rate into the reporting currency
+    #[must_use]
+    pub fn unconverted(&self) -> Vec<Currency> {
+        self.fx
+            .unconvertible(self.totals().net_contributions().currencies())
+    }
+
+    /// Current value less net contributions: what the account has earned over its lifetime.
+    /// `None` if either is unknown
+    #[must_use]
+    pub fn lifetime_gain(&self) -> Option<Money> {
+        self.current?.checked_sub(self.net_contributions()?).ok()
+    }
+
+    /// Lifetime gain relative to net contributions, percent
+    #[must_use]
+    pub fn lifetime_return(&self) -> Option<Decimal> {
+        let contributions = self.net_contributions()?.value;
+        if contributions <= Decimal::ZERO {
+            return None;
+        }
+        self.lifetime_gain()
+            .map(|gain| (gain.value / contributions * dec!(100)).round_dp(2))
+    }
+}
+
+/// Operation types a cash flow report is made of
+#[must_use]
+pub fn cashflow_operation_types() -> Vec<OperationType> {
+    CASHFLOW_KINDS
+        .iter()
+        .flat_map(|kind| kind.operation_types().iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Figi;
+    use crate::domain::test_support::{operation, paid};
+
+    fn operations() -> Vec<Operation> {
+        vec![
+            operation("1", OperationType::Input, "2024-01-05T10:00:00Z", "10000"),
+            operation("2", OperationType::Buy, "2024-01-10T10:00:00Z", "-9000"),
+            operation("3", OperationType::BrokerFee, "2024-01-10T10:00:00Z", "-27"),
+            operation("4", OperationType::Input, "2024-03-05T10:00:00Z", "5000"),
+            operation("4", OperationType::Input, "2024-03-05T10:00:00Z", "5000"),
+            operation("5", OperationType::Dividend, "2024-07-20T10:00:00Z", "330"),
+            operation(
+                "6",
+                OperationType::DividendTax,
+                "2024-07-20T10:00:00Z",
+                "-43",
+            ),
+            operation("7", OperationType::Output, "2025-02-01T10:00:00Z", "-3000"),
+        ]
+    }
+
+    #[test]
+    fn operations_are_summed_per_month() {
+        let flow = CashFlow::new(&operations(), Interval::Month);
+
+        assert_eq!(flow.rows.len(), 4);
+        assert_eq!(flow.rows[0].period, "2024-01");
+        assert_eq!(flow.rows[0].deposits.get(Currency::RUB).value, dec!(10000));
+        assert_eq!(flow.rows[0].costs.get(Currency::RUB).value, dec!(-27));
+        let totals = flow.totals();
+        assert_eq!(
+            totals.net_contributions().get(Currency::RUB).value,
+            dec!(12000)
+        );
+        assert_eq!(totals.income.get(Currency::RUB).value, dec!(330));
+    }
+
+    #[test]
+    fn lifetime_gain_is_current_value_less_net_contributions() {
+        let flow = CashFlow::new(&operations(), Interval::Year).with_current(
+            Money::from_value(dec!(13200), Currency::RUB),
+            FxRates::default(),
+        );
+
+        assert_eq!(flow.rows.len(), 2);
+        assert_eq!(
+            flow.rows[1].withdrawals.get(Currency::RUB).value,
+            dec!(-3000)
+        );
+        assert_eq!(flow.lifetime_gain().unwrap().value, dec!(1200));
+        assert_eq!(flow.lifetime_return(), Some(dec!(10)));
+    }
+
+    #[test]
+    fn merged_accounts_sum_rows_of_a_period() {
+        let flow = CashFlow::new(&operations(), Interval::Year)
+            .with_current(
+                Money::from_value(dec!(13200), Currency::RUB),
+                FxRates::default(),
+            )
+            .with_skipped(vec![SkippedPosition::new(
+                Figi::new("BBG000DELIST"),
+                None,
+                "share",
+                "Failed to get current price",
+            )]);
+
+        let total = CashFlow::merge(Interval::Year, FxRates::default(), [&flow, &flow]);
+
+        assert_eq!(total.rows.len(), 2);
+        assert_eq!(total.net_contributions().unwrap().value, dec!(24000));
+        assert_eq!(total.current.unwrap().value, dec!(26400));
+        assert_eq!(total.skipped.len(), 1);
+    }
+
+    #[test]
+    fn contributions_without_rate_leave_lifetime_gain_unknown() {
+        let deposit = paid(
+            operation("8", OperationType::Input, "2025-03-01T10:00:00Z", "0"),
+            dec!(100),
+            "usd",
+        );
+        let mut operations = operations();
+        operations.push(deposit);
+
+        let flow = CashFlow::new(&operations, Interval::Year).with_current(
+            Money::from_value(dec!(13200), Currency::RUB),
+            FxRates::default(),
+        );
+
+        assert_eq!(flow.unconverted(), vec![Currency::USD]);
+        assert!(flow.net_contributions().is_none());
+        assert!(flow.lifetime_gain().is_none());
+        assert!(flow.lifetime_return().is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell};
+
+use crate::ux;
+
+use super::super::accounts::{AccountList, AccountSection, AccountsView, Consolidated};
+
+const ALL_ACCOUNTS: &str = "All accounts";
+
+fn write_title(f: &mut std::fmt::Formatter<'_>, title: &str) -> std::fmt::Result {
+    let mut table = ux::new_table();
+    table.set_header([Cell::new(title)
+        .add_attribute(Attribute::Bold)
+        .fg(comfy_table::Color::DarkMagenta)]);
+    writeln!(f, "{table}")
+}
+
+impl<T: Display> Display for AccountSection<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let title = if self.account_name.is_empty() {
+            format!("Account {}", self.account_id)
+        } else {
+            format!("Account {} ({})", self.account_name, self.account_id)
+        };
+        write_title(f, &title)?;
+        writeln!(f, "{}", self.data)
+    }
+}
+
+impl<T: Display> Display for Consolidated<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for account in &self.accounts {
+            write!(f, "{account}")?;
+        }
+        write_title(f, ALL_ACCOUNTS)?;
+        write!(f, "{}", self.total)
+    }
+}
+
+impl<T: Display> Display for AccountsView<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single(data) => write!(f, "{data}"),
+            Self::All(consolidated) => write!(f, "{consolidated}"),
+        }
+    }
+}
+
+impl Display for AccountList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = ux::new_table();
+        table.set_header(
+            [
+                "", "Id", "Name", "Type", "Status", "Opened", "Closed", "Access",
+            ]
+            .map(|h| Cell::new(h).add_attribute(Attribute::Bold)),
+        );
+
+        let date = |d: Option<chrono::DateTime<chrono::Utc>>| {
+            d.map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+        for account in &self.accounts {
+            let marker = if account.selected { "*" } else { "" };
+            table.add_row([
+                Cell::new(marker).fg(comfy_table::Color::Green),
+                Cell::new(&account.id),
+                Cell::new(&account.name),
+                Cell::new(account.account_type),
+                Cell::new(account.status),
+                Cell::new(date(account.opened)),
+                Cell::new(date(account.closed)),
+                Cell::new(account.access_level),
+            ]);
+        }
+        writeln!(f, "{table}")?;
+
+        if self.fallback {
+            writeln!(
+                f,
+                "* No account of the requested type, the first account is read instead"
+            )
+        } else {
+            writeln!(f, "* Read by other commands with the current options")
+        }
+    }
+}
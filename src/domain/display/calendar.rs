@@ -3,13 +3,14 @@ use std::fmt::Display;
 
 use chrono::{DateTime, Datelike, Utc};
 use comfy_table::{Attribute, Cell, Table};
-use iso_currency::Currency;
 
 use crate::ux;
 
 use super::super::calendar::CalendarPayment;
-use super::super::money::Money;
+use super::super::money::MoneyBag;
 use super::super::{CouponCalendar, DividendCalendar};
+use super::skipped::write_skipped;
+use crate::domain::SkippedPosition;
 use crate::domain::calendar::CombinedCalendar;
 
 fn format_date(dt: DateTime<Utc>) -> String {
@@ -83,7 +84,7 @@ fn add_payment_row<P: CalendarPayment>(table: &mut Table, payment: &P) {
 }
 
 /// Adds a month total row to the calendar table
-fn add_month_total<P: CalendarPayment>(table: &mut Table, month_name_str: &str, total: Money) {
+fn add_month_total<P: CalendarPayment>(table: &mut Table, month_name_str: &str, total: &MoneyBag) {
     table.add_row([
         Cell::new(""),
         Cell::new(""),
@@ -94,7 +95,7 @@ fn add_month_total<P: CalendarPayment>(table: &mut Table, month_name_str: &str,
 }
 
 /// Adds a year total row to the calendar table
-fn add_year_total<P: CalendarPayment>(table: &mut Table, year: i32, total: Money) {
+fn add_year_total<P: CalendarPayment>(table: &mut Table, year: i32, total: &MoneyBag) {
     table.add_row([
         Cell::new(""),
         Cell::new(""),
@@ -107,7 +108,7 @@ fn add_year_total<P: CalendarPayment>(table: &mut Table, year: i32, total: Money
 }
 
 /// Adds the grand total row to the calendar table
-fn add_grand_total(table: &mut Table, total: Money) {
+fn add_grand_total(table: &mut Table, total: &MoneyBag) {
     table.add_row([
         Cell::new(""),
         Cell::new(""),
@@ -165,7 +166,7 @@ pub(super) fn format_calendar<P: CalendarPayment>(upcoming: &[P]) -> String {
 
     let grouped = group_and_sort_payments(upcoming);
 
-    let mut grand_total = Money::zero(Currency::RUB);
+    let mut grand_total = MoneyBag::new();
 
     for year in grouped.keys() {
         let Some(months) = grouped.get(year) else {
@@ -174,7 +175,7 @@ pub(super) fn format_calendar<P: CalendarPayment>(upcoming: &[P]) -> String {
 
         add_year_header(&mut table, *year);
 
-        let mut year_total = Money::zero(Currency::RUB);
+        let mut year_total = MoneyBag::new();
 
         for month in months.keys() {
             let Some(payments) = months.get(month) else {
@@ -184,43 +185,59 @@ pub(super) fn format_calendar<P: CalendarPayment>(upcoming: &[P]) -> String {
             let month_name_str = month_name(*month);
             add_month_header(&mut table, month_name_str);
 
-            let mut month_total = Money::zero(Currency::RUB);
+            let mut month_total = MoneyBag::new();
 
             for payment in payments {
                 add_payment_row(&mut table, *payment);
                 month_total += payment.total_payment();
             }
 
-            add_month_total::<P>(&mut table, month_name_str, month_total);
+            add_month_total::<P>(&mut table, month_name_str, &month_total);
 
-            year_total += month_total;
-            grand_total += month_total;
+            year_total.extend(month_total.iter());
+            grand_total.extend(month_total.iter());
         }
 
-        add_year_total::<P>(&mut table, *year, year_total);
+        add_year_total::<P>(&mut table, *year, &year_total);
         add_separator_row(&mut table);
     }
 
-    add_grand_total(&mut table, grand_total);
+    add_grand_total(&mut table, &grand_total);
 
     table.to_string()
 }
 
+/// Writes positions left out of a calendar below it
+fn write_calendar_skipped(
+    f: &mut std::fmt::Formatter<'_>,
+    skipped: &[SkippedPosition],
+) -> std::fmt::Result {
+    if skipped.is_empty() {
+        Ok(())
+    } else {
+        writeln!(f)?;
+        write_skipped(f, skipped)
+    }
+}
+
 impl Display for DividendCalendar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", format_calendar(&self.upcoming))
+        write!(f, "{}", format_calendar(&self.upcoming))?;
+        write_calendar_skipped(f, &self.skipped)
     }
 }
 
 impl Display for CouponCalendar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", format_calendar(&self.upcoming))
+        write!(f, "{}", format_calendar(&self.upcoming))?;
+        write_calendar_skipped(f, &self.skipped)
     }
 }
 
 impl Display for CombinedCalendar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", format_calendar(&self.upcoming))
+        write!(f, "{}", format_calendar(&self.upcoming))?;
+        write_calendar_skipped(f, &self.skipped)
     }
 }
 
@@ -264,7 +281,10 @@ mod tests {
                 dividend_type: "type".to_string(),
             },
         ];
-        let calendar = DividendCalendar { upcoming: payments };
+        let calendar = DividendCalendar {
+            upcoming: payments,
+            ..Default::default()
+        };
         let output = format!("{calendar}");
         let pos_2024 = output.find("2024").unwrap();
         let pos_2025 = output.find("2025").unwrap();
@@ -275,7 +295,7 @@ mod tests {
     fn combined_calendar_empty() {
         use crate::domain::calendar::CombinedCalendar;
 
-        let calendar = CombinedCalendar { upcoming: vec![] };
+        let calendar = CombinedCalendar::default();
         let output = format!("{calendar}");
         assert!(output.contains("Payments Calendar"));
         assert!(output.contains("No upcoming dividend or coupon payments"));
@@ -317,6 +337,7 @@ mod tests {
                 CombinedPayment::Dividend(dividend),
                 CombinedPayment::Coupon(coupon),
             ],
+            ..Default::default()
         };
 
         let output = format!("{calendar}");
@@ -372,6 +393,7 @@ mod tests {
                 CombinedPayment::Coupon(coupon.clone()),
                 CombinedPayment::Dividend(dividend.clone()),
             ],
+            ..Default::default()
         };
 
         let output = format!("{calendar}");
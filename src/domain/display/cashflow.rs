@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell, TableComponent};
+use itertools::Itertools;
+
+use crate::ux;
+
+use super::super::cashflow::{CashFlow, Interval};
+use super::skipped::write_skipped;
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+impl Display for CashFlow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut report_table = ux::new_table();
+        let title = match self.interval {
+            Interval::Month => "Cash flow by month",
+            Interval::Year => "Cash flow by year",
+        };
+        report_table.set_header([Cell::new(title)
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        report_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut rows_table = ux::new_table();
+        rows_table.set_header([
+            bold("Period"),
+            bold("Deposits"),
+            bold("Withdrawals"),
+            bold("Net contributions"),
+            bold("Income"),
+            bold("Costs"),
+        ]);
+        for row in &self.rows {
+            rows_table.add_row([
+                Cell::new(&row.period),
+                Cell::new(&row.deposits),
+                Cell::new(&row.withdrawals),
+                ux::colored_cell(row.net_contributions()),
+                Cell::new(&row.income),
+                Cell::new(&row.costs),
+            ]);
+        }
+        report_table.add_row([Cell::new(rows_table)]);
+        writeln!(f, "{report_table}")?;
+
+        let totals = self.totals();
+        let mut table = ux::new_table();
+        let title = bold("Totals").fg(comfy_table::Color::DarkYellow);
+        table.set_header([title, Cell::new("")]);
+        ux::add_row(&mut table, "Deposits", &totals.deposits);
+        ux::add_row(&mut table, "Withdrawals", &totals.withdrawals);
+        match self.net_contributions() {
+            Some(contributions) => ux::add_row(&mut table, "Net contributions", contributions),
+            None => ux::add_row(&mut table, "Net contributions", totals.net_contributions()),
+        }
+        ux::add_row_colorized(&mut table, "Income", totals.income);
+        ux::add_row_colorized(&mut table, "Costs", totals.costs);
+        if let Some(current) = self.current {
+            ux::add_row(&mut table, "Current value", current);
+        }
+        if let Some(gain) = self.lifetime_gain() {
+            ux::add_row_colorized(&mut table, "Lifetime gain", gain);
+        }
+        if let Some(percent) = self.lifetime_return() {
+            ux::add_row(&mut table, "Lifetime return, %", percent);
+        }
+        writeln!(f, "{table}")?;
+
+        let unconverted = self.unconverted();
+        if !unconverted.is_empty() {
+            writeln!(
+                f,
+                "No exchange rate for {}, the lifetime gain is unknown",
+                unconverted.iter().map(|c| c.code()).join(", ")
+            )?;
+        }
+        write_skipped(f, &self.skipped)
+    }
+}
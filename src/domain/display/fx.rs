@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell, Table};
+use iso_currency::Currency;
+
+use crate::ux;
+
+use super::super::fx::FxRate;
+
+impl Display for FxRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "1 {} = {} {}",
+            self.currency.code(),
+            self.rate.round_dp(4).normalize(),
+            self.reporting_currency.code()
+        )
+    }
+}
+
+/// Creates the table with exchange rates used to convert totals
+/// and currencies left out of them for lack of a rate
+pub(super) fn create_fx_table(rates: &[FxRate], unconverted: &[Currency]) -> Table {
+    let mut table = ux::new_table();
+
+    let title = Cell::new("Exchange rates")
+        .add_attribute(Attribute::Bold)
+        .fg(comfy_table::Color::DarkCyan);
+    table.set_header([title]);
+
+    for rate in rates {
+        table.add_row([Cell::new(rate)]);
+    }
+    for currency in unconverted {
+        table.add_row([Cell::new(format!(
+            "No rate for {}, left out of totals",
+            currency.code()
+        ))
+        .fg(comfy_table::Color::Red)]);
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn fx_rate_display() {
+        let rate = FxRate {
+            currency: Currency::USD,
+            reporting_currency: Currency::RUB,
+            rate: dec!(81.123456),
+        };
+
+        assert_eq!(rate.to_string(), "1 USD = 81.1235 RUB");
+    }
+}
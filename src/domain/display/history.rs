@@ -5,16 +5,68 @@ use comfy_table::{Attribute, Cell, TableComponent};
 use crate::ux;
 
 use super::super::history::History;
+use super::super::lots::TaxLots;
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+fn write_lots(f: &mut std::fmt::Formatter<'_>, lots: &TaxLots) -> std::fmt::Result {
+    if !lots.sales.is_empty() {
+        let mut table = ux::new_table();
+        table.set_header([
+            bold("Sold").fg(comfy_table::Color::DarkYellow),
+            bold("Quantity"),
+            bold("Unmatched"),
+            bold("Proceeds"),
+            bold("Cost"),
+            bold("Realized"),
+        ]);
+        for sale in &lots.sales {
+            table.add_row([
+                Cell::new(sale.datetime),
+                Cell::new(sale.quantity),
+                Cell::new(sale.unmatched),
+                Cell::new(sale.proceeds),
+                Cell::new(sale.cost),
+                ux::colored_cell(sale.realized()),
+            ]);
+        }
+        writeln!(f, "{table}")?;
+    }
+    if !lots.open.is_empty() {
+        let mut table = ux::new_table();
+        table.set_header([
+            bold("Open lots").fg(comfy_table::Color::DarkYellow),
+            bold("Quantity"),
+            bold("Price"),
+            bold("Cost"),
+        ]);
+        for lot in &lots.open {
+            table.add_row([
+                Cell::new(lot.acquired),
+                Cell::new(lot.quantity),
+                Cell::new(lot.price),
+                Cell::new(lot.cost()),
+            ]);
+        }
+        writeln!(f, "{table}")?;
+    }
+    Ok(())
+}
 
 impl Display for History {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut history_table = ux::new_table();
 
         let currency = self.currency.code().to_owned();
-        let title = format!(
+        let mut title = format!(
             "{} ({} | {} | {})",
             self.name, self.ticker, self.figi, currency
         );
+        if !self.period.is_all() {
+            title = format!("{title}, {}", self.period);
+        }
 
         history_table.set_header([Cell::new(title)
             .add_attribute(Attribute::Bold)
@@ -43,7 +95,8 @@ impl Display for History {
         }
 
         history_table.add_row([Cell::new(items_table)]);
-        write!(f, "{history_table}")?;
+        writeln!(f, "{history_table}")?;
+        write_lots(f, &self.lots)?;
 
         let mut table = ux::new_table();
 
@@ -55,6 +108,7 @@ impl Display for History {
         ux::add_row_colorized(&mut table, "Expenses", self.expenses());
         ux::add_row_colorized(&mut table, "Profit", self.profit());
         ux::add_row_colorized(&mut table, "Balance", self.balance());
+        ux::add_row_colorized(&mut table, "Realized", self.lots.realized());
         write!(f, "{table}")
     }
 }
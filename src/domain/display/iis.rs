@@ -0,0 +1,98 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell, TableComponent};
+
+use crate::ux;
+
+use super::super::iis::{IisKind, IisReport};
+use super::super::money::Money;
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+fn date(date: Option<DateTime<Utc>>) -> String {
+    date.map_or_else(|| "-".to_string(), |d| d.date_naive().to_string())
+}
+
+fn money_cell(money: Option<Money>) -> Cell {
+    Cell::new(money.map_or_else(|| "-".to_string(), |m| m.to_string()))
+}
+
+impl Display for IisReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut report_table = ux::new_table();
+        report_table.set_header([Cell::new("Individual investment account (IIS)")
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        report_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut years_table = ux::new_table();
+        years_table.set_header([
+            bold("Year"),
+            bold("Contributions"),
+            bold("Limit"),
+            bold("Excess"),
+            bold("Remaining"),
+            bold("Deduction"),
+        ]);
+        for year in &self.years {
+            let excess = year.excess();
+            let excess_cell = if excess.value.is_zero() {
+                Cell::new(excess)
+            } else {
+                Cell::new(excess).fg(comfy_table::Color::Red)
+            };
+            years_table.add_row([
+                Cell::new(year.year),
+                Cell::new(year.contributions),
+                money_cell(year.limit),
+                excess_cell,
+                money_cell(year.remaining()),
+                Cell::new(year.deduction()),
+            ]);
+        }
+        report_table.add_row([Cell::new(years_table)]);
+        writeln!(f, "{report_table}")?;
+
+        let mut table = ux::new_table();
+        let title = bold("Totals").fg(comfy_table::Color::DarkYellow);
+        table.set_header([title, Cell::new("")]);
+        if self.opened.is_some() {
+            let kind = match self.kind() {
+                Some(IisKind::Old) => "Opened before 2024 (type A or B)",
+                _ => "Opened since 2024 (IIS-3)",
+            };
+            ux::add_row(&mut table, "Opened", date(self.opened));
+            ux::add_row(&mut table, "Rules", kind);
+            let held = if self.is_held() { "yes" } else { "no" };
+            ux::add_row(&mut table, "Held until", date(self.holding_until()));
+            ux::add_row(&mut table, "Holding period satisfied", held);
+        }
+        ux::add_row(&mut table, "Contributions", self.contributions());
+        ux::add_row(&mut table, "Estimated deductions", self.deductions());
+        write!(f, "{table}")?;
+
+        let voiding = self.voiding_withdrawals();
+        if voiding.is_empty() {
+            return Ok(());
+        }
+        let mut warnings = ux::new_table();
+        let title = bold("Withdrawals voiding the benefit").fg(comfy_table::Color::Red);
+        warnings.set_header([title, Cell::new(""), Cell::new("")]);
+        warnings.add_row([bold("Date"), bold("Amount"), bold("Consequence")]);
+        for withdrawal in voiding {
+            warnings.add_row([
+                Cell::new(withdrawal.datetime.date_naive()),
+                Cell::new(withdrawal.amount),
+                Cell::new(format!(
+                    "Made before {}: the account is closed and received deductions must be paid back",
+                    date(self.holding_until())
+                ))
+                .fg(comfy_table::Color::Red),
+            ]);
+        }
+        write!(f, "\n{warnings}")
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell, TableComponent};
+
+use crate::ux;
+
+use super::super::journal::{Journal, Subtotal};
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+fn subtotals_table(title: &str, subtotals: &[Subtotal], with_month: bool) -> comfy_table::Table {
+    let mut table = ux::new_table();
+    let title = bold(title).fg(comfy_table::Color::DarkYellow);
+    if with_month {
+        table.set_header([title, bold("Type"), bold("Count"), bold("Total")]);
+    } else {
+        table.set_header([title, bold("Count"), bold("Total")]);
+    }
+    for subtotal in subtotals {
+        let mut row = Vec::with_capacity(4);
+        if with_month {
+            row.push(Cell::new(subtotal.month.as_deref().unwrap_or_default()));
+        }
+        row.push(Cell::new(subtotal.kind.name()));
+        row.push(Cell::new(subtotal.count));
+        row.push(ux::colored_cell(subtotal.total.clone()));
+        table.add_row(row);
+    }
+    table
+}
+
+impl Display for Journal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut journal_table = ux::new_table();
+        let title = format!("Operations, {}", self.period);
+        journal_table.set_header([Cell::new(title)
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        journal_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut items_table = ux::new_table();
+        items_table.set_header([
+            bold("Date"),
+            bold("Type"),
+            bold("Ticker"),
+            bold("Quantity"),
+            bold("Price"),
+            bold("Payment"),
+            bold("Description"),
+        ]);
+        for item in &self.items {
+            items_table.add_row([
+                Cell::new(item.datetime),
+                Cell::new(item.kind.name()),
+                Cell::new(&item.ticker),
+                Cell::new(item.quantity),
+                Cell::new(item.price),
+                ux::colored_cell(item.payment),
+                Cell::new(&item.description),
+            ]);
+        }
+        journal_table.add_row([Cell::new(items_table)]);
+        writeln!(f, "{journal_table}")?;
+
+        writeln!(f, "{}", subtotals_table("Month", &self.subtotals(), true))?;
+        write!(f, "{}", subtotals_table("Totals", &self.totals(), false))
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell, TableComponent};
+
+use crate::ux;
+
+use super::super::ldv::LdvReport;
+use super::skipped::write_skipped;
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+fn date_cell(date: Option<DateTime<Utc>>) -> Cell {
+    Cell::new(date.map_or_else(|| "-".to_string(), |d| d.date_naive().to_string()))
+}
+
+impl Display for LdvReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut report_table = ux::new_table();
+        let title = format!(
+            "Long-term ownership benefit (LDV) on {}",
+            self.today.date_naive()
+        );
+        report_table.set_header([Cell::new(title)
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        report_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut positions_table = ux::new_table();
+        positions_table.set_header([
+            bold("Ticker"),
+            bold("Held"),
+            bold("Eligible"),
+            bold("Untracked"),
+            bold("Next eligible"),
+            bold("Units"),
+            bold("Fully eligible"),
+            bold("Exempt gain"),
+            bold("Taxable gain"),
+            bold("Tax today"),
+            bold("Saved by waiting"),
+        ]);
+        for position in &self.positions {
+            positions_table.add_row([
+                Cell::new(&position.ticker),
+                Cell::new(position.quantity.round_dp(2)),
+                Cell::new(position.eligible_quantity()),
+                Cell::new(position.untracked_quantity().round_dp(2)),
+                date_cell(position.next_eligible()),
+                Cell::new(position.next_eligible_quantity()),
+                date_cell(position.fully_eligible()),
+                ux::colored_cell(position.exempt_gain()),
+                ux::colored_cell(position.taxable_gain()),
+                Cell::new(position.tax_today()),
+                Cell::new(position.saved_by_waiting()),
+            ]);
+        }
+        report_table.add_row([Cell::new(positions_table)]);
+        writeln!(f, "{report_table}")?;
+
+        let mut table = ux::new_table();
+        let title = bold("Totals").fg(comfy_table::Color::DarkYellow);
+        table.set_header([title, Cell::new("")]);
+        ux::add_row_colorized(&mut table, "Exempt gain", self.exempt_gain());
+        ux::add_row(&mut table, "Tax if sold today", self.tax_today());
+        ux::add_row(&mut table, "Saved by waiting", self.saved_by_waiting());
+        writeln!(f, "{table}")?;
+
+        write_skipped(f, &self.skipped)
+    }
+}
@@ -1,4 +1,14 @@
+mod accounts;
 mod calendar;
+mod cashflow;
+mod fx;
 mod history;
+mod iis;
+mod journal;
+mod ldv;
 mod portfolio;
+mod returns;
 pub mod risk;
+mod simulate;
+mod skipped;
+mod tax;
@@ -7,12 +7,16 @@ use crate::ux;
 use super::super::paper::Paper;
 use super::super::paper::Profit;
 use super::super::portfolio::{Asset, Portfolio};
+use super::fx::create_fx_table;
+use super::returns::{add_returns_rows, percent_cell};
+use super::skipped::write_skipped;
 
 const TOTAL_INCOME: &str = "Total income";
 const INCOME: &str = "Income";
 const CURRENT_VALUE: &str = "Current value";
 const BALANCE_VALUE: &str = "Balance value";
 const BALANCE_INCOME: &str = "Balance income";
+const REALIZED_INCOME: &str = "Realized income";
 
 impl<P: Profit> Display for Asset<P> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -44,6 +48,7 @@ impl<P: Profit> Display for Asset<P> {
             ux::add_row_colorized(&mut table, TOTAL_INCOME, self.total_income());
             ux::add_row_colorized(&mut table, P::name(), self.dividends());
         }
+        ux::add_row_colorized(&mut table, REALIZED_INCOME, self.realized());
 
         ux::add_row(&mut table, "Instruments count", self.papers().len());
         asset_table.add_row([Cell::new(table)]);
@@ -87,13 +92,32 @@ impl<P: Profit> Display for Paper<P> {
         table.add_row(["", ""]);
 
         ux::add_row_colorized(&mut table, INCOME, self.income());
+        add_returns_rows(&mut table, self.returns());
 
         if P::applicable() {
             ux::add_row_colorized(&mut table, P::name(), self.dividends());
             ux::add_row_colorized(&mut table, TOTAL_INCOME, self.total_income());
         }
 
+        let now = self.totals.today;
+        let held = match (self.holding_days(now), self.totals.first_bought) {
+            (Some(days), Some(bought)) => format!("{days} days since {}", bought.date_naive()),
+            _ => "-".to_string(),
+        };
+        ux::add_row(&mut table, "Holding period", held);
+        table.add_row([
+            Cell::new("CAGR of total income, %"),
+            percent_cell(self.cagr(now)),
+        ]);
+        if P::applicable() {
+            table.add_row([
+                Cell::new("Yield on cost (12 months), %"),
+                percent_cell(self.yield_on_cost()),
+            ]);
+        }
+
         ux::add_row_colorized(&mut table, "Taxes and fees", self.fees());
+        ux::add_row_colorized(&mut table, REALIZED_INCOME, self.realized());
 
         write!(f, "{table}")
     }
@@ -117,15 +141,24 @@ impl Display for Portfolio {
 
             ux::add_row_colorized(&mut table, BALANCE_INCOME, self.income());
             ux::add_row_colorized(&mut table, TOTAL_INCOME, self.total_income());
+            add_returns_rows(&mut table, self.returns().unwrap_or_default());
             ux::add_row_colorized(&mut table, "Dividends and coupons", self.dividends());
+            ux::add_row_colorized(&mut table, REALIZED_INCOME, self.realized());
 
             ux::add_row(&mut table, BALANCE_VALUE, self.balance());
             ux::add_row(&mut table, CURRENT_VALUE, self.current());
 
             writeln!(f)?;
-            writeln!(f, "{table}")
+            writeln!(f, "{table}")?;
         } else {
-            writeln!(f)
+            writeln!(f)?;
+        }
+
+        let rates = self.used_fx_rates();
+        let unconverted = self.unconverted_currencies();
+        if !rates.is_empty() || !unconverted.is_empty() {
+            writeln!(f, "{}", create_fx_table(&rates, &unconverted))?;
         }
+        write_skipped(f, &self.skipped)
     }
 }
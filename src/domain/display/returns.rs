@@ -0,0 +1,26 @@
+use comfy_table::{Cell, Table};
+use rust_decimal::Decimal;
+
+use super::super::returns::Returns;
+
+pub(super) const XIRR: &str = "XIRR, % per year";
+pub(super) const TWR: &str = "Time-weighted return, %";
+
+/// Adds XIRR and time-weighted return rows, a dash for a return that is unknown
+pub(super) fn add_returns_rows(table: &mut Table, returns: Returns) {
+    for (title, percent) in [(XIRR, returns.xirr), (TWR, returns.twr)] {
+        table.add_row([Cell::new(title), percent_cell(percent)]);
+    }
+}
+
+/// Colorized percent, a dash if it is unknown
+pub(super) fn percent_cell(percent: Option<Decimal>) -> Cell {
+    match percent {
+        Some(p) if p.is_sign_negative() && !p.is_zero() => {
+            Cell::new(p).fg(comfy_table::Color::DarkRed)
+        }
+        Some(p) if p.is_zero() => Cell::new(p),
+        Some(p) => Cell::new(p).fg(comfy_table::Color::DarkGreen),
+        None => Cell::new("-"),
+    }
+}
@@ -9,12 +9,17 @@ use comfy_table::{Attribute, Cell, Table};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
+use super::super::returns::Returns;
 use super::super::risk::{
     AssetAllocation, CurrencyAllocation, PositionConcentration, RebalanceAction,
-    RebalancingAnalysis, RiskAnalysis, RiskLevel, RiskMetrics,
+    RebalancingAnalysis, RiskAnalysis, RiskLevel, RiskMetrics, RiskReport,
 };
 use crate::ux;
 
+use super::fx::create_fx_table;
+use super::returns::add_returns_rows;
+use super::skipped::write_skipped;
+
 impl Display for RiskLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -52,7 +57,7 @@ fn add_risk_row(table: &mut Table, label: &str, value: &str, risk_value: Decimal
 }
 
 /// Creates the risk summary table
-fn create_risk_summary_table(metrics: &RiskMetrics) -> Table {
+fn create_risk_summary_table(metrics: &RiskMetrics, returns: Returns) -> Table {
     let mut table = ux::new_table();
 
     // Header
@@ -143,13 +148,19 @@ fn create_risk_summary_table(metrics: &RiskMetrics) -> Table {
             ux::format_decimal(metrics.var_95_yearly).unwrap_or_default()
         )),
     ]);
+    // Returns section header
+    let returns_header = Cell::new("Returns")
+        .add_attribute(Attribute::Bold)
+        .fg(comfy_table::Color::DarkCyan);
+    table.add_row([returns_header]);
+    add_returns_rows(&mut table, returns);
 
     table
 }
 
 impl Display for RiskAnalysis {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let risk_summary = create_risk_summary_table(&self.risk_metrics);
+        let risk_summary = create_risk_summary_table(&self.risk_metrics, self.returns);
         let asset_allocation = create_asset_allocation_table(&self.asset_allocation);
         let currency_diversification = create_currency_table(&self.currency_allocation);
         let position_concentration = create_position_table(&self.position_concentration);
@@ -159,10 +170,26 @@ impl Display for RiskAnalysis {
         writeln!(f, "\n{currency_diversification}")?;
         writeln!(f, "\n{position_concentration}")?;
 
+        if !self.fx_rates.is_empty() || !self.unconverted_currencies.is_empty() {
+            writeln!(
+                f,
+                "\n{}",
+                create_fx_table(&self.fx_rates, &self.unconverted_currencies)
+            )?;
+        }
+
         Ok(())
     }
 }
 
+impl Display for RiskReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.analysis)?;
+        write!(f, "{}", self.rebalancing)?;
+        write_skipped(f, &self.skipped)
+    }
+}
+
 impl Display for RebalancingAnalysis {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let table = create_rebalancing_table(self);
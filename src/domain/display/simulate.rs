@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell, TableComponent};
+use rust_decimal_macros::dec;
+
+use crate::ux;
+
+use super::super::simulate::SellSimulation;
+use super::skipped::write_skipped;
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+impl Display for SellSimulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut report_table = ux::new_table();
+        let title = format!(
+            "Selling {} of {} {} on {} (simulation, no order placed)",
+            self.quantity,
+            self.held.normalize(),
+            self.ticker,
+            self.today.date_naive()
+        );
+        report_table.set_header([Cell::new(title)
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        report_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut lots_table = ux::new_table();
+        lots_table.set_header([
+            bold("Acquired"),
+            bold("Units"),
+            bold("Price"),
+            bold("Gain"),
+            bold("LDV exempt"),
+        ]);
+        for lot in &self.lots {
+            lots_table.add_row([
+                Cell::new(lot.acquired.date_naive()),
+                Cell::new(lot.quantity),
+                Cell::new(lot.price),
+                ux::colored_cell(lot.gain),
+                Cell::new(if lot.exempt { "yes" } else { "no" }),
+            ]);
+        }
+        if self.unmatched > 0 {
+            lots_table.add_row([
+                Cell::new("Unknown"),
+                Cell::new(self.unmatched),
+                Cell::new("-"),
+                Cell::new("-"),
+                Cell::new("-"),
+            ]);
+        }
+        report_table.add_row([Cell::new(lots_table)]);
+        writeln!(f, "{report_table}")?;
+
+        let mut table = ux::new_table();
+        let title = bold("Totals").fg(comfy_table::Color::DarkYellow);
+        table.set_header([title, Cell::new("")]);
+        ux::add_row(&mut table, "Price", self.price);
+        ux::add_row(&mut table, "Proceeds", self.proceeds());
+        ux::add_row(
+            &mut table,
+            format!(
+                "Fee ({}%)",
+                (self.fee_rate * dec!(100)).round_dp(3).normalize()
+            ),
+            self.fee(),
+        );
+        ux::add_row_colorized(&mut table, "Realized", self.realized());
+        ux::add_row(&mut table, "LDV exempt units", self.exempt_quantity());
+        ux::add_row_colorized(&mut table, "LDV exempt gain", self.exempt_gain());
+        ux::add_row_colorized(&mut table, "Taxable gain", self.taxable_gain());
+        ux::add_row(&mut table, "Tax withheld", self.tax());
+        ux::add_row(&mut table, "Tax saved by LDV", self.exempt_tax());
+        ux::add_row(&mut table, "Net cash", self.net_cash());
+        write!(f, "{table}")?;
+
+        let (Some(before), Some(after)) = (self.before(), self.after()) else {
+            return write_skipped(f, &self.skipped);
+        };
+        let mut allocation_table = ux::new_table();
+        let title = bold("Allocation").fg(comfy_table::Color::DarkYellow);
+        allocation_table.set_header([title, bold("Before"), bold("After")]);
+        allocation_table.add_row([
+            Cell::new("Portfolio"),
+            Cell::new(before.portfolio),
+            Cell::new(after.portfolio),
+        ]);
+        allocation_table.add_row([
+            Cell::new("Position"),
+            Cell::new(before.position),
+            Cell::new(after.position),
+        ]);
+        allocation_table.add_row([
+            Cell::new("Weight, %"),
+            Cell::new(before.weight()),
+            Cell::new(after.weight()),
+        ]);
+        writeln!(f, "\n{allocation_table}")?;
+
+        write_skipped(f, &self.skipped)
+    }
+}
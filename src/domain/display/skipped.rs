@@ -0,0 +1,79 @@
+use std::fmt::{self, Display};
+
+use comfy_table::{Attribute, Cell};
+
+use crate::ux;
+
+use super::super::skipped::SkippedPosition;
+
+impl Display for SkippedPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ticker {
+            Some(ticker) => write!(f, "{} ({ticker}, {})", self.figi, self.instrument_type)?,
+            None => write!(f, "{} ({})", self.figi, self.instrument_type)?,
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Writes the table of positions left out of a report, nothing if there are none
+pub(super) fn write_skipped(
+    f: &mut fmt::Formatter<'_>,
+    skipped: &[SkippedPosition],
+) -> fmt::Result {
+    if skipped.is_empty() {
+        return Ok(());
+    }
+
+    let mut table = ux::new_table();
+    let title = Cell::new("Skipped positions")
+        .add_attribute(Attribute::Bold)
+        .fg(comfy_table::Color::Red);
+    table.set_header([title, Cell::new(""), Cell::new(""), Cell::new("")]);
+    table.add_row([
+        Cell::new("FIGI").add_attribute(Attribute::Bold),
+        Cell::new("Ticker").add_attribute(Attribute::Bold),
+        Cell::new("Type").add_attribute(Attribute::Bold),
+        Cell::new("Reason").add_attribute(Attribute::Bold),
+    ]);
+    for position in skipped {
+        table.add_row([
+            Cell::new(&position.figi),
+            Cell::new(position.ticker.as_ref().map_or("", |t| t.as_str())),
+            Cell::new(&position.instrument_type),
+            Cell::new(&position.reason).fg(comfy_table::Color::Red),
+        ]);
+    }
+    writeln!(f, "{table}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Figi, Ticker};
+
+    #[test]
+    fn skipped_position_display() {
+        let known = SkippedPosition::new(
+            Figi::new("BBG004730N88"),
+            Some(Ticker::new("SBER")),
+            "share",
+            "Failed to get current price",
+        );
+        let unknown = SkippedPosition::new(
+            Figi::new("BBG000000001"),
+            None,
+            "option",
+            "Unsupported instrument type",
+        );
+
+        assert_eq!(
+            known.to_string(),
+            "BBG004730N88 (SBER, share): Failed to get current price"
+        );
+        assert_eq!(
+            unknown.to_string(),
+            "BBG000000001 (option): Unsupported instrument type"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+use comfy_table::{Attribute, Cell, TableComponent};
+use itertools::Itertools;
+use rust_decimal_macros::dec;
+
+use crate::ux;
+
+use super::super::tax::{NDFL_RATE, TaxReport};
+
+fn bold(title: &str) -> Cell {
+    Cell::new(title).add_attribute(Attribute::Bold)
+}
+
+impl Display for TaxReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut report_table = ux::new_table();
+        let title = format!("Personal income tax (NDFL) estimate, {}", self.year);
+        report_table.set_header([Cell::new(title)
+            .add_attribute(Attribute::Bold)
+            .fg(comfy_table::Color::DarkBlue)]);
+        report_table.set_style(TableComponent::HeaderLines, ' ');
+
+        let mut instruments_table = ux::new_table();
+        instruments_table.set_header([
+            bold("Ticker"),
+            bold("Name"),
+            bold("Realized"),
+            bold("Dividends"),
+            bold("Coupons"),
+            bold("Withheld"),
+            bold("Self-declared"),
+        ]);
+        for tax in &self.instruments {
+            let (ticker, name) = if tax.figi.is_empty() {
+                ("", "Account")
+            } else if tax.ticker.is_empty() {
+                (tax.figi.as_str(), tax.name.as_str())
+            } else {
+                (tax.ticker.as_str(), tax.name.as_str())
+            };
+            instruments_table.add_row([
+                Cell::new(ticker),
+                Cell::new(name),
+                ux::colored_cell(tax.realized.clone()),
+                Cell::new(&tax.dividends),
+                Cell::new(&tax.coupons),
+                Cell::new(&tax.withheld),
+                Cell::new(tax.self_declared()),
+            ]);
+        }
+        report_table.add_row([Cell::new(instruments_table)]);
+        writeln!(f, "{report_table}")?;
+
+        let mut table = ux::new_table();
+        let title = bold("Totals").fg(comfy_table::Color::DarkYellow);
+        table.set_header([title, Cell::new("")]);
+        ux::add_row_colorized(&mut table, "Realized", self.realized());
+        ux::add_row(&mut table, "Dividends", self.dividends());
+        ux::add_row(&mut table, "Coupons", self.coupons());
+        ux::add_row(&mut table, "Taxable income", self.taxable_income());
+        ux::add_row(&mut table, "Tax due", self.tax_due());
+        ux::add_row(&mut table, "Withheld by broker", self.withheld());
+        ux::add_row_colorized(&mut table, "Outstanding", self.outstanding());
+        ux::add_row(
+            &mut table,
+            "Dividends to self-declare",
+            self.self_declared(),
+        );
+        write!(f, "{table}")?;
+
+        let estimated = self.estimated();
+        if !estimated.is_empty() {
+            write!(
+                f,
+                "\nTax in {} is estimated at {}% without conversion at the Central Bank rate",
+                estimated.iter().map(|c| c.code()).join(", "),
+                (NDFL_RATE * dec!(100)).normalize()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,645 @@
+//! CSV export of domain types, one row per item.
+
+use std::collections::BTreeSet;
+use std::io;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use super::accounts::{AccountList, AccountsView, Consolidated};
+use super::calendar::{
+    CalendarPayment, CombinedCalendar, CombinedPayment, CouponPayment, DividendPayment,
+};
+use super::cashflow::CashFlow;
+use super::history::History;
+use super::iis::IisReport;
+use super::journal::Journal;
+use super::ldv::LdvReport;
+use super::money::MoneyBag;
+use super::paper::{Paper, Profit};
+use super::portfolio::{Asset, Portfolio};
+use super::tax::TaxReport;
+use super::{CouponCalendar, DividendCalendar};
+use crate::output::ToCsv;
+
+#[derive(Serialize)]
+struct PaperRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    asset: &'static str,
+    ticker: &'a str,
+    figi: &'a str,
+    name: &'a str,
+    currency: &'static str,
+    quantity: Decimal,
+    average_buy_price: Decimal,
+    current_price: Decimal,
+    balance: Decimal,
+    current: Decimal,
+    dividends: Decimal,
+    fees: Decimal,
+    income: Decimal,
+    realized: Decimal,
+    xirr: Option<Decimal>,
+    twr: Option<Decimal>,
+    holding_days: Option<i64>,
+    cagr: Option<Decimal>,
+    yield_on_cost: Option<Decimal>,
+}
+
+#[derive(Serialize)]
+struct PaymentRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    kind: &'static str,
+    payment_date: DateTime<Utc>,
+    ex_date: DateTime<Utc>,
+    ticker: &'a str,
+    figi: &'a str,
+    name: &'a str,
+    currency: &'static str,
+    quantity: Decimal,
+    payment_per_unit: Decimal,
+    total_payment: Decimal,
+    payment_type: &'a str,
+}
+
+#[derive(Serialize)]
+struct HistoryRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    ticker: &'a str,
+    figi: &'a str,
+    datetime: DateTime<Utc>,
+    quantity: i64,
+    price: Decimal,
+    payment: Decimal,
+    currency: &'static str,
+    description: &'a str,
+    state: &'static str,
+}
+
+#[derive(Serialize)]
+struct JournalRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    datetime: DateTime<Utc>,
+    kind: &'static str,
+    ticker: &'a str,
+    figi: &'a str,
+    quantity: i64,
+    price: Decimal,
+    payment: Decimal,
+    currency: &'static str,
+    description: &'a str,
+}
+
+#[derive(Serialize)]
+struct LdvRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    ticker: &'a str,
+    figi: &'a str,
+    name: &'a str,
+    currency: &'static str,
+    quantity: Decimal,
+    eligible_quantity: i64,
+    untracked_quantity: Decimal,
+    next_eligible: Option<DateTime<Utc>>,
+    next_eligible_quantity: i64,
+    fully_eligible: Option<DateTime<Utc>>,
+    exempt_gain: Decimal,
+    taxable_gain: Decimal,
+    tax_today: Decimal,
+    saved_by_waiting: Decimal,
+}
+
+#[derive(Serialize)]
+struct TaxRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    year: i32,
+    ticker: &'a str,
+    figi: &'a str,
+    name: &'a str,
+    currency: &'static str,
+    realized: Decimal,
+    dividends: Decimal,
+    coupons: Decimal,
+    withheld: Decimal,
+    self_declared: Decimal,
+}
+
+#[derive(Serialize)]
+struct FlowRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    period: &'a str,
+    currency: &'static str,
+    deposits: Decimal,
+    withdrawals: Decimal,
+    net_contributions: Decimal,
+    income: Decimal,
+    costs: Decimal,
+}
+
+#[derive(Serialize)]
+struct IisRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    year: i32,
+    contributions: Decimal,
+    limit: Option<Decimal>,
+    excess: Decimal,
+    remaining: Option<Decimal>,
+    deduction: Decimal,
+}
+
+impl<'a, P: Profit> From<(&'static str, &'a Paper<P>)> for PaperRow<'a> {
+    fn from((asset, paper): (&'static str, &'a Paper<P>)) -> Self {
+        Self {
+            account_id: None,
+            asset,
+            ticker: paper.ticker.as_str(),
+            figi: paper.figi.as_str(),
+            name: &paper.name,
+            currency: paper.currency().code(),
+            quantity: paper.quantity(),
+            average_buy_price: paper.average_buy_price().value,
+            current_price: paper.current_instrument_price().value,
+            balance: paper.balance().value,
+            current: paper.current().value,
+            dividends: paper.totals.additional_profit.value,
+            fees: paper.totals.fees.value,
+            income: paper.current().value - paper.balance().value,
+            realized: paper.realized().value,
+            xirr: paper.returns().xirr,
+            twr: paper.returns().twr,
+            holding_days: paper.holding_days(paper.totals.today),
+            cagr: paper.cagr(paper.totals.today),
+            yield_on_cost: paper.yield_on_cost(),
+        }
+    }
+}
+
+impl<'a> From<&'a DividendPayment> for PaymentRow<'a> {
+    fn from(payment: &'a DividendPayment) -> Self {
+        Self {
+            account_id: None,
+            kind: "dividend",
+            payment_date: payment.payment_date(),
+            ex_date: payment.ex_date(),
+            ticker: payment.ticker.as_str(),
+            figi: payment.figi.as_str(),
+            name: &payment.name,
+            currency: payment.currency.code(),
+            quantity: payment.quantity,
+            payment_per_unit: payment.dividend_per_share.value,
+            total_payment: payment.total_dividend.value,
+            payment_type: &payment.dividend_type,
+        }
+    }
+}
+
+impl<'a> From<&'a CouponPayment> for PaymentRow<'a> {
+    fn from(payment: &'a CouponPayment) -> Self {
+        Self {
+            account_id: None,
+            kind: "coupon",
+            payment_date: payment.payment_date(),
+            ex_date: payment.ex_date(),
+            ticker: payment.ticker.as_str(),
+            figi: payment.figi.as_str(),
+            name: &payment.name,
+            currency: payment.currency.code(),
+            quantity: payment.quantity,
+            payment_per_unit: payment.coupon_per_bond.value,
+            total_payment: payment.total_coupon.value,
+            payment_type: &payment.coupon_type,
+        }
+    }
+}
+
+impl<'a> From<&'a CombinedPayment> for PaymentRow<'a> {
+    fn from(payment: &'a CombinedPayment) -> Self {
+        match payment {
+            CombinedPayment::Dividend(d) => Self::from(d),
+            CombinedPayment::Coupon(c) => Self::from(c),
+        }
+    }
+}
+
+fn write_papers<W: io::Write, P: Profit>(
+    writer: &mut csv::Writer<W>,
+    account_id: Option<&str>,
+    asset: &Asset<P>,
+) -> csv::Result<()> {
+    for paper in asset.papers() {
+        writer.serialize(PaperRow {
+            account_id,
+            ..PaperRow::from((asset.name, paper))
+        })?;
+    }
+    Ok(())
+}
+
+fn write_payments<'a, W, T>(
+    writer: &mut csv::Writer<W>,
+    account_id: Option<&'a str>,
+    payments: &'a [T],
+) -> csv::Result<()>
+where
+    W: io::Write,
+    PaymentRow<'a>: From<&'a T>,
+{
+    for payment in payments {
+        writer.serialize(PaymentRow {
+            account_id,
+            ..PaymentRow::from(payment)
+        })?;
+    }
+    Ok(())
+}
+
+impl ToCsv for Portfolio {
+    /// Writes every paper regardless of the aggregate mode, since an export
+    /// without positions is useless.
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        write_papers(writer, account_id, &self.etfs)?;
+        write_papers(writer, account_id, &self.futures)?;
+        write_papers(writer, account_id, &self.bonds)?;
+        write_papers(writer, account_id, &self.shares)?;
+        write_papers(writer, account_id, &self.currencies)
+    }
+}
+
+impl ToCsv for DividendCalendar {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        write_payments(writer, account_id, &self.upcoming)
+    }
+}
+
+impl ToCsv for CouponCalendar {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        write_payments(writer, account_id, &self.upcoming)
+    }
+}
+
+impl ToCsv for CombinedCalendar {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        write_payments(writer, account_id, &self.upcoming)
+    }
+}
+
+impl ToCsv for AccountList {
+    /// Account rows already carry their id, so `account_id` is ignored
+    fn write_account_csv<W: io::Write>(
+        &self,
+        _account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for account in &self.accounts {
+            writer.serialize(account)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ToCsv> ToCsv for Consolidated<T> {
+    /// Writes every account with its id, the total is left out since it repeats the same rows.
+    fn write_account_csv<W: io::Write>(
+        &self,
+        _account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for account in &self.accounts {
+            account
+                .data
+                .write_account_csv(Some(&account.account_id), writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: ToCsv> ToCsv for AccountsView<T> {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        match self {
+            Self::Single(data) => data.write_account_csv(account_id, writer),
+            Self::All(consolidated) => consolidated.write_account_csv(account_id, writer),
+        }
+    }
+}
+
+impl ToCsv for History {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for item in &self.items {
+            writer.serialize(HistoryRow {
+                account_id,
+                ticker: &self.ticker,
+                figi: &self.figi,
+                datetime: item.datetime,
+                quantity: item.quantity - item.quantity_rest,
+                price: item.price.value,
+                payment: item.payment.value,
+                currency: item.payment.currency.code(),
+                description: &item.description,
+                state: item.operation_state,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl ToCsv for LdvReport {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for position in &self.positions {
+            writer.serialize(LdvRow {
+                account_id,
+                ticker: &position.ticker,
+                figi: &position.figi,
+                name: &position.name,
+                currency: position.currency().code(),
+                quantity: position.quantity,
+                eligible_quantity: position.eligible_quantity(),
+                untracked_quantity: position.untracked_quantity(),
+                next_eligible: position.next_eligible(),
+                next_eligible_quantity: position.next_eligible_quantity(),
+                fully_eligible: position.fully_eligible(),
+                exempt_gain: position.exempt_gain().value,
+                taxable_gain: position.taxable_gain().value,
+                tax_today: position.tax_today().value,
+                saved_by_waiting: position.saved_by_waiting().value,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl ToCsv for TaxReport {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for tax in &self.instruments {
+            let self_declared = tax.self_declared();
+            let bags = [
+                &tax.realized,
+                &tax.dividends,
+                &tax.coupons,
+                &tax.withheld,
+                &self_declared,
+            ];
+            // A row per currency the instrument had income or tax in
+            let currencies = bags
+                .iter()
+                .flat_map(|bag| bag.currencies())
+                .collect::<BTreeSet<_>>();
+            for currency in currencies {
+                let value = |bag: &MoneyBag| bag.get(currency).value;
+                writer.serialize(TaxRow {
+                    account_id,
+                    year: self.year,
+                    ticker: &tax.ticker,
+                    figi: &tax.figi,
+                    name: &tax.name,
+                    currency: currency.code(),
+                    realized: value(&tax.realized),
+                    dividends: value(&tax.dividends),
+                    coupons: value(&tax.coupons),
+                    withheld: value(&tax.withheld),
+                    self_declared: value(&self_declared),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToCsv for CashFlow {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for row in &self.rows {
+            let net_contributions = row.net_contributions();
+            let bags = [&row.deposits, &row.withdrawals, &row.income, &row.costs];
+            // A row per currency money moved in within the period
+            let currencies = bags
+                .iter()
+                .flat_map(|bag| bag.currencies())
+                .collect::<BTreeSet<_>>();
+            for currency in currencies {
+                let value = |bag: &MoneyBag| bag.get(currency).value;
+                writer.serialize(FlowRow {
+                    account_id,
+                    period: &row.period,
+                    currency: currency.code(),
+                    deposits: value(&row.deposits),
+                    withdrawals: value(&row.withdrawals),
+                    net_contributions: value(&net_contributions),
+                    income: value(&row.income),
+                    costs: value(&row.costs),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToCsv for IisReport {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for year in &self.years {
+            writer.serialize(IisRow {
+                account_id,
+                year: year.year,
+                contributions: year.contributions.value,
+                limit: year.limit.map(|limit| limit.value),
+                excess: year.excess().value,
+                remaining: year.remaining().map(|remaining| remaining.value),
+                deduction: year.deduction().value,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl ToCsv for Journal {
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()> {
+        for item in &self.items {
+            writer.serialize(JournalRow {
+                account_id,
+                datetime: item.datetime,
+                kind: item.kind.name(),
+                ticker: &item.ticker,
+                figi: &item.figi,
+                quantity: item.quantity,
+                price: item.price.value,
+                payment: item.payment.value,
+                currency: item.payment.currency.code(),
+                description: &item.description,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use iso_currency::Currency;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::{
+        AccountSection, DividendProfit, Figi, HistoryItem, Money, Period, Position, TaxLots,
+        Ticker, Totals,
+    };
+    use crate::output::to_csv;
+
+    #[test]
+    fn portfolio_rows_use_plain_decimal_separator() {
+        let currency = Currency::RUB;
+        let mut portfolio = Portfolio::new(false);
+        portfolio.shares.add_paper(Paper {
+            name: "Sberbank".to_string(),
+            ticker: Ticker::new("SBER"),
+            figi: Figi::new("BBG004730N88"),
+            position: Position {
+                currency,
+                average_buy_price: Money::from_value(dec!(250.5), currency),
+                current_instrument_price: Money::from_value(dec!(300), currency),
+                quantity: dec!(10000),
+            },
+            totals: Totals {
+                additional_profit: Money::from_value(dec!(1234.56), currency),
+                fees: Money::from_value(dec!(-10), currency),
+                ..Totals::zero(currency)
+            },
+            profit: DividendProfit,
+        });
+
+        let csv = to_csv(&portfolio).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next().unwrap(),
+            "asset,ticker,figi,name,currency,quantity,average_buy_price,current_price,balance,current,dividends,fees,income,realized,xirr,twr,holding_days,cagr,yield_on_cost"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "Shares,SBER,BBG004730N88,Sberbank,RUB,10000,250.5,300,2505000.0,3000000,1234.56,-10,495000.0,0,,,,,0"
+        );
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn consolidated_rows_start_with_account_id() {
+        let payment = |figi: &str| DividendPayment {
+            figi: Figi::new(figi),
+            ticker: Ticker::new("SBER"),
+            name: "Sberbank".to_string(),
+            currency: Currency::RUB,
+            dividend_per_share: Money::from_value(dec!(33.3), Currency::RUB),
+            total_dividend: Money::from_value(dec!(333), Currency::RUB),
+            quantity: dec!(10),
+            ex_dividend_date: Utc.with_ymd_and_hms(2025, 7, 18, 0, 0, 0).unwrap(),
+            payment_date: None,
+            dividend_type: String::new(),
+        };
+        let accounts = vec![
+            AccountSection::new(
+                "100",
+                "Broker",
+                DividendCalendar {
+                    upcoming: vec![payment("BBG004730N88")],
+                    ..Default::default()
+                },
+            ),
+            AccountSection::new(
+                "200",
+                "IIS",
+                DividendCalendar {
+                    upcoming: vec![payment("BBG004730N88")],
+                    ..Default::default()
+                },
+            ),
+        ];
+        let consolidated = Consolidated::new(accounts, |a| {
+            DividendCalendar::merge(a.iter().map(|s| &s.data))
+        });
+
+        let csv = to_csv(&consolidated).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("account_id,kind,"));
+        assert!(lines[1].starts_with("100,dividend,"));
+        assert!(lines[2].starts_with("200,dividend,"));
+    }
+
+    #[test]
+    fn history_rows_quote_descriptions() {
+        let history = History {
+            name: "Sberbank".to_string(),
+            ticker: "SBER".to_string(),
+            figi: "BBG004730N88".to_string(),
+            currency: Currency::RUB,
+            items: vec![HistoryItem {
+                datetime: Utc.with_ymd_and_hms(2025, 3, 15, 10, 0, 0).unwrap(),
+                quantity: 10,
+                quantity_rest: 0,
+                price: Money::from_value(dec!(300.25), Currency::RUB),
+                payment: Money::from_value(dec!(-3002.5), Currency::RUB),
+                description: "Buy, market".to_string(),
+                operation_state: "Executed",
+            }],
+            period: Period::all(),
+            lots: TaxLots::default(),
+        };
+
+        let csv = to_csv(&history).unwrap();
+
+        assert!(csv.contains(
+            "SBER,BBG004730N88,2025-03-15T10:00:00Z,10,300.25,-3002.5,RUB,\"Buy, market\",Executed"
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use iso_currency::Currency;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use super::money::{Money, MoneyBag};
+
+/// Currency all exchange rates are quoted against (the Moscow exchange settles currencies in rubles)
+pub const BASE_CURRENCY: Currency = Currency::RUB;
+
+/// Exchange rates used to bring money in different currencies to a single reporting currency.
+///
+/// Rates are stored as the price of one currency unit in [`BASE_CURRENCY`].
+#[derive(Debug, Clone, Serialize)]
+pub struct FxRates {
+    reporting_currency: Currency,
+    rates: BTreeMap<Currency, Decimal>,
+}
+
+/// Exchange rate of a single currency into the reporting currency
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FxRate {
+    pub currency: Currency,
+    pub reporting_currency: Currency,
+    /// Price of one `currency` unit in `reporting_currency`
+    pub rate: Decimal,
+}
+
+impl Default for FxRates {
+    fn default() -> Self {
+        Self::new(BASE_CURRENCY)
+    }
+}
+
+impl FxRates {
+    /// Creates rates that only know the base currency
+    #[must_use]
+    pub fn new(reporting_currency: Currency) -> Self {
+        let mut rates = BTreeMap::new();
+        rates.insert(BASE_CURRENCY, Decimal::ONE);
+        Self {
+            reporting_currency,
+            rates,
+        }
+    }
+
+    /// Sets the price of one `currency` unit in [`BASE_CURRENCY`].
+    /// Non positive rates are ignored.
+    pub fn insert(&mut self, currency: Currency, rate: Decimal) {
+        if rate > Decimal::ZERO && currency != BASE_CURRENCY {
+            self.rates.insert(currency, rate);
+        }
+    }
+
+    #[must_use]
+    pub fn reporting_currency(&self) -> Currency {
+        self.reporting_currency
+    }
+
+    /// Price of one `currency` unit in the reporting currency
+    #[must_use]
+    pub fn rate(&self, currency: Currency) -> Option<Decimal> {
+        self.exchange(Decimal::ONE, currency)
+    }
+
+    /// Converts `value` in `currency` into the reporting currency.
+    /// Multiplies before dividing so that cross rates do not lose precision.
+    fn exchange(&self, value: Decimal, currency: Currency) -> Option<Decimal> {
+        if currency == self.reporting_currency {
+            return Some(value);
+        }
+        let from = self.rates.get(&currency)?;
+        let to = self.rates.get(&self.reporting_currency)?;
+        Some(value * from / to)
+    }
+
+    /// The same rates reporting into another currency
+    #[must_use]
+    pub fn with_reporting_currency(&self, reporting_currency: Currency) -> Self {
+        Self {
+            reporting_currency,
+            rates: self.rates.clone(),
+        }
+    }
+
+    /// Converts money into the reporting currency
+    /// or returns `None` if there is no rate for its currency.
+    #[must_use]
+    pub fn convert(&self, money: Money) -> Option<Money> {
+        self.exchange(money.value, money.currency)
+            .map(|value| Money::from_value(value, self.reporting_currency))
+    }
+
+    /// Sums the bag in the reporting currency.
+    ///
+    /// Amounts in currencies without a known rate are left out,
+    /// see [`FxRates::unconvertible`].
+    #[must_use]
+    pub fn total(&self, bag: &MoneyBag) -> Money {
+        let value = bag
+            .iter()
+            .filter_map(|money| self.exchange(money.value, money.currency))
+            .sum();
+        Money::from_value(value, self.reporting_currency)
+    }
+
+    /// Currencies among `currencies` that cannot be converted into the reporting currency
+    #[must_use]
+    pub fn unconvertible(&self, currencies: impl IntoIterator<Item = Currency>) -> Vec<Currency> {
+        let mut currencies: Vec<Currency> = currencies
+            .into_iter()
+            .filter(|c| self.rate(*c).is_none())
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        currencies
+    }
+
+    /// Rates of the given currencies into the reporting currency
+    /// (the reporting currency itself and unknown currencies are skipped)
+    #[must_use]
+    pub fn rates_for(&self, currencies: impl IntoIterator<Item = Currency>) -> Vec<FxRate> {
+        let mut currencies: Vec<Currency> = currencies
+            .into_iter()
+            .filter(|c| *c != self.reporting_currency)
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        currencies
+            .into_iter()
+            .filter_map(|currency| {
+                Some(FxRate {
+                    currency,
+                    reporting_currency: self.reporting_currency,
+                    rate: self.rate(currency)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn convert_into_base_currency() {
+        let mut fx = FxRates::new(Currency::RUB);
+        fx.insert(Currency::USD, dec!(90));
+
+        let converted = fx
+            .convert(Money::from_value(dec!(10), Currency::USD))
+            .unwrap();
+
+        assert_eq!(converted.value, dec!(900));
+        assert_eq!(converted.currency, Currency::RUB);
+    }
+
+    #[test]
+    fn convert_into_foreign_reporting_currency() {
+        let mut fx = FxRates::new(Currency::USD);
+        fx.insert(Currency::USD, dec!(90));
+        fx.insert(Currency::EUR, dec!(99));
+
+        let from_rub = fx
+            .convert(Money::from_value(dec!(900), Currency::RUB))
+            .unwrap();
+        let from_eur = fx
+            .convert(Money::from_value(dec!(10), Currency::EUR))
+            .unwrap();
+
+        assert_eq!(from_rub.value, dec!(10));
+        assert_eq!(from_rub.currency, Currency::USD);
+        assert_eq!(from_eur.value, dec!(11));
+    }
+
+    #[test]
+    fn convert_unknown_currency_fails() {
+        let fx = FxRates::new(Currency::RUB);
+
+        let converted = fx.convert(Money::from_value(dec!(10), Currency::CNY));
+
+        assert!(converted.is_none());
+        assert_eq!(
+            fx.unconvertible([Currency::CNY, Currency::RUB]),
+            [Currency::CNY]
+        );
+    }
+
+    #[test]
+    fn total_skips_unknown_currencies() {
+        let mut fx = FxRates::new(Currency::RUB);
+        fx.insert(Currency::USD, dec!(90));
+        let bag: MoneyBag = [
+            Money::from_value(dec!(100), Currency::RUB),
+            Money::from_value(dec!(2), Currency::USD),
+            Money::from_value(dec!(7), Currency::CNY),
+        ]
+        .into_iter()
+        .collect();
+
+        let total = fx.total(&bag);
+
+        assert_eq!(total, Money::from_value(dec!(280), Currency::RUB));
+    }
+
+    #[test]
+    fn rates_for_skips_reporting_and_unknown_currencies() {
+        let mut fx = FxRates::new(Currency::RUB);
+        fx.insert(Currency::USD, dec!(90));
+
+        let rates = fx.rates_for([Currency::RUB, Currency::USD, Currency::CNY, Currency::USD]);
+
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].currency, Currency::USD);
+        assert_eq!(rates[0].rate, dec!(90));
+    }
+}
@@ -1,12 +1,16 @@
 use chrono::{DateTime, Utc};
 use iso_currency::Currency;
 use itertools::Itertools;
+use serde::Serialize;
 use tinkoff_invest_api::tcs::{InstrumentShort, Operation, OperationState};
 
 use crate::{to_datetime_utc, to_money};
 
 use super::NumberRange;
-use super::money::Money;
+use super::lots::TaxLots;
+use super::money::{Money, MoneyBag};
+use super::operations::unique_operations;
+use super::period::Period;
 
 pub struct History {
     pub name: String,
@@ -14,8 +18,13 @@ pub struct History {
     pub figi: String,
     pub currency: Currency,
     pub items: Vec<HistoryItem>,
+    /// Period the items were made in
+    pub period: Period,
+    /// Purchases and sales matched first in, first out
+    pub lots: TaxLots,
 }
 
+#[derive(Serialize)]
 pub struct HistoryItem {
     pub datetime: DateTime<Utc>,
     pub quantity: i64,
@@ -27,10 +36,10 @@ pub struct HistoryItem {
 }
 
 impl History {
+    /// History of `operations` with the instrument. Sales are matched with purchases
+    /// among `operations`, so they should include every purchase made before.
     pub fn new(operations: &[Operation], instrument: &InstrumentShort) -> Option<Self> {
-        let items = operations
-            .iter()
-            .unique_by(|op| &op.id)
+        let items = unique_operations(operations)
             .map(HistoryItem::from)
             .sorted_by(|a, b| Ord::cmp(&a.datetime, &b.datetime))
             .collect_vec();
@@ -41,35 +50,63 @@ impl History {
             figi: instrument.figi.clone(),
             items,
             currency,
+            period: Period::all(),
+            lots: TaxLots::new(operations),
         })
     }
 
+    /// History of the instrument in several accounts together. Sales are matched
+    /// with purchases of the same account only.
+    pub fn of_accounts<'a>(
+        accounts: impl IntoIterator<Item = &'a [Operation]>,
+        instrument: &InstrumentShort,
+    ) -> Option<Self> {
+        let accounts = accounts.into_iter().collect_vec();
+        let operations = accounts.iter().copied().flatten().cloned().collect_vec();
+        let mut history = Self::new(&operations, instrument)?;
+        history.lots = accounts.into_iter().map(TaxLots::new).collect();
+        Some(history)
+    }
+
+    /// Keeps items and sales made within `period` only, so that totals cover the period.
+    /// Open lots are those held at the end of the history.
+    /// Returns `None` if there are no such items.
+    #[must_use]
+    pub fn within(mut self, period: Period) -> Option<Self> {
+        self.items.retain(|item| period.contains(item.datetime));
+        if self.items.is_empty() {
+            return None;
+        }
+        self.period = period;
+        self.lots = self.lots.within(period);
+        Some(self)
+    }
+
+    /// Payments may be in several currencies (e.g. dividends of a foreign share paid in rubles)
     #[must_use]
-    pub fn expenses(&self) -> Money {
+    pub fn expenses(&self) -> MoneyBag {
         self.sum(|i| i.payment.is_negative())
     }
 
     #[must_use]
-    pub fn profit(&self) -> Money {
+    pub fn profit(&self) -> MoneyBag {
         self.sum(|i| !i.payment.is_negative())
     }
 
     #[must_use]
-    pub fn balance(&self) -> Money {
-        self.expenses() + self.profit()
+    pub fn balance(&self) -> MoneyBag {
+        self.sum(|_| true)
     }
 
-    fn sum<P>(&self, predicate: P) -> Money
+    fn sum<P>(&self, predicate: P) -> MoneyBag
     where
         P: FnMut(&&HistoryItem) -> bool,
     {
         self.items
             .iter()
             .filter(predicate)
-            .fold(Money::zero(self.currency), |mut acc, p| {
-                acc += p.payment;
-                acc
-            })
+            .map(|i| i.payment)
+            .collect()
     }
 }
 
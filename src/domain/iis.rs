@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Months, Utc};
+use iso_currency::Currency;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::Operation;
+
+use crate::{to_datetime_utc, to_money};
+
+use super::journal::OperationKind;
+use super::money::Money;
+use super::operations::unique_operations;
+use super::tax::NDFL_RATE;
+
+/// First year accounts are opened under the rules of 2024 (IIS-3)
+pub const IIS_NEW_RULES_YEAR: i32 = 2024;
+
+/// Last year new accounts are opened with the shortest holding period of five years
+pub const IIS_FIVE_YEARS_UNTIL: i32 = 2026;
+
+/// Yearly contribution limit of accounts opened before [`IIS_NEW_RULES_YEAR`]
+pub const IIS_OLD_CONTRIBUTION_LIMIT: Decimal = dec!(1_000_000);
+
+/// Yearly contributions a type-A deduction is given for, per person
+pub const IIS_DEDUCTION_BASE: Decimal = dec!(400_000);
+
+/// Rules an individual investment account (IIS) was opened under
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IisKind {
+    /// Opened before 2024: limited contributions, held for three years
+    Old,
+    /// Opened since 2024: unlimited contributions, held for five to ten years
+    New,
+}
+
+/// Contributions of a calendar year
+#[derive(Clone)]
+pub struct IisYear {
+    pub year: i32,
+    pub contributions: Money,
+    /// Statutory contribution limit, `None` if there is none
+    pub limit: Option<Money>,
+}
+
+/// Money withdrawn from the account
+#[derive(Clone, Serialize)]
+pub struct IisWithdrawal {
+    pub datetime: DateTime<Utc>,
+    /// Amount withdrawn, positive
+    pub amount: Money,
+}
+
+/// Contributions, deductions and holding period of an IIS
+#[derive(Clone)]
+pub struct IisReport {
+    /// `None` if the opening date is unknown
+    pub opened: Option<DateTime<Utc>>,
+    /// Years with contributions, oldest first
+    pub years: Vec<IisYear>,
+    /// Withdrawals, oldest first
+    pub withdrawals: Vec<IisWithdrawal>,
+    /// Moment the holding period is checked at
+    pub today: DateTime<Utc>,
+}
+
+impl IisKind {
+    /// Rules of an account opened at `opened`
+    #[must_use]
+    pub fn of(opened: DateTime<Utc>) -> Self {
+        if opened.year() < IIS_NEW_RULES_YEAR {
+            Self::Old
+        } else {
+            Self::New
+        }
+    }
+
+    /// Years an account opened at `opened` must be held to keep the benefit:
+    /// three for old accounts, five for new ones opened in 2024–2026 and one more
+    /// for every later year up to ten
+    #[must_use]
+    pub fn holding_years(opened: DateTime<Utc>) -> u32 {
+        match Self::of(opened) {
+            Self::Old => 3,
+            Self::New => {
+                let later = u32::try_from(opened.year() - IIS_FIVE_YEARS_UNTIL).unwrap_or_default();
+                (5 + later).min(10)
+            }
+        }
+    }
+
+    /// Yearly contribution limit, `None` if contributions are unlimited
+    #[must_use]
+    pub fn contribution_limit(self) -> Option<Money> {
+        match self {
+            Self::Old => Some(Money::from_value(IIS_OLD_CONTRIBUTION_LIMIT, Currency::RUB)),
+            Self::New => None,
+        }
+    }
+}
+
+impl IisYear {
+    /// Contributions above the limit, zero within it
+    #[must_use]
+    pub fn excess(&self) -> Money {
+        let value = self.limit.map_or(Decimal::ZERO, |limit| {
+            (self.contributions.value - limit.value).max(Decimal::ZERO)
+        });
+        Money::from_value(value, Currency::RUB)
+    }
+
+    /// Contributions that can still be made this year, `None` if unlimited
+    #[must_use]
+    pub fn remaining(&self) -> Option<Money> {
+        self.limit.map(|limit| {
+            Money::from_value(
+                (limit.value - self.contributions.value).max(Decimal::ZERO),
+                Currency::RUB,
+            )
+        })
+    }
+
+    /// Estimated type-A deduction: tax refund of contributions up to [`IIS_DEDUCTION_BASE`]
+    #[must_use]
+    pub fn deduction(&self) -> Money {
+        let base = self.contributions.value.min(IIS_DEDUCTION_BASE);
+        Money::from_value((base * NDFL_RATE).round_dp(2), Currency::RUB)
+    }
+}
+
+impl IisReport {
+    /// Report of an account opened at `opened` from its deposit and withdrawal `operations`.
+    /// IIS are funded in rubles only, deposits in other currencies are ignored.
+    #[must_use]
+    pub fn new(
+        opened: Option<DateTime<Utc>>,
+        operations: &[Operation],
+        today: DateTime<Utc>,
+    ) -> Self {
+        let limit = opened.and_then(|opened| IisKind::of(opened).contribution_limit());
+        let mut contributions = BTreeMap::new();
+        let mut withdrawals = Vec::new();
+        for op in
+            unique_operations(operations).sorted_by_key(|op| to_datetime_utc(op.date.as_ref()))
+        {
+            let Some(payment) = to_money(op.payment.as_ref()) else {
+                continue;
+            };
+            let datetime = to_datetime_utc(op.date.as_ref());
+            match OperationKind::of(op.operation_type()) {
+                OperationKind::Deposit if payment.currency == Currency::RUB => {
+                    *contributions
+                        .entry(datetime.year())
+                        .or_insert(Decimal::ZERO) += payment.value;
+                }
+                OperationKind::Withdrawal => withdrawals.push(IisWithdrawal {
+                    datetime,
+                    amount: Money::from_value(payment.value.abs(), payment.currency),
+                }),
+                _ => {}
+            }
+        }
+        Self {
+            opened,
+            years: contributions
+                .into_iter()
+                .map(|(year, value)| IisYear {
+                    year,
+                    contributions: Money::from_value(value, Currency::RUB),
+                    limit,
+                })
+                .collect(),
+            withdrawals,
+            today,
+        }
+    }
+
+    /// Yearly contributions of several accounts together. The deduction base is per person,
+    /// so it limits the sum. Opening dates, limits and withdrawals of accounts differ
+    /// and are left out.
+    #[must_use]
+    pub fn merge<'a>(
+        today: DateTime<Utc>,
+        reports: impl IntoIterator<Item = &'a IisReport>,
+    ) -> Self {
+        let mut contributions = BTreeMap::new();
+        for year in reports.into_iter().flat_map(|report| &report.years) {
+            *contributions.entry(year.year).or_insert(Decimal::ZERO) += year.contributions.value;
+        }
+        Self {
+            opened: None,
+            years: contributions
+                .into_iter()
+                .map(|(year, value)| IisYear {
+                    year,
+                    contributions: Money::from_value(value, Currency::RUB),
+                    limit: None,
+                })
+                .collect(),
+            withdrawals: Vec::new(),
+            today,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> Option<IisKind> {
+        self.opened.map(IisKind::of)
+    }
+
+    /// Date the minimum holding period ends
+    #[must_use]
+    pub fn holding_until(&self) -> Option<DateTime<Utc>> {
+        self.opened.map(|opened| {
+            opened
+                .checked_add_months(Months::new(IisKind::holding_years(opened) * 12))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+        })
+    }
+
+    /// Whether the account has been held long enough to keep the benefit
+    #[must_use]
+    pub fn is_held(&self) -> bool {
+        self.holding_until()
+            .is_some_and(|until| until <= self.today)
+    }
+
+    /// Withdrawals made before the holding period ended. Any of them closes the account
+    /// and the deductions received must be paid back.
+    #[must_use]
+    pub fn voiding_withdrawals(&self) -> Vec<&IisWithdrawal> {
+        match self.holding_until() {
+            Some(until) => self
+                .withdrawals
+                .iter()
+                .filter(|w| w.datetime < until)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn contributions(&self) -> Money {
+        self.sum(|year| year.contributions)
+    }
+
+    /// Estimated deductions of all years
+    #[must_use]
+    pub fn deductions(&self) -> Money {
+        self.sum(IisYear::deduction)
+    }
+
+    /// Sum in rubles, amounts in other currencies are not counted
+    fn sum(&self, money: impl Fn(&IisYear) -> Money) -> Money {
+        self.years
+            .iter()
+            .fold(Money::zero(Currency::RUB), |sum, year| {
+                sum.checked_add(money(year)).unwrap_or(sum)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use tinkoff_invest_api::tcs::OperationType;
+
+    use super::*;
+    use crate::domain::test_support::operation;
+
+    fn report(opened: &str) -> IisReport {
+        let operations = [
+            operation("1", OperationType::Input, "2023-02-01T10:00:00Z", "300000"),
+            operation("2", OperationType::Input, "2023-11-01T10:00:00Z", "800000"),
+            operation("3", OperationType::Input, "2024-03-01T10:00:00Z", "150000"),
+            operation("4", OperationType::Output, "2024-06-01T10:00:00Z", "-50000"),
+            operation("5", OperationType::Buy, "2024-06-02T10:00:00Z", "-100000"),
+        ];
+        IisReport::new(
+            Some(opened.parse().unwrap()),
+            &operations,
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn contributions_above_limit_of_old_account() {
+        let report = report("2022-06-15T00:00:00Z");
+
+        assert_eq!(report.kind(), Some(IisKind::Old));
+        assert_eq!(report.years.len(), 2);
+        let first = &report.years[0];
+        assert_eq!(first.contributions.value, dec!(1_100_000));
+        assert_eq!(first.excess().value, dec!(100_000));
+        assert_eq!(first.deduction().value, dec!(52_000));
+        assert_eq!(report.years[1].remaining().unwrap().value, dec!(850_000));
+        assert_eq!(report.deductions().value, dec!(71_500));
+    }
+
+    #[test]
+    fn withdrawal_before_holding_period_ends_voids_benefit() {
+        let report = report("2022-06-15T00:00:00Z");
+
+        assert_eq!(
+            report.holding_until(),
+            Some("2025-06-15T00:00:00Z".parse().unwrap())
+        );
+        assert!(!report.is_held());
+        assert_eq!(report.voiding_withdrawals().len(), 1);
+        assert_eq!(report.withdrawals[0].amount.value, dec!(50_000));
+    }
+
+    #[test]
+    fn new_account_is_unlimited() {
+        let report = report("2024-01-10T00:00:00Z");
+
+        assert_eq!(report.kind(), Some(IisKind::New));
+        assert!(report.years[0].limit.is_none());
+        assert_eq!(report.years[0].excess().value, dec!(0));
+    }
+
+    #[test]
+    fn deposits_without_id_are_all_counted() {
+        let operations = [
+            operation("", OperationType::Input, "2023-02-01T10:00:00Z", "300000"),
+            operation("", OperationType::Input, "2023-03-01T10:00:00Z", "200000"),
+        ];
+
+        let report = IisReport::new(
+            Some("2022-06-15T00:00:00Z".parse().unwrap()),
+            &operations,
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        );
+
+        assert_eq!(report.contributions().value, dec!(500_000));
+    }
+
+    #[rstest]
+    #[case("2021-06-15T00:00:00Z", 3)]
+    #[case("2024-01-10T00:00:00Z", 5)]
+    #[case("2025-09-01T00:00:00Z", 5)]
+    #[case("2026-03-01T00:00:00Z", 5)]
+    #[case("2027-01-15T00:00:00Z", 6)]
+    #[case("2031-03-01T00:00:00Z", 10)]
+    fn holding_period_grows_for_new_accounts(#[case] opened: &str, #[case] years: u32) {
+        assert_eq!(IisKind::holding_years(opened.parse().unwrap()), years);
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use iso_currency::Currency;
+use itertools::Itertools;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::{to_datetime_utc, to_money};
+
+use super::money::{Money, MoneyBag};
+use super::operations::unique_operations;
+use super::paper::Instrument;
+use super::period::Period;
+
+/// Group of operation types shown and filtered together
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationKind {
+    Deposit,
+    Withdrawal,
+    Buy,
+    Sell,
+    Dividend,
+    Coupon,
+    Tax,
+    Fee,
+    /// Anything else, like bond repayments or variation margin
+    Other,
+}
+
+const DEPOSITS: &[OperationType] = &[
+    OperationType::Input,
+    OperationType::InputSwift,
+    OperationType::InputAcquiring,
+    OperationType::InpMulti,
+];
+
+const WITHDRAWALS: &[OperationType] = &[
+    OperationType::Output,
+    OperationType::OutputSwift,
+    OperationType::OutputAcquiring,
+    OperationType::OutMulti,
+];
+
+const BUYS: &[OperationType] = &[
+    OperationType::Buy,
+    OperationType::BuyCard,
+    OperationType::BuyMargin,
+    OperationType::DeliveryBuy,
+];
+
+const SELLS: &[OperationType] = &[
+    OperationType::Sell,
+    OperationType::SellCard,
+    OperationType::SellMargin,
+    OperationType::DeliverySell,
+];
+
+const DIVIDENDS: &[OperationType] = &[
+    OperationType::Dividend,
+    OperationType::DividendTransfer,
+    OperationType::DivExt,
+];
+
+const COUPONS: &[OperationType] = &[OperationType::Coupon];
+
+const TAXES: &[OperationType] = &[
+    OperationType::Tax,
+    OperationType::TaxProgressive,
+    OperationType::BondTax,
+    OperationType::BondTaxProgressive,
+    OperationType::DividendTax,
+    OperationType::DividendTaxProgressive,
+    OperationType::BenefitTax,
+    OperationType::BenefitTaxProgressive,
+    OperationType::TaxCorrection,
+    OperationType::TaxCorrectionProgressive,
+    OperationType::TaxCorrectionCoupon,
+    OperationType::TaxRepo,
+    OperationType::TaxRepoProgressive,
+    OperationType::TaxRepoHold,
+    OperationType::TaxRepoHoldProgressive,
+    OperationType::TaxRepoRefund,
+    OperationType::TaxRepoRefundProgressive,
+];
+
+const FEES: &[OperationType] = &[
+    OperationType::ServiceFee,
+    OperationType::MarginFee,
+    OperationType::BrokerFee,
+    OperationType::SuccessFee,
+    OperationType::TrackMfee,
+    OperationType::TrackPfee,
+    OperationType::CashFee,
+    OperationType::OutFee,
+    OperationType::OutStampDuty,
+    OperationType::AdviceFee,
+    OperationType::OutputPenalty,
+];
+
+impl OperationKind {
+    pub const ALL: [Self; 9] = [
+        Self::Deposit,
+        Self::Withdrawal,
+        Self::Buy,
+        Self::Sell,
+        Self::Dividend,
+        Self::Coupon,
+        Self::Tax,
+        Self::Fee,
+        Self::Other,
+    ];
+
+    #[must_use]
+    pub fn of(operation_type: OperationType) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.operation_types().contains(&operation_type))
+            .unwrap_or(Self::Other)
+    }
+
+    /// Operation types of the kind, empty for [`OperationKind::Other`]
+    #[must_use]
+    pub const fn operation_types(self) -> &'static [OperationType] {
+        match self {
+            Self::Deposit => DEPOSITS,
+            Self::Withdrawal => WITHDRAWALS,
+            Self::Buy => BUYS,
+            Self::Sell => SELLS,
+            Self::Dividend => DIVIDENDS,
+            Self::Coupon => COUPONS,
+            Self::Tax => TAXES,
+            Self::Fee => FEES,
+            Self::Other => &[],
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Buy => "buy",
+            Self::Sell => "sell",
+            Self::Dividend => "dividend",
+            Self::Coupon => "coupon",
+            Self::Tax => "tax",
+            Self::Fee => "fee",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Executed operation of an account with any instrument
+#[derive(Serialize)]
+pub struct JournalItem {
+    pub datetime: DateTime<Utc>,
+    pub kind: OperationKind,
+    /// Ticker of the instrument, empty for operations without one or with an unknown instrument
+    pub ticker: String,
+    pub figi: String,
+    pub quantity: i64,
+    pub price: Money,
+    pub payment: Money,
+    pub description: String,
+}
+
+/// Operations of the same kind made in a month or over the whole journal
+#[derive(Serialize)]
+pub struct Subtotal {
+    /// `YYYY-MM`, `None` for totals over the whole journal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub month: Option<String>,
+    pub kind: OperationKind,
+    pub count: usize,
+    /// Payments may be in several currencies
+    pub total: MoneyBag,
+}
+
+/// Executed operations of an account across all instruments, oldest first
+pub struct Journal {
+    pub items: Vec<JournalItem>,
+    /// Period the operations were made in
+    pub period: Period,
+}
+
+impl JournalItem {
+    #[must_use]
+    pub fn new(op: &Operation, instruments: &HashMap<String, Instrument>) -> Self {
+        let currency =
+            Currency::from_code(&op.currency.to_ascii_uppercase()).unwrap_or(Currency::RUB);
+        Self {
+            datetime: to_datetime_utc(op.date.as_ref()),
+            kind: OperationKind::of(op.operation_type()),
+            ticker: instruments
+                .get(&op.figi)
+                .map(|i| i.ticker.as_str().to_string())
+                .unwrap_or_default(),
+            figi: op.figi.clone(),
+            quantity: op.quantity - op.quantity_rest,
+            price: to_money(op.price.as_ref()).unwrap_or_else(|| Money::zero(currency)),
+            payment: to_money(op.payment.as_ref()).unwrap_or_else(|| Money::zero(currency)),
+            description: op.r#type.clone(),
+        }
+    }
+}
+
+impl Journal {
+    /// Journal of `operations` made within `period`, each operation once.
+    /// Tickers are taken from `instruments` by FIGI.
+    #[must_use]
+    pub fn new(
+        operations: &[Operation],
+        instruments: &HashMap<String, Instrument>,
+        period: Period,
+    ) -> Self {
+        let items = unique_operations(operations)
+            .map(|op| JournalItem::new(op, instruments))
+            .filter(|item| period.contains(item.datetime))
+            .sorted_by_key(|item| item.datetime)
+            .collect();
+        Self { items, period }
+    }
+
+    /// Keeps operations of `kinds` only, all of them if `kinds` is empty
+    #[must_use]
+    pub fn with_kinds(mut self, kinds: &[OperationKind]) -> Self {
+        if !kinds.is_empty() {
+            self.items.retain(|item| kinds.contains(&item.kind));
+        }
+        self
+    }
+
+    /// Subtotals by month and kind, in chronological order
+    #[must_use]
+    pub fn subtotals(&self) -> Vec<Subtotal> {
+        self.group_by(|item| Some(item.datetime.format("%Y-%m").to_string()))
+    }
+
+    /// Totals by kind over the whole journal
+    #[must_use]
+    pub fn totals(&self) -> Vec<Subtotal> {
+        self.group_by(|_| None)
+    }
+
+    fn group_by<F>(&self, month: F) -> Vec<Subtotal>
+    where
+        F: Fn(&JournalItem) -> Option<String>,
+    {
+        let mut groups: BTreeMap<(Option<String>, OperationKind), (usize, MoneyBag)> =
+            BTreeMap::new();
+        for item in &self.items {
+            let (count, total) = groups.entry((month(item), item.kind)).or_default();
+            *count += 1;
+            *total += item.payment;
+        }
+        groups
+            .into_iter()
+            .map(|((month, kind), (count, total))| Subtotal {
+                month,
+                kind,
+                count,
+                total,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::{
+        Ticker,
+        test_support::{of, operation},
+    };
+
+    fn journal(period: Period) -> Journal {
+        let operations = [
+            operation("1", OperationType::Input, "2025-01-05T10:00:00Z", "10000"),
+            operation("2", OperationType::Buy, "2025-01-10T10:00:00Z", "-2500"),
+            operation(
+                "3",
+                OperationType::BrokerFee,
+                "2025-01-10T10:00:00Z",
+                "-7.5",
+            ),
+            operation("4", OperationType::Buy, "2025-01-20T10:00:00Z", "-2600"),
+            operation("4", OperationType::Buy, "2025-01-20T10:00:00Z", "-2600"),
+            operation("5", OperationType::Dividend, "2025-07-20T10:00:00Z", "330"),
+            operation(
+                "6",
+                OperationType::DividendTax,
+                "2025-07-20T10:00:00Z",
+                "-43",
+            ),
+        ]
+        .map(|op| of("BBG004730N88", op));
+        let instruments = HashMap::from([(
+            "BBG004730N88".to_string(),
+            Instrument {
+                name: "Сбер Банк".to_string(),
+                ticker: Ticker::new("SBER".to_string()),
+                country: "RU".to_string(),
+            },
+        )]);
+        Journal::new(&operations, &instruments, period)
+    }
+
+    #[test]
+    fn operation_types_are_grouped_into_kinds() {
+        assert_eq!(
+            OperationKind::of(OperationType::Input),
+            OperationKind::Deposit
+        );
+        assert_eq!(
+            OperationKind::of(OperationType::DividendTaxProgressive),
+            OperationKind::Tax
+        );
+        assert_eq!(
+            OperationKind::of(OperationType::BrokerFee),
+            OperationKind::Fee
+        );
+        assert_eq!(
+            OperationKind::of(OperationType::BondRepayment),
+            OperationKind::Other
+        );
+    }
+
+    #[test]
+    fn subtotals_are_grouped_by_month_and_kind() {
+        let journal = journal(Period::all());
+
+        let subtotals = journal.subtotals();
+
+        assert_eq!(journal.items.len(), 6);
+        assert_eq!(journal.items[1].ticker, "SBER");
+        let buys = &subtotals[1];
+        assert_eq!(buys.month.as_deref(), Some("2025-01"));
+        assert_eq!(buys.kind, OperationKind::Buy);
+        assert_eq!(buys.count, 2);
+        assert_eq!(buys.total.get(Currency::RUB).value, dec!(-5100));
+        assert_eq!(subtotals.len(), 5);
+        assert_eq!(subtotals[3].month.as_deref(), Some("2025-07"));
+    }
+
+    #[test]
+    fn totals_cover_the_period() {
+        let journal = journal(Period::between(
+            None,
+            chrono::NaiveDate::from_ymd_opt(2025, 6, 30),
+        ));
+
+        let totals = journal.totals();
+
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[1].kind, OperationKind::Buy);
+        assert_eq!(totals[1].count, 2);
+        assert!(totals.iter().all(|t| t.month.is_none()));
+    }
+
+    #[test]
+    fn operations_without_id_are_not_merged() {
+        let operations = [
+            operation("", OperationType::Input, "2025-01-05T10:00:00Z", "10000"),
+            operation("", OperationType::Input, "2025-02-05T10:00:00Z", "5000"),
+        ];
+
+        let journal = Journal::new(&operations, &HashMap::new(), Period::all());
+
+        assert_eq!(journal.items.len(), 2);
+        assert_eq!(journal.totals()[0].count, 2);
+    }
+}
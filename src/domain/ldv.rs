@@ -0,0 +1,429 @@
+use chrono::{DateTime, Datelike, Months, Utc};
+use iso_currency::Currency;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+
+use super::fx::FxRates;
+use super::lots::TaxLots;
+use super::money::{Money, MoneyBag};
+use super::skipped::{SkippedPosition, extend_unique};
+use super::tax::ndfl;
+
+/// Months a lot must be held for its gain to be exempt from personal income tax
+pub const LDV_HOLDING_MONTHS: u32 = 36;
+
+/// Gain exempt in a year for every full year a security was held, in rubles
+pub const LDV_YEARLY_CAP: Decimal = dec!(3_000_000);
+
+/// Open lot of a position with the date its gain becomes tax exempt
+#[derive(Clone, Serialize)]
+pub struct LdvLot {
+    pub acquired: DateTime<Utc>,
+    pub eligible_from: DateTime<Utc>,
+    pub quantity: i64,
+    /// Cost of a unit
+    pub price: Money,
+    /// Gain of the lot at the current price, a loss if negative
+    pub gain: Money,
+}
+
+/// Long-term ownership benefit (LDV) state of a held security
+#[derive(Clone)]
+pub struct LdvPosition {
+    pub name: String,
+    pub ticker: String,
+    pub figi: String,
+    /// Units held according to the portfolio
+    pub quantity: Decimal,
+    pub current_price: Money,
+    /// Open lots, oldest first
+    pub lots: Vec<LdvLot>,
+    /// Moment eligibility is checked at
+    pub today: DateTime<Utc>,
+    /// Rates to convert the yearly cap of the exempt gain into the currency of the position
+    fx: FxRates,
+}
+
+/// LDV state of every held security of an account
+pub struct LdvReport {
+    pub today: DateTime<Utc>,
+    pub positions: Vec<LdvPosition>,
+    /// Positions that failed to load
+    pub skipped: Vec<SkippedPosition>,
+}
+
+impl LdvLot {
+    #[must_use]
+    pub fn is_eligible(&self, today: DateTime<Utc>) -> bool {
+        self.eligible_from <= today
+    }
+}
+
+impl LdvPosition {
+    /// Position of `quantity` units priced at `current_price` which open lots are in `lots`.
+    /// Lots bought in another currency than the price are left untracked
+    #[must_use]
+    pub fn new(
+        name: String,
+        ticker: String,
+        figi: String,
+        quantity: Decimal,
+        current_price: Money,
+        lots: &TaxLots,
+        today: DateTime<Utc>,
+    ) -> Self {
+        let lots = lots
+            .open
+            .iter()
+            .filter(|lot| lot.price.currency == current_price.currency)
+            .map(|lot| {
+                let units = Decimal::from(lot.quantity);
+                LdvLot {
+                    acquired: lot.acquired,
+                    eligible_from: lot
+                        .acquired
+                        .checked_add_months(Months::new(LDV_HOLDING_MONTHS))
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                    quantity: lot.quantity,
+                    price: lot.price,
+                    gain: Money::from_value(
+                        (current_price.value - lot.price.value) * units,
+                        current_price.currency,
+                    ),
+                }
+            })
+            .collect();
+        Self {
+            name,
+            ticker,
+            figi,
+            quantity,
+            current_price,
+            lots,
+            today,
+            fx: FxRates::default(),
+        }
+    }
+
+    /// Sets the rates the yearly cap of the exempt gain is converted with
+    #[must_use]
+    pub fn with_fx(mut self, fx: FxRates) -> Self {
+        self.fx = fx;
+        self
+    }
+
+    #[must_use]
+    pub fn currency(&self) -> Currency {
+        self.current_price.currency
+    }
+
+    /// Units whose gain is already exempt
+    #[must_use]
+    pub fn eligible_quantity(&self) -> i64 {
+        self.eligible().map(|lot| lot.quantity).sum()
+    }
+
+    /// Units held without a known purchase, e.g. transferred from another broker,
+    /// or bought in another currency than the current price
+    #[must_use]
+    pub fn untracked_quantity(&self) -> Decimal {
+        let tracked: i64 = self.lots.iter().map(|lot| lot.quantity).sum();
+        (self.quantity - Decimal::from(tracked)).max(Decimal::ZERO)
+    }
+
+    /// Date the next lots become eligible, `None` if all of them are
+    #[must_use]
+    pub fn next_eligible(&self) -> Option<DateTime<Utc>> {
+        self.waiting().map(|lot| lot.eligible_from).min()
+    }
+
+    /// Units becoming eligible on [`LdvPosition::next_eligible`]
+    #[must_use]
+    pub fn next_eligible_quantity(&self) -> i64 {
+        self.next_lots().map(|lot| lot.quantity).sum()
+    }
+
+    /// Date every open lot is eligible, `None` if they already are
+    #[must_use]
+    pub fn fully_eligible(&self) -> Option<DateTime<Utc>> {
+        self.waiting().map(|lot| lot.eligible_from).max()
+    }
+
+    /// Gain of eligible lots that can be taken without tax today, up to the yearly cap
+    /// as if the position were the only one sold in the year
+    #[must_use]
+    pub fn exempt_gain(&self) -> Money {
+        ldv_exempt_gain(
+            self.sum(self.eligible().map(|lot| lot.gain)),
+            self.eligible().map(|lot| (lot.acquired, lot.quantity)),
+            self.today,
+            &self.fx,
+        )
+    }
+
+    /// Gain of lots not eligible yet and of eligible ones above the yearly cap,
+    /// losses offset gains
+    #[must_use]
+    pub fn taxable_gain(&self) -> Money {
+        let gain = self.sum(self.lots.iter().map(|lot| lot.gain));
+        gain.checked_sub(self.exempt_gain()).unwrap_or(gain)
+    }
+
+    /// Tax due if the whole position is sold today
+    #[must_use]
+    pub fn tax_today(&self) -> Money {
+        ndfl(self.taxable_gain(), self.today.year())
+    }
+
+    /// Tax saved by selling after [`LdvPosition::next_eligible`] instead of today,
+    /// at current prices
+    #[must_use]
+    pub fn saved_by_waiting(&self) -> Money {
+        let today = self.tax_today();
+        let Some(next) = self.next_eligible() else {
+            return Money::zero(self.currency());
+        };
+        let later = Self {
+            today: next,
+            ..self.clone()
+        };
+        today.checked_sub(later.tax_today()).unwrap_or(today)
+    }
+
+    fn eligible(&self) -> impl Iterator<Item = &LdvLot> {
+        self.lots.iter().filter(|lot| lot.is_eligible(self.today))
+    }
+
+    fn waiting(&self) -> impl Iterator<Item = &LdvLot> {
+        self.lots.iter().filter(|lot| !lot.is_eligible(self.today))
+    }
+
+    fn next_lots(&self) -> impl Iterator<Item = &LdvLot> {
+        let next = self.next_eligible();
+        self.waiting()
+            .filter(move |lot| Some(lot.eligible_from) == next)
+    }
+
+    /// Sum of `gains`, those in another currency than the price are not counted
+    fn sum<'a>(&self, gains: impl Iterator<Item = Money> + 'a) -> Money {
+        gains.fold(Money::zero(self.currency()), |sum, gain| {
+            sum.checked_add(gain).unwrap_or(sum)
+        })
+    }
+}
+
+impl LdvReport {
+    /// Positions of all `reports` together, e.g. of every account
+    #[must_use]
+    pub fn merge<'a>(
+        today: DateTime<Utc>,
+        reports: impl IntoIterator<Item = &'a LdvReport>,
+    ) -> Self {
+        let mut total = Self {
+            today,
+            positions: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for report in reports {
+            total.positions.extend(report.positions.iter().cloned());
+            extend_unique(&mut total.skipped, &report.skipped);
+        }
+        total
+    }
+
+    /// Exempt gains of all positions, per currency
+    #[must_use]
+    pub fn exempt_gain(&self) -> MoneyBag {
+        self.positions
+            .iter()
+            .map(LdvPosition::exempt_gain)
+            .collect()
+    }
+
+    /// Tax due if every position is sold today, per currency
+    #[must_use]
+    pub fn tax_today(&self) -> MoneyBag {
+        self.positions.iter().map(LdvPosition::tax_today).collect()
+    }
+
+    /// Tax saved by selling every position after its next lots become eligible, per currency
+    #[must_use]
+    pub fn saved_by_waiting(&self) -> MoneyBag {
+        self.positions
+            .iter()
+            .map(LdvPosition::saved_by_waiting)
+            .collect()
+    }
+}
+
+/// Part of `gain` of eligible `lots` (purchase date and units) sold at `today` that is exempt:
+/// up to [`LDV_YEARLY_CAP`] for every full year held, the years weighted by units of each lot.
+/// Losses are kept whole. No gain is exempt if the cap cannot be converted with `fx`.
+#[must_use]
+pub fn ldv_exempt_gain(
+    gain: Money,
+    lots: impl IntoIterator<Item = (DateTime<Utc>, i64)>,
+    today: DateTime<Utc>,
+    fx: &FxRates,
+) -> Money {
+    let (units, unit_years) = lots.into_iter().fold(
+        (0_i64, 0_i64),
+        |(units, unit_years), (acquired, quantity)| {
+            let years = today.years_since(acquired).unwrap_or_default();
+            (units + quantity, unit_years + quantity * i64::from(years))
+        },
+    );
+    let years = if units == 0 {
+        Decimal::ZERO
+    } else {
+        Decimal::from(unit_years) / Decimal::from(units)
+    };
+    let cap = fx
+        .with_reporting_currency(gain.currency)
+        .convert(Money::from_value(LDV_YEARLY_CAP * years, Currency::RUB))
+        .map_or(Decimal::ZERO, |cap| cap.value);
+    Money::from_value(gain.value.min(cap), gain.currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use tinkoff_invest_api::tcs::OperationType;
+
+    use super::*;
+    use crate::domain::test_support::{paid, trade};
+
+    fn position(price: Decimal) -> LdvPosition {
+        let lots = TaxLots::new(&[
+            trade("1", OperationType::Buy, "2021-03-01T10:00:00Z", 10, "-1000"),
+            trade("2", OperationType::Buy, "2023-05-10T10:00:00Z", 10, "-2000"),
+            trade("3", OperationType::Buy, "2024-02-01T10:00:00Z", 5, "-1500"),
+        ]);
+        LdvPosition::new(
+            "Sberbank".to_string(),
+            "SBER".to_string(),
+            "BBG004730N88".to_string(),
+            dec!(30),
+            Money::from_value(price, Currency::RUB),
+            &lots,
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn lots_held_three_years_are_eligible() {
+        let position = position(dec!(250));
+
+        assert_eq!(position.eligible_quantity(), 10);
+        assert_eq!(position.untracked_quantity(), dec!(5));
+        assert_eq!(
+            position.next_eligible(),
+            Some("2026-05-10T10:00:00Z".parse().unwrap())
+        );
+        assert_eq!(position.next_eligible_quantity(), 10);
+        assert_eq!(
+            position.fully_eligible(),
+            Some("2027-02-01T10:00:00Z".parse().unwrap())
+        );
+        assert_eq!(position.exempt_gain().value, dec!(1500));
+    }
+
+    #[test]
+    fn waiting_saves_tax_of_next_lots() {
+        let position = position(dec!(250));
+
+        // 10 × (250 − 200) − 5 × (300 − 250) = 250 taxable
+        assert_eq!(position.taxable_gain().value, dec!(250));
+        assert_eq!(position.tax_today().value, dec!(32.50));
+        assert_eq!(position.saved_by_waiting().value, dec!(32.50));
+    }
+
+    #[test]
+    fn lots_in_another_currency_are_untracked() {
+        let usd = paid(
+            trade("4", OperationType::Buy, "2021-04-01T10:00:00Z", 5, "-50"),
+            dec!(-50),
+            "usd",
+        );
+        let lots = TaxLots::new(&[
+            trade("1", OperationType::Buy, "2021-03-01T10:00:00Z", 10, "-1000"),
+            usd,
+        ]);
+        let position = LdvPosition::new(
+            "Sberbank".to_string(),
+            "SBER".to_string(),
+            "BBG004730N88".to_string(),
+            dec!(15),
+            Money::from_value(dec!(250), Currency::RUB),
+            &lots,
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        );
+
+        assert_eq!(position.eligible_quantity(), 10);
+        assert_eq!(position.untracked_quantity(), dec!(5));
+        assert_eq!(position.exempt_gain().value, dec!(1500));
+    }
+
+    #[test]
+    fn losses_are_not_taxed() {
+        let position = position(dec!(150));
+
+        assert_eq!(position.tax_today().value, dec!(0));
+        assert_eq!(position.saved_by_waiting().value, dec!(0));
+    }
+
+    #[test]
+    fn exempt_gain_is_capped_by_years_held() {
+        // 10 units held 4 full years and 10 units held 2 years: 3 years on average
+        let lots = [
+            ("2021-03-01T10:00:00Z".parse().unwrap(), 10),
+            ("2023-05-10T10:00:00Z".parse().unwrap(), 10),
+        ];
+        let today = "2025-06-01T00:00:00Z".parse().unwrap();
+        let rub = |value| Money::from_value(value, Currency::RUB);
+
+        let capped = ldv_exempt_gain(rub(dec!(10_000_000)), lots, today, &FxRates::default());
+        let within = ldv_exempt_gain(rub(dec!(1_000_000)), lots, today, &FxRates::default());
+        let loss = ldv_exempt_gain(rub(dec!(-1_000)), lots, today, &FxRates::default());
+
+        assert_eq!(capped.value, dec!(9_000_000));
+        assert_eq!(within.value, dec!(1_000_000));
+        assert_eq!(loss.value, dec!(-1_000));
+    }
+
+    #[test]
+    fn gain_above_cap_is_taxed_progressively() {
+        let position = position(dec!(1_500_100));
+
+        // 10 units held 4 years gain 15 000 000, 12 000 000 of it is exempt
+        assert_eq!(position.exempt_gain().value, dec!(12_000_000));
+        // 3 000 000 above the cap, 10 × 1 499 900 and 5 × 1 499 800 not eligible yet
+        assert_eq!(position.taxable_gain().value, dec!(25_498_000));
+        // 2 400 000 × 13% + 23 098 000 × 15%
+        assert_eq!(position.tax_today().value, dec!(3_776_700));
+    }
+
+    #[test]
+    fn nothing_is_exempt_without_rate_of_currency() {
+        let usd = paid(
+            trade("1", OperationType::Buy, "2021-04-01T10:00:00Z", 5, "-50"),
+            dec!(-50),
+            "usd",
+        );
+        let position = LdvPosition::new(
+            "Apple".to_string(),
+            "AAPL".to_string(),
+            "BBG000B9XRY4".to_string(),
+            dec!(5),
+            Money::from_value(dec!(20), Currency::USD),
+            &TaxLots::new(&[usd]),
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        );
+        let mut fx = FxRates::default();
+        fx.insert(Currency::USD, dec!(80));
+
+        assert_eq!(position.exempt_gain().value, dec!(0));
+        assert_eq!(position.clone().with_fx(fx).exempt_gain().value, dec!(50));
+    }
+}
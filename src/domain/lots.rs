@@ -0,0 +1,365 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use iso_currency::Currency;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::{to_datetime_utc, to_money};
+
+use super::journal::OperationKind;
+use super::money::{Money, MoneyBag};
+use super::operations::unique_operations;
+use super::period::Period;
+
+/// Units bought in one purchase and not sold yet
+#[derive(Clone, Serialize)]
+pub struct TaxLot {
+    pub acquired: DateTime<Utc>,
+    pub quantity: i64,
+    /// Cost of a unit, the purchase payment divided by the quantity bought
+    pub price: Money,
+    /// Broker fee of the purchase per unit
+    #[serde(skip)]
+    pub fee: Money,
+}
+
+/// Units sold in one operation, matched with the lots they were bought in
+#[derive(Clone, Serialize)]
+pub struct Sale {
+    pub datetime: DateTime<Utc>,
+    pub quantity: i64,
+    /// Units without a purchase to match, e.g. bought before the operations known
+    /// or in another currency than the sale
+    pub unmatched: i64,
+    /// Payment received for the matched units
+    pub proceeds: Money,
+    /// Purchase cost of the matched units
+    pub cost: Money,
+    /// Broker fees of the sale and of the purchases of the matched units
+    #[serde(skip)]
+    pub fees: Money,
+    /// Parts of the lots the matched units were taken from
+    #[serde(skip)]
+    pub lots: Vec<TaxLot>,
+}
+
+/// Purchases and sales of an instrument matched first in, first out,
+/// the way the Russian tax code requires
+#[derive(Clone, Default)]
+pub struct TaxLots {
+    /// Lots still held, oldest first
+    pub open: Vec<TaxLot>,
+    /// Sales, oldest first
+    pub sales: Vec<Sale>,
+}
+
+impl TaxLot {
+    #[must_use]
+    pub fn cost(&self) -> Money {
+        self.price * Decimal::from(self.quantity)
+    }
+}
+
+impl Sale {
+    /// Realized profit (or loss if negative) of the matched units. Only units matched with
+    /// lots in the currency of the sale have a cost, so both are in the same currency.
+    #[must_use]
+    pub fn realized(&self) -> Money {
+        self.proceeds
+            .checked_sub(self.cost)
+            .unwrap_or_else(|_| Money::zero(self.proceeds.currency))
+    }
+}
+
+impl TaxLots {
+    /// Replays buys and sells among `operations`, each operation once, in the order they were made.
+    /// Broker fees are attributed to the trades they were charged for, other operations are ignored.
+    #[must_use]
+    pub fn new(operations: &[Operation]) -> Self {
+        let mut fees: HashMap<&str, MoneyBag> = HashMap::new();
+        for op in unique_operations(operations)
+            .filter(|op| op.operation_type() == OperationType::BrokerFee)
+            .filter(|op| !op.parent_operation_id.is_empty())
+        {
+            if let Some(fee) = to_money(op.payment.as_ref()) {
+                *fees.entry(op.parent_operation_id.as_str()).or_default() += fee * dec!(-1);
+            }
+        }
+        let trades = unique_operations(operations)
+            .filter_map(|op| {
+                let fee = fees.get(op.id.as_str()).cloned().unwrap_or_default();
+                Trade::new(op, &fee)
+            })
+            .sorted_by_key(|trade| trade.datetime);
+
+        let mut open = VecDeque::new();
+        let mut sales = Vec::new();
+        for trade in trades {
+            if trade.quantity == 0 {
+                continue;
+            }
+            let price = trade.payment.value.abs() / Decimal::from(trade.quantity);
+            if trade.kind == OperationKind::Buy {
+                open.push_back(TaxLot {
+                    acquired: trade.datetime,
+                    quantity: trade.quantity,
+                    price: Money::from_value(price, trade.payment.currency),
+                    fee: trade.fee / Decimal::from(trade.quantity),
+                });
+            } else {
+                sales.push(sell(&mut open, &trade, price));
+            }
+        }
+        Self {
+            open: open.into(),
+            sales,
+        }
+    }
+
+    /// Keeps sales made within `period` only. Open lots are left as they are.
+    #[must_use]
+    pub fn within(mut self, period: Period) -> Self {
+        self.sales.retain(|sale| period.contains(sale.datetime));
+        self
+    }
+
+    /// Realized profit of all sales, in every currency the instrument was traded in
+    #[must_use]
+    pub fn realized(&self) -> MoneyBag {
+        self.sales.iter().map(Sale::realized).collect()
+    }
+
+    /// Cost of the lots still held
+    #[must_use]
+    pub fn open_cost(&self) -> MoneyBag {
+        self.open.iter().map(TaxLot::cost).collect()
+    }
+
+    #[must_use]
+    pub fn open_quantity(&self) -> i64 {
+        self.open.iter().map(|lot| lot.quantity).sum()
+    }
+}
+
+/// Lots of several accounts together. Sales stay matched with purchases of their own account.
+impl FromIterator<TaxLots> for TaxLots {
+    fn from_iter<I: IntoIterator<Item = TaxLots>>(iter: I) -> Self {
+        let (open, sales): (Vec<_>, Vec<_>) = iter.into_iter().map(|l| (l.open, l.sales)).unzip();
+        Self {
+            open: open
+                .into_iter()
+                .flatten()
+                .sorted_by_key(|lot| lot.acquired)
+                .collect(),
+            sales: sales
+                .into_iter()
+                .flatten()
+                .sorted_by_key(|sale| sale.datetime)
+                .collect(),
+        }
+    }
+}
+
+/// Takes units of `trade` from the oldest lots
+fn sell(open: &mut VecDeque<TaxLot>, trade: &Trade, price: Decimal) -> Sale {
+    let currency = trade.payment.currency;
+    let mut left = trade.quantity;
+    let mut matched = 0;
+    let mut cost = Money::zero(currency);
+    let mut fees = trade.fee;
+    let mut lots = Vec::new();
+    while left > 0 {
+        let Some(lot) = open.front_mut() else {
+            break;
+        };
+        let taken = left.min(lot.quantity);
+        // Units of lots bought in another currency are sold but cannot be matched
+        if let Ok(sum) = cost.checked_add(lot.price * Decimal::from(taken)) {
+            cost = sum;
+            fees = fees
+                .checked_add(lot.fee * Decimal::from(taken))
+                .unwrap_or(fees);
+            matched += taken;
+            lots.push(TaxLot {
+                quantity: taken,
+                ..lot.clone()
+            });
+        }
+        lot.quantity -= taken;
+        left -= taken;
+        if lot.quantity == 0 {
+            open.pop_front();
+        }
+    }
+    Sale {
+        datetime: trade.datetime,
+        quantity: trade.quantity,
+        unmatched: trade.quantity - matched,
+        proceeds: Money::from_value(price * Decimal::from(matched), currency),
+        cost,
+        fees,
+        lots,
+    }
+}
+
+/// Executed part of a buy or sell operation
+struct Trade {
+    datetime: DateTime<Utc>,
+    kind: OperationKind,
+    quantity: i64,
+    payment: Money,
+    /// Broker fee charged for the trade, in its currency
+    fee: Money,
+}
+
+impl Trade {
+    /// Trade of a buy or sell `op` which broker fees are `fees`
+    fn new(op: &Operation, fees: &MoneyBag) -> Option<Self> {
+        let kind = OperationKind::of(op.operation_type());
+        if kind != OperationKind::Buy && kind != OperationKind::Sell {
+            return None;
+        }
+        let currency =
+            Currency::from_code(&op.currency.to_ascii_uppercase()).unwrap_or(Currency::RUB);
+        Some(Self {
+            datetime: to_datetime_utc(op.date.as_ref()),
+            kind,
+            quantity: op.quantity - op.quantity_rest,
+            payment: to_money(op.payment.as_ref()).unwrap_or_else(|| Money::zero(currency)),
+            fee: fees.get(currency),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use tinkoff_invest_api::tcs::OperationType;
+
+    use super::*;
+    use crate::domain::test_support::{paid, trade};
+
+    #[test]
+    fn sales_take_oldest_lots_first() {
+        let operations = [
+            trade("3", OperationType::Sell, "2025-03-01T10:00:00Z", 15, "4500"),
+            trade("1", OperationType::Buy, "2025-01-10T10:00:00Z", 10, "-2500"),
+            trade("2", OperationType::Buy, "2025-02-10T10:00:00Z", 10, "-2700"),
+            trade(
+                "4",
+                OperationType::BrokerFee,
+                "2025-03-01T10:00:00Z",
+                0,
+                "-4.5",
+            ),
+        ];
+
+        let lots = TaxLots::new(&operations);
+
+        assert_eq!(lots.sales.len(), 1);
+        let sale = &lots.sales[0];
+        assert_eq!(sale.cost.value, dec!(3850));
+        assert_eq!(sale.proceeds.value, dec!(4500));
+        assert_eq!(sale.realized().value, dec!(650));
+        assert_eq!(lots.open.len(), 1);
+        assert_eq!(lots.open[0].quantity, 5);
+        assert_eq!(lots.open[0].price.value, dec!(270));
+        assert_eq!(
+            lots.open[0].acquired,
+            "2025-02-10T10:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(lots.open_cost().get(Currency::RUB).value, dec!(1350));
+    }
+
+    #[test]
+    fn units_without_purchase_are_unmatched() {
+        let operations = [
+            trade("1", OperationType::Buy, "2025-01-10T10:00:00Z", 2, "-200"),
+            trade("2", OperationType::Sell, "2025-02-10T10:00:00Z", 4, "480"),
+        ];
+
+        let lots = TaxLots::new(&operations);
+
+        let sale = &lots.sales[0];
+        assert_eq!(sale.unmatched, 2);
+        assert_eq!(sale.proceeds.value, dec!(240));
+        assert_eq!(sale.realized().value, dec!(40));
+        assert!(lots.open.is_empty());
+        assert_eq!(lots.open_quantity(), 0);
+    }
+
+    #[test]
+    fn units_of_lots_in_another_currency_are_unmatched() {
+        let operations = [
+            paid(
+                trade("1", OperationType::Buy, "2025-01-10T10:00:00Z", 5, "-50"),
+                dec!(-50),
+                "usd",
+            ),
+            trade("2", OperationType::Buy, "2025-01-20T10:00:00Z", 5, "-500"),
+            trade("3", OperationType::Sell, "2025-02-10T10:00:00Z", 8, "960"),
+        ];
+
+        let lots = TaxLots::new(&operations);
+
+        let sale = &lots.sales[0];
+        assert_eq!(sale.unmatched, 5);
+        assert_eq!(sale.proceeds.value, dec!(360));
+        assert_eq!(sale.realized().value, dec!(60));
+        assert_eq!(lots.open_quantity(), 2);
+    }
+
+    #[test]
+    fn operations_without_id_are_not_merged() {
+        let operations = [
+            trade("", OperationType::Buy, "2025-01-10T10:00:00Z", 5, "-500"),
+            trade("", OperationType::Buy, "2025-01-20T10:00:00Z", 5, "-600"),
+        ];
+
+        let lots = TaxLots::new(&operations);
+
+        assert_eq!(lots.open_quantity(), 10);
+    }
+
+    #[test]
+    fn lots_of_accounts_are_matched_separately() {
+        let first = [
+            trade("1", OperationType::Buy, "2025-01-10T10:00:00Z", 5, "-500"),
+            trade("2", OperationType::Sell, "2025-03-10T10:00:00Z", 5, "700"),
+        ];
+        let second = [
+            trade("3", OperationType::Buy, "2025-02-10T10:00:00Z", 5, "-600"),
+            trade("4", OperationType::Buy, "2024-12-10T10:00:00Z", 5, "-400"),
+        ];
+
+        let lots: TaxLots = [TaxLots::new(&first), TaxLots::new(&second)]
+            .into_iter()
+            .collect();
+
+        // The sale takes the lot of its own account, not the older one of the other account
+        assert_eq!(lots.realized().get(Currency::RUB).value, dec!(200));
+        assert_eq!(lots.open_quantity(), 10);
+        assert_eq!(
+            lots.open[0].acquired,
+            "2024-12-10T10:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn sales_outside_period_are_left_out() {
+        let operations = [
+            trade("1", OperationType::Buy, "2024-06-10T10:00:00Z", 10, "-1000"),
+            trade("2", OperationType::Sell, "2024-12-10T10:00:00Z", 5, "600"),
+            trade("3", OperationType::Sell, "2025-02-10T10:00:00Z", 5, "400"),
+        ];
+
+        let lots = TaxLots::new(&operations).within(Period::year(2025).unwrap());
+
+        assert_eq!(lots.sales.len(), 1);
+        assert_eq!(lots.realized().get(Currency::RUB).value, dec!(-100));
+    }
+}
@@ -1,21 +1,53 @@
+pub mod accounts;
 pub mod calendar;
+pub mod cashflow;
 pub mod display;
+mod export;
+pub mod fx;
 pub mod history;
+pub mod iis;
+pub mod journal;
+pub mod ldv;
+pub mod lots;
 pub mod money;
+pub mod operations;
 pub mod paper;
+pub mod period;
 pub mod portfolio;
+pub mod returns;
 pub mod risk;
+mod serialize;
+pub mod simulate;
+pub mod skipped;
+pub mod tax;
+#[cfg(test)]
+mod test_support;
 
+pub use accounts::{
+    AccountInfo, AccountList, AccountSection, AccountsView, Consolidated, account_opened,
+};
 pub use calendar::{
     CalendarPayment, CouponCalendar, CouponPayment, DividendCalendar, DividendPayment,
 };
+pub use cashflow::{CashFlow, CashFlowRow, Interval};
+pub use fx::{FxRate, FxRates};
 pub use history::{History, HistoryItem};
-pub use money::{Income, Money};
+pub use iis::{IisKind, IisReport, IisWithdrawal, IisYear};
+pub use journal::{Journal, JournalItem, OperationKind, Subtotal};
+pub use ldv::{LdvLot, LdvPosition, LdvReport};
+pub use lots::{Sale, TaxLot, TaxLots};
+pub use money::{CurrencyMismatch, Income, Money, MoneyBag};
+pub use operations::AccountOperations;
 pub use paper::{
     CouponProfit, DividendProfit, Figi, Instrument, NoneProfit, Paper, Position, Profit, Ticker,
     Totals,
 };
+pub use period::{Lookback, Period};
 pub use portfolio::{Asset, LoadedPaper, Portfolio};
+pub use returns::{DatedFlow, Returns, Valuation};
+pub use simulate::{Allocation, SellSimulation, SoldLot};
+pub use skipped::SkippedPosition;
+pub use tax::{InstrumentTax, TaxReport};
 
 /// Numeric value that can be classified as negative, zero, or positive (for table coloring).
 ///
@@ -1,11 +1,14 @@
 use std::{
+    collections::BTreeMap,
+    error::Error,
     fmt::Display,
-    ops::{self, AddAssign, DivAssign, MulAssign, SubAssign},
+    ops::{self, AddAssign, DivAssign, MulAssign},
 };
 
 use iso_currency::Currency;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::{Serialize, Serializer};
 
 use crate::ux::format_decimal;
 
@@ -13,15 +16,31 @@ use super::NumberRange;
 
 const HUNDRED: Decimal = dec!(100);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
 pub struct Money {
     pub value: Decimal,
     pub currency: Currency,
 }
 
+/// Error of an operation that combines money in different currencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrencyMismatch {
+    pub left: Currency,
+    pub right: Currency,
+}
+
+/// Sum of money that may span several currencies, kept per currency.
+///
+/// Use it where amounts legitimately come in different currencies
+/// (e.g. calendar or history totals) instead of adding [`Money`] directly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoneyBag {
+    amounts: BTreeMap<Currency, Decimal>,
+}
+
 #[derive(Clone, Copy)]
 pub struct Income {
-    currency: Currency,
+    pub(crate) currency: Currency,
     pub(crate) current: Decimal,
     pub(crate) balance: Decimal,
 }
@@ -44,6 +63,82 @@ impl Money {
             currency,
         }
     }
+
+    /// Adds money of the same currency.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CurrencyMismatch`] if `rhs` is in another currency.
+    pub fn checked_add(self, rhs: Money) -> Result<Money, CurrencyMismatch> {
+        self.ensure_same_currency(rhs.currency)?;
+        Ok(Self::from_value(self.value + rhs.value, self.currency))
+    }
+
+    /// Subtracts money of the same currency.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CurrencyMismatch`] if `rhs` is in another currency.
+    pub fn checked_sub(self, rhs: Money) -> Result<Money, CurrencyMismatch> {
+        self.ensure_same_currency(rhs.currency)?;
+        Ok(Self::from_value(self.value - rhs.value, self.currency))
+    }
+
+    fn ensure_same_currency(self, other: Currency) -> Result<(), CurrencyMismatch> {
+        if self.currency == other {
+            Ok(())
+        } else {
+            Err(CurrencyMismatch {
+                left: self.currency,
+                right: other,
+            })
+        }
+    }
+}
+
+impl MoneyBag {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds money to the amount kept for its currency
+    pub fn add(&mut self, money: Money) {
+        *self.amounts.entry(money.currency).or_default() += money.value;
+    }
+
+    /// Amount kept for `currency` (zero if there is none)
+    #[must_use]
+    pub fn get(&self, currency: Currency) -> Money {
+        let value = self.amounts.get(&currency).copied().unwrap_or_default();
+        Money::from_value(value, currency)
+    }
+
+    /// Amounts ordered by currency
+    pub fn iter(&self) -> impl Iterator<Item = Money> + '_ {
+        self.amounts
+            .iter()
+            .map(|(currency, value)| Money::from_value(*value, *currency))
+    }
+
+    pub fn currencies(&self) -> impl Iterator<Item = Currency> + '_ {
+        self.amounts.keys().copied()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.amounts.is_empty()
+    }
+
+    /// The only amount if all money is in a single currency
+    #[must_use]
+    pub fn single(&self) -> Option<Money> {
+        if self.amounts.len() == 1 {
+            self.iter().next()
+        } else {
+            None
+        }
+    }
 }
 
 impl Income {
@@ -75,87 +170,37 @@ impl Income {
         }
     }
 
-    fn income(&self) -> Decimal {
+    pub(crate) fn income(&self) -> Decimal {
         self.current - self.balance
     }
-}
-
-impl ops::Add<Money> for Money {
-    type Output = Money;
 
-    fn add(self, rhs: Money) -> Money {
-        Money {
-            value: self.value + rhs.value,
-            currency: self.currency,
-        }
-    }
-}
-
-impl ops::Add<Decimal> for Money {
-    type Output = Money;
-
-    fn add(self, rhs: Decimal) -> Money {
-        Money {
-            value: self.value + rhs,
-            currency: self.currency,
-        }
-    }
-}
-
-impl AddAssign for Money {
-    fn add_assign(&mut self, other: Self) {
-        self.value += other.value;
-    }
-}
-
-impl AddAssign<Decimal> for Money {
-    fn add_assign(&mut self, other: Decimal) {
-        self.value += other;
-    }
-}
-
-impl ops::Sub<Money> for Money {
-    type Output = Money;
-
-    fn sub(self, rhs: Money) -> Money {
-        Money {
-            value: self.value - rhs.value,
-            currency: self.currency,
-        }
+    #[must_use]
+    pub fn current(&self) -> Money {
+        Money::from_value(self.current, self.currency)
     }
-}
-
-impl ops::Sub<Decimal> for Money {
-    type Output = Money;
 
-    fn sub(self, rhs: Decimal) -> Money {
-        Money {
-            value: self.value - rhs,
-            currency: self.currency,
+    #[must_use]
+    pub fn balance(&self) -> Money {
+        Money::from_value(self.balance, self.currency)
+    }
+
+    /// Adds income of the same currency.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CurrencyMismatch`] if `rhs` is in another currency.
+    pub fn checked_add(self, rhs: Income) -> Result<Income, CurrencyMismatch> {
+        if self.currency != rhs.currency {
+            return Err(CurrencyMismatch {
+                left: self.currency,
+                right: rhs.currency,
+            });
         }
-    }
-}
-
-impl SubAssign for Money {
-    fn sub_assign(&mut self, other: Self) {
-        self.value -= other.value;
-    }
-}
-
-impl SubAssign<Decimal> for Money {
-    fn sub_assign(&mut self, other: Decimal) {
-        self.value -= other;
-    }
-}
-
-impl ops::Mul<Money> for Money {
-    type Output = Money;
-
-    fn mul(self, rhs: Money) -> Money {
-        Money {
-            value: self.value * rhs.value,
+        Ok(Income {
             currency: self.currency,
-        }
+            current: self.current + rhs.current,
+            balance: self.balance + rhs.balance,
+        })
     }
 }
 
@@ -170,29 +215,12 @@ impl ops::Mul<Decimal> for Money {
     }
 }
 
-impl MulAssign for Money {
-    fn mul_assign(&mut self, other: Self) {
-        self.value *= other.value;
-    }
-}
-
 impl MulAssign<Decimal> for Money {
     fn mul_assign(&mut self, other: Decimal) {
         self.value *= other;
     }
 }
 
-impl ops::Div<Money> for Money {
-    type Output = Money;
-
-    fn div(self, rhs: Money) -> Money {
-        Money {
-            value: self.value / rhs.value,
-            currency: self.currency,
-        }
-    }
-}
-
 impl ops::Div<Decimal> for Money {
     type Output = Money;
 
@@ -204,34 +232,44 @@ impl ops::Div<Decimal> for Money {
     }
 }
 
-impl DivAssign for Money {
-    fn div_assign(&mut self, other: Self) {
-        self.value /= other.value;
-    }
-}
-
 impl DivAssign<Decimal> for Money {
     fn div_assign(&mut self, other: Decimal) {
         self.value /= other;
     }
 }
 
-impl ops::Add<Income> for Income {
-    type Output = Income;
+impl AddAssign<Money> for MoneyBag {
+    fn add_assign(&mut self, other: Money) {
+        self.add(other);
+    }
+}
 
-    fn add(self, rhs: Income) -> Income {
-        Income {
-            current: self.current + rhs.current,
-            balance: self.balance + rhs.balance,
-            currency: self.currency,
+impl FromIterator<Money> for MoneyBag {
+    fn from_iter<I: IntoIterator<Item = Money>>(iter: I) -> Self {
+        let mut bag = Self::new();
+        bag.extend(iter);
+        bag
+    }
+}
+
+impl Extend<Money> for MoneyBag {
+    fn extend<I: IntoIterator<Item = Money>>(&mut self, iter: I) {
+        for money in iter {
+            self.add(money);
         }
     }
 }
 
-impl AddAssign for Income {
-    fn add_assign(&mut self, other: Self) {
-        self.current += other.current;
-        self.balance += other.balance;
+impl From<Money> for MoneyBag {
+    fn from(money: Money) -> Self {
+        Self::from_iter([money])
+    }
+}
+
+impl Serialize for MoneyBag {
+    /// Serialized as a list of [`Money`], one per currency
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
     }
 }
 
@@ -246,6 +284,49 @@ impl Display for Money {
     }
 }
 
+impl Display for CurrencyMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot combine {} with {}",
+            self.left.code(),
+            self.right.code()
+        )
+    }
+}
+
+impl Error for CurrencyMismatch {}
+
+impl Display for MoneyBag {
+    /// Formats amounts as `1 000 ₽ + 10 $`, empty bag as zero
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "{}", format_decimal(Decimal::ZERO)?);
+        }
+        for (i, money) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " + ")?;
+            }
+            write!(f, "{money}")?;
+        }
+        Ok(())
+    }
+}
+
+impl NumberRange for MoneyBag {
+    fn is_negative(&self) -> bool {
+        !self.is_zero()
+            && self
+                .amounts
+                .values()
+                .all(|v| v.is_sign_negative() || v.is_zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.amounts.values().all(Decimal::is_zero)
+    }
+}
+
 impl NumberRange for Money {
     fn is_negative(&self) -> bool {
         self.value.is_sign_negative()
@@ -289,11 +370,68 @@ mod tests {
     fn money_add_same_currency_ok() {
         let m1 = Money::from_value(dec!(100), Currency::RUB);
         let m2 = Money::from_value(dec!(50), Currency::RUB);
-        let result = m1 + m2;
+        let result = m1.checked_add(m2).unwrap();
         assert_eq!(result.value, dec!(150));
         assert_eq!(result.currency, Currency::RUB);
     }
 
+    #[test]
+    fn money_checked_add_other_currency_fails() {
+        let rub = Money::from_value(dec!(100), Currency::RUB);
+        let usd = Money::from_value(dec!(5), Currency::USD);
+
+        let result = rub.checked_add(usd);
+
+        assert_eq!(
+            result,
+            Err(CurrencyMismatch {
+                left: Currency::RUB,
+                right: Currency::USD
+            })
+        );
+    }
+
+    #[test]
+    fn money_checked_sub_same_currency_ok() {
+        let m1 = Money::from_value(dec!(100), Currency::USD);
+        let m2 = Money::from_value(dec!(30), Currency::USD);
+
+        assert_eq!(
+            m1.checked_sub(m2),
+            Ok(Money::from_value(dec!(70), Currency::USD))
+        );
+    }
+
+    #[test]
+    fn money_bag_keeps_currencies_apart() {
+        let bag: MoneyBag = [
+            Money::from_value(dec!(100), Currency::RUB),
+            Money::from_value(dec!(5), Currency::USD),
+            Money::from_value(dec!(50), Currency::RUB),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(bag.get(Currency::RUB).value, dec!(150));
+        assert_eq!(bag.get(Currency::USD).value, dec!(5));
+        assert!(bag.get(Currency::EUR).value.is_zero());
+        assert!(bag.single().is_none());
+    }
+
+    #[test]
+    fn money_bag_sign() {
+        let bag: MoneyBag = [
+            Money::from_value(dec!(-100), Currency::RUB),
+            Money::from_value(dec!(0), Currency::USD),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(bag.is_negative());
+        assert!(!MoneyBag::new().is_negative());
+        assert!(MoneyBag::new().is_zero());
+    }
+
     #[test]
     fn income_percent_zero_balance() {
         let income = Income::new(
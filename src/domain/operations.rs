@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+
+use tinkoff_invest_api::tcs::Operation;
+
+/// Operations of an account grouped by FIGI.
+///
+/// Loaded once per account, it serves every position instead of a request per instrument.
+#[derive(Debug, Default, Clone)]
+pub struct AccountOperations {
+    by_figi: HashMap<String, Vec<Operation>>,
+    ids: HashSet<String>,
+}
+
+impl AccountOperations {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `op` to the operations of its instrument. Operations seen before are ignored.
+    pub fn add(&mut self, op: Operation) {
+        if !op.id.is_empty() && !self.ids.insert(op.id.clone()) {
+            return;
+        }
+        self.by_figi.entry(op.figi.clone()).or_default().push(op);
+    }
+
+    /// Operations with the instrument, empty if there are none
+    #[must_use]
+    pub fn get(&self, figi: &str) -> &[Operation] {
+        self.by_figi.get(figi).map_or(&[], Vec::as_slice)
+    }
+
+    /// Operations grouped by FIGI, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[Operation])> {
+        self.by_figi
+            .iter()
+            .map(|(figi, ops)| (figi.as_str(), ops.as_slice()))
+    }
+
+    /// Number of operations
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.by_figi.values().map(Vec::len).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.by_figi.is_empty()
+    }
+}
+
+/// Operations each once. Operations without an id cannot be told apart and are all kept,
+/// as [`AccountOperations::add`] does.
+pub(crate) fn unique_operations<'a>(
+    operations: impl IntoIterator<Item = &'a Operation>,
+) -> impl Iterator<Item = &'a Operation> {
+    let mut ids = HashSet::new();
+    operations
+        .into_iter()
+        .filter(move |op| op.id.is_empty() || ids.insert(op.id.as_str()))
+}
+
+impl FromIterator<Operation> for AccountOperations {
+    fn from_iter<I: IntoIterator<Item = Operation>>(iter: I) -> Self {
+        let mut operations = Self::new();
+        for op in iter {
+            operations.add(op);
+        }
+        operations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(id: &str, figi: &str) -> Operation {
+        Operation {
+            id: id.to_string(),
+            figi: figi.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn operations_are_grouped_by_figi() {
+        let operations: AccountOperations = [op("1", "A"), op("2", "B"), op("3", "A")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations.get("A").len(), 2);
+        assert_eq!(operations.get("B")[0].id, "2");
+        assert!(operations.get("C").is_empty());
+    }
+
+    #[test]
+    fn repeated_operations_are_ignored() {
+        let operations: AccountOperations = [op("1", "A"), op("1", "A"), op("", "A"), op("", "A")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(operations.get("A").len(), 3);
+    }
+
+    #[test]
+    fn unique_operations_keep_those_without_id() {
+        let operations = [op("1", "A"), op("1", "A"), op("", "A"), op("", "B")];
+
+        assert_eq!(unique_operations(&operations).count(), 3);
+    }
+}
@@ -1,12 +1,18 @@
+use chrono::{DateTime, Utc};
 use iso_currency::Currency;
 use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use super::money::{Income, Money};
+use super::returns::Returns;
 
 /// Newtype for FIGI (Financial Instrument Global Identifier)
 /// Provides type safety and prevents mixing up with other string identifiers
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Figi(pub String);
 
 impl Figi {
@@ -47,7 +53,8 @@ impl AsRef<str> for Figi {
 
 /// Newtype for ticker symbol
 /// Provides type safety and prevents mixing up with other string identifiers
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Ticker(pub String);
 
 impl Ticker {
@@ -86,13 +93,16 @@ impl AsRef<str> for Ticker {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Instrument {
     pub name: String,
     pub ticker: Ticker,
+    /// Country of risk of the issuer as an ISO code like `RU`, empty if unknown
+    #[serde(default)]
+    pub country: String,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize)]
 pub struct Position {
     pub currency: Currency,
     pub average_buy_price: Money,
@@ -100,13 +110,43 @@ pub struct Position {
     pub quantity: Decimal,
 }
 
-#[derive(Clone)]
+const DAYS_IN_YEAR: f64 = 365.0;
+
+/// Operation totals of a paper, kept in the paper's position currency
+#[derive(Clone, Serialize)]
 pub struct Totals {
     /// Dividends, coupons etc. i.e. some extra value
     /// an asset may earn
     pub additional_profit: Money,
     /// Taxes and fees
     pub fees: Money,
+    /// Profit of units sold, matched with purchases first in, first out
+    pub realized: Money,
+    /// Money-weighted and time-weighted returns of the position
+    pub returns: Returns,
+    /// Purchase date of the oldest units still held, `None` if no such purchase is known
+    pub first_bought: Option<DateTime<Utc>>,
+    /// Dividends or coupons less their taxes received over the last 12 months
+    pub trailing_income: Money,
+    /// Moment the totals are computed at, holding period and CAGR are measured up to it
+    #[serde(skip)]
+    pub today: DateTime<Utc>,
+}
+
+impl Totals {
+    /// Totals of a paper without operations, computed now
+    #[must_use]
+    pub fn zero(currency: Currency) -> Self {
+        Self {
+            additional_profit: Money::zero(currency),
+            fees: Money::zero(currency),
+            realized: Money::zero(currency),
+            returns: Returns::default(),
+            first_bought: None,
+            trailing_income: Money::zero(currency),
+            today: Utc::now(),
+        }
+    }
 }
 
 /// Represents additional asset profit
@@ -179,7 +219,12 @@ impl<P: Profit> Paper<P> {
     #[must_use]
     pub fn total_income(&self) -> Income {
         let div = self.dividends();
-        Income::new(self.current() + (div.current - div.balance), self.balance())
+        let current = self.current();
+        let current = current
+            .checked_add(div.current())
+            .and_then(|sum| sum.checked_sub(div.balance()))
+            .unwrap_or(current);
+        Income::new(current, self.balance())
     }
 
     /// Expences (the amount of money thea really spent), i.e. average position price multiplied to quantity
@@ -194,20 +239,73 @@ impl<P: Profit> Paper<P> {
         self.position.current_instrument_price * self.position.quantity
     }
 
-    /// Dividends and coupons
+    /// Dividends and coupons (not counted if totals are in another currency than the position)
     #[must_use]
     pub fn dividends(&self) -> Income {
-        Income::new(
-            self.totals.additional_profit + self.balance(),
-            self.balance(),
-        )
+        let balance = self.balance();
+        let current = balance
+            .checked_add(self.totals.additional_profit)
+            .unwrap_or(balance);
+        Income::new(current, balance)
     }
 
-    /// Taxes and fees
+    /// Taxes and fees (not counted if totals are in another currency than the position)
     #[must_use]
     pub fn fees(&self) -> Income {
+        let balance = self.balance();
         // IMPORTANT: we must add self.totals.fees because their value is negative
-        Income::new(self.balance() + self.totals.fees, self.balance())
+        let current = balance.checked_add(self.totals.fees).unwrap_or(balance);
+        Income::new(current, balance)
+    }
+
+    /// Realized profit of sold units (not counted if totals are in another currency than the position)
+    #[must_use]
+    pub fn realized(&self) -> Money {
+        if self.totals.realized.currency == self.currency() {
+            self.totals.realized
+        } else {
+            Money::zero(self.currency())
+        }
+    }
+
+    /// XIRR and time-weighted return of the position's operations and current value
+    #[must_use]
+    pub fn returns(&self) -> Returns {
+        self.totals.returns
+    }
+
+    /// Days from the purchase of the oldest units held to `now`, `None` if no purchase is known
+    #[must_use]
+    pub fn holding_days(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.totals
+            .first_bought
+            .map(|bought| (now - bought).num_days().max(0))
+    }
+
+    /// Compound annual growth rate of total income over the holding period, percent.
+    /// `None` if the position is held for less than a day or its balance is unknown.
+    #[must_use]
+    pub fn cagr(&self, now: DateTime<Utc>) -> Option<Decimal> {
+        let days = self.holding_days(now).filter(|days| *days > 0)?;
+        let income = self.total_income();
+        if income.balance <= Decimal::ZERO || income.current < Decimal::ZERO {
+            return None;
+        }
+        let growth = (income.current / income.balance).to_f64()?;
+        let years = days.to_f64()? / DAYS_IN_YEAR;
+        let rate = (growth.powf(1.0 / years) - 1.0) * 100.0;
+        Decimal::try_from(rate).ok().map(|r| r.round_dp(2))
+    }
+
+    /// Dividends or coupons received over the last 12 months relative to the balance, percent.
+    /// `None` if the balance is zero or the income is in another currency than the position.
+    #[must_use]
+    pub fn yield_on_cost(&self) -> Option<Decimal> {
+        let balance = self.balance();
+        if balance.value.is_zero() || self.totals.trailing_income.currency != balance.currency {
+            return None;
+        }
+        Some((self.totals.trailing_income.value / balance.value * dec!(100)).round_dp(2))
     }
 
     #[must_use]
@@ -230,3 +328,50 @@ impl<P: Profit> Paper<P> {
         self.position.average_buy_price
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paper(first_bought: &str, trailing_income: Decimal) -> Paper<DividendProfit> {
+        let rub = |value| Money::from_value(value, Currency::RUB);
+        Paper {
+            name: "Sberbank".to_string(),
+            ticker: Ticker::new("SBER"),
+            figi: Figi::new("BBG004730N88"),
+            position: Position {
+                currency: Currency::RUB,
+                average_buy_price: rub(dec!(100)),
+                current_instrument_price: rub(dec!(110)),
+                quantity: dec!(10),
+            },
+            totals: Totals {
+                additional_profit: rub(dec!(100)),
+                first_bought: Some(first_bought.parse().unwrap()),
+                trailing_income: rub(trailing_income),
+                ..Totals::zero(Currency::RUB)
+            },
+            profit: DividendProfit,
+        }
+    }
+
+    #[test]
+    fn cagr_annualizes_total_income_over_holding_period() {
+        let paper = paper("2022-01-01T00:00:00Z", dec!(50));
+        let now = "2024-01-01T00:00:00Z".parse().unwrap();
+
+        // 1000 grew to 1200 with dividends in two years
+        assert_eq!(paper.holding_days(now), Some(730));
+        assert_eq!(paper.cagr(now), Some(dec!(9.54)));
+        assert_eq!(paper.yield_on_cost(), Some(dec!(5)));
+    }
+
+    #[test]
+    fn cagr_is_unknown_for_position_bought_today() {
+        let paper = paper("2024-01-01T10:00:00Z", dec!(0));
+        let now = "2024-01-01T12:00:00Z".parse().unwrap();
+
+        assert_eq!(paper.holding_days(now), Some(0));
+        assert_eq!(paper.cagr(now), None);
+    }
+}
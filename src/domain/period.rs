@@ -0,0 +1,156 @@
+use std::fmt::{self, Display};
+
+use chrono::{DateTime, Days, FixedOffset, Months, NaiveDate, NaiveTime, TimeDelta, Utc};
+
+/// Offset of Moscow time (UTC+3) the broker counts days and years in
+const MOSCOW_OFFSET_SECONDS: i32 = 3 * 3600;
+
+/// Time span counted back from now, like `30d` or `1y`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookback {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Lookback {
+    /// Moment the span starts at when it ends at `now`
+    #[must_use]
+    pub fn since(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let start = match self {
+            Self::Days(n) => now.checked_sub_days(Days::new(n.into())),
+            Self::Weeks(n) => now.checked_sub_days(Days::new(u64::from(n) * 7)),
+            Self::Months(n) => now.checked_sub_months(Months::new(n)),
+            Self::Years(n) => n
+                .checked_mul(12)
+                .and_then(|months| now.checked_sub_months(Months::new(months))),
+        };
+        start.unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+/// Half-open time interval `[from, to)`, unbounded on a side that is `None`.
+///
+/// Dates are days in Moscow time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Period {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Period {
+    /// Unbounded period
+    #[must_use]
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// From the start of `from` until the end of `to`, both days included
+    #[must_use]
+    pub fn between(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        Self {
+            from: from.map(start_of_day),
+            to: to.and_then(|to| to.checked_add_days(Days::new(1)).map(start_of_day)),
+        }
+    }
+
+    /// Calendar year, `None` if it cannot be represented
+    #[must_use]
+    pub fn year(year: i32) -> Option<Self> {
+        let from = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let to = NaiveDate::from_ymd_opt(year.checked_add(1)?, 1, 1)?;
+        Some(Self {
+            from: Some(start_of_day(from)),
+            to: Some(start_of_day(to)),
+        })
+    }
+
+    /// The `lookback` span ending at `now`
+    #[must_use]
+    pub fn last(lookback: Lookback, now: DateTime<Utc>) -> Self {
+        Self {
+            from: Some(lookback.since(now)),
+            to: None,
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, datetime: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| datetime >= from) && self.to.is_none_or(|to| datetime < to)
+    }
+
+    #[must_use]
+    pub fn is_all(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+}
+
+fn moscow() -> FixedOffset {
+    FixedOffset::east_opt(MOSCOW_OFFSET_SECONDS).expect("Moscow offset is within a day")
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    (midnight - moscow()).and_utc()
+}
+
+fn day_of(datetime: DateTime<Utc>) -> NaiveDate {
+    datetime.with_timezone(&moscow()).date_naive()
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `to` is exclusive, the last day shown is the one before it
+        let last_day = |to: DateTime<Utc>| day_of(to - TimeDelta::nanoseconds(1));
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => write!(f, "{} – {}", day_of(from), last_day(to)),
+            (Some(from), None) => write!(f, "since {}", day_of(from)),
+            (None, Some(to)) => write!(f, "until {}", last_day(to)),
+            (None, None) => write!(f, "all time"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn year_covers_whole_days() {
+        let period = Period::year(2025).unwrap();
+
+        assert!(period.contains(utc("2024-12-31T21:00:00Z")));
+        assert!(period.contains(utc("2025-12-31T20:59:59Z")));
+        assert!(!period.contains(utc("2025-12-31T21:00:00Z")));
+        assert!(!period.contains(utc("2024-12-31T20:59:59Z")));
+        assert_eq!(period.to_string(), "2025-01-01 – 2025-12-31");
+    }
+
+    #[test]
+    fn between_includes_last_day() {
+        let period = Period::between(None, NaiveDate::from_ymd_opt(2025, 3, 31));
+
+        assert!(period.contains(utc("2025-03-31T20:59:59Z")));
+        assert!(!period.contains(utc("2025-03-31T21:00:00Z")));
+        assert_eq!(period.to_string(), "until 2025-03-31");
+        assert!(Period::all().contains(utc("1990-01-01T00:00:00Z")));
+    }
+
+    #[rstest]
+    #[case(Lookback::Days(30), "2025-05-16T12:00:00Z")]
+    #[case(Lookback::Weeks(2), "2025-06-01T12:00:00Z")]
+    #[case(Lookback::Months(6), "2024-12-15T12:00:00Z")]
+    #[case(Lookback::Years(1), "2024-06-15T12:00:00Z")]
+    fn lookback_counts_back_from_now(#[case] lookback: Lookback, #[case] expected: &str) {
+        let period = Period::last(lookback, utc("2025-06-15T12:00:00Z"));
+
+        assert_eq!(period.from, Some(utc(expected)));
+        assert_eq!(period.to, None);
+    }
+}
@@ -1,7 +1,15 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use chrono::Utc;
 use iso_currency::Currency;
+use tinkoff_invest_api::tcs::Operation;
 
-use super::money::{Income, Money};
+use super::fx::{FxRate, FxRates};
+use super::money::{Income, Money, MoneyBag};
 use super::paper::{CouponProfit, DividendProfit, NoneProfit, Paper, Profit};
+use super::returns::Returns;
+use super::skipped::{SkippedPosition, extend_unique};
 
 /// A position loaded from the API, tagged by instrument kind.
 pub enum LoadedPaper {
@@ -20,6 +28,13 @@ pub struct Portfolio {
     pub etfs: Asset<DividendProfit>,
     pub currencies: Asset<NoneProfit>,
     pub futures: Asset<NoneProfit>,
+    /// Rates used to convert totals into the reporting currency
+    pub(crate) fx: Arc<FxRates>,
+    /// Positions that failed to load and are left out of totals
+    pub(crate) skipped: Vec<SkippedPosition>,
+    /// Operations of the account the returns are computed from,
+    /// `None` unless the portfolio holds every position of the account
+    pub(crate) operations: Option<Vec<Operation>>,
 }
 
 /// Asset is a [`Paper`]'s container
@@ -31,22 +46,96 @@ pub struct Asset<P: Profit> {
     /// If true papers will be displyed
     /// If false they only accounted during calculations (balance, income etc,)
     pub(crate) output_papers: bool,
+    /// Rates used to convert papers' money into the reporting currency
+    pub(crate) fx: Arc<FxRates>,
 }
 
 /// Macro to generate Portfolio aggregation methods
 macro_rules! impl_portfolio_aggregator {
-    ($method:ident, $asset_method:ident, $return_type:ty, $zero:expr) => {
+    ($method:ident, Money) => {
+        #[must_use]
+        pub fn $method(&self) -> Money {
+            self.fx
+                .total(&self.assets().iter().map(|a| a.$method()).collect())
+        }
+    };
+    ($method:ident, Income) => {
         #[must_use]
-        pub fn $method(&self) -> $return_type {
-            self.assets()
-                .iter()
-                .map(|a| a.$asset_method())
-                .fold($zero, |acc, x| acc + x)
+        pub fn $method(&self) -> Income {
+            let incomes = self.assets().map(|a| a.$method());
+            Income::new(
+                self.fx
+                    .total(&incomes.iter().map(Income::current).collect()),
+                self.fx
+                    .total(&incomes.iter().map(Income::balance).collect()),
+            )
         }
     };
 }
 
 impl Portfolio {
+    /// Adds papers of `other` (e.g. another account of the user) to this portfolio
+    pub fn merge(&mut self, other: &Portfolio) {
+        self.bonds.merge(&other.bonds);
+        self.shares.merge(&other.shares);
+        self.etfs.merge(&other.etfs);
+        self.currencies.merge(&other.currencies);
+        self.futures.merge(&other.futures);
+        extend_unique(&mut self.skipped, &other.skipped);
+        if let Some(operations) = &other.operations {
+            self.add_operations(operations.iter().cloned());
+        }
+    }
+
+    /// All papers tagged by instrument kind
+    #[must_use]
+    pub fn loaded_papers(&self) -> Vec<LoadedPaper> {
+        let bonds = self.bonds.papers.iter().cloned().map(LoadedPaper::Bond);
+        let shares = self.shares.papers.iter().cloned().map(LoadedPaper::Share);
+        let etfs = self.etfs.papers.iter().cloned().map(LoadedPaper::Etf);
+        let currencies = self
+            .currencies
+            .papers
+            .iter()
+            .cloned()
+            .map(LoadedPaper::Currency);
+        let futures = self.futures.papers.iter().cloned().map(LoadedPaper::Future);
+        bonds
+            .chain(shares)
+            .chain(etfs)
+            .chain(currencies)
+            .chain(futures)
+            .collect()
+    }
+
+    /// Records a position that failed to load
+    pub fn add_skipped(&mut self, position: SkippedPosition) {
+        self.skipped.push(position);
+    }
+
+    /// Positions that failed to load and are left out of totals
+    #[must_use]
+    pub fn skipped(&self) -> &[SkippedPosition] {
+        &self.skipped
+    }
+
+    /// Adds operations of the account, deposits and withdrawals among them.
+    /// Only a portfolio with every position of the account is given them.
+    pub fn add_operations(&mut self, operations: impl IntoIterator<Item = Operation>) {
+        self.operations
+            .get_or_insert_with(Vec::new)
+            .extend(operations);
+    }
+
+    /// XIRR of deposits and withdrawals and time-weighted return of the account
+    /// up to its current value, `None` if the portfolio holds some positions only
+    #[must_use]
+    pub fn returns(&self) -> Option<Returns> {
+        self.operations
+            .as_ref()
+            .map(|operations| Returns::of_account(operations, self.current(), &self.fx, Utc::now()))
+    }
+
     pub fn add_loaded_paper(&mut self, paper: LoadedPaper) {
         match paper {
             LoadedPaper::Bond(p) => self.bonds.add_paper(p),
@@ -59,15 +148,52 @@ impl Portfolio {
 
     #[must_use]
     pub fn new(output_papers: bool) -> Self {
+        Self::with_fx_rates(output_papers, FxRates::default())
+    }
+
+    /// Creates a portfolio which totals are converted into `fx` reporting currency
+    #[must_use]
+    pub fn with_fx_rates(output_papers: bool, fx: FxRates) -> Self {
+        let fx = Arc::new(fx);
         Self {
-            bonds: Asset::new("Bonds", CouponProfit, output_papers),
-            shares: Asset::new("Shares", DividendProfit, output_papers),
-            etfs: Asset::new("Etfs", DividendProfit, output_papers),
-            currencies: Asset::new("Currencies", NoneProfit, output_papers),
-            futures: Asset::new("Futures", NoneProfit, output_papers),
+            bonds: Asset::new("Bonds", CouponProfit, output_papers).with_fx_rates(fx.clone()),
+            shares: Asset::new("Shares", DividendProfit, output_papers).with_fx_rates(fx.clone()),
+            etfs: Asset::new("Etfs", DividendProfit, output_papers).with_fx_rates(fx.clone()),
+            currencies: Asset::new("Currencies", NoneProfit, output_papers)
+                .with_fx_rates(fx.clone()),
+            futures: Asset::new("Futures", NoneProfit, output_papers).with_fx_rates(fx.clone()),
+            fx,
+            skipped: Vec::new(),
+            operations: None,
         }
     }
 
+    /// Currency all portfolio totals are expressed in
+    #[must_use]
+    pub fn reporting_currency(&self) -> Currency {
+        self.fx.reporting_currency()
+    }
+
+    #[must_use]
+    pub fn fx_rates(&self) -> &FxRates {
+        &self.fx
+    }
+
+    /// Rates used to convert papers held in currencies other than the reporting one
+    #[must_use]
+    pub fn used_fx_rates(&self) -> Vec<FxRate> {
+        let currencies: BTreeSet<Currency> =
+            self.assets().iter().flat_map(|a| a.currencies()).collect();
+        self.fx.rates_for(currencies)
+    }
+
+    /// Currencies of papers left out of totals because there is no exchange rate for them
+    #[must_use]
+    pub fn unconverted_currencies(&self) -> Vec<Currency> {
+        self.fx
+            .unconvertible(self.assets().iter().flat_map(|a| a.currencies()))
+    }
+
     /// Returns a slice of all assets
     #[must_use]
     fn assets(&self) -> [&dyn PortfolioAsset; 5] {
@@ -80,16 +206,12 @@ impl Portfolio {
         ]
     }
 
-    impl_portfolio_aggregator!(income, income, Income, Income::zero(Currency::RUB));
-    impl_portfolio_aggregator!(
-        total_income,
-        total_income,
-        Income,
-        Income::zero(Currency::RUB)
-    );
-    impl_portfolio_aggregator!(balance, balance, Money, Money::zero(Currency::RUB));
-    impl_portfolio_aggregator!(current, current, Money, Money::zero(Currency::RUB));
-    impl_portfolio_aggregator!(dividends, dividends, Money, Money::zero(Currency::RUB));
+    impl_portfolio_aggregator!(income, Income);
+    impl_portfolio_aggregator!(total_income, Income);
+    impl_portfolio_aggregator!(balance, Money);
+    impl_portfolio_aggregator!(current, Money);
+    impl_portfolio_aggregator!(dividends, Money);
+    impl_portfolio_aggregator!(realized, Money);
 
     #[must_use]
     pub fn count_not_empty_assets(&self) -> usize {
@@ -104,7 +226,9 @@ trait PortfolioAsset {
     fn balance(&self) -> Money;
     fn current(&self) -> Money;
     fn dividends(&self) -> Money;
+    fn realized(&self) -> Money;
     fn is_asset_empty(&self) -> bool;
+    fn currencies(&self) -> Vec<Currency>;
 }
 
 impl<P: Profit> PortfolioAsset for Asset<P> {
@@ -128,9 +252,17 @@ impl<P: Profit> PortfolioAsset for Asset<P> {
         Asset::dividends(self)
     }
 
+    fn realized(&self) -> Money {
+        Asset::realized(self)
+    }
+
     fn is_asset_empty(&self) -> bool {
         Asset::is_empty(self)
     }
+
+    fn currencies(&self) -> Vec<Currency> {
+        self.papers.iter().map(Paper::currency).collect()
+    }
 }
 
 impl Default for Portfolio {
@@ -147,48 +279,48 @@ impl<P: Profit> Asset<P> {
             name,
             output_papers,
             profit,
+            fx: Arc::new(FxRates::default()),
         }
     }
 
+    /// Sets rates used to convert totals into the reporting currency
+    #[must_use]
+    pub fn with_fx_rates(mut self, fx: Arc<FxRates>) -> Self {
+        self.fx = fx;
+        self
+    }
+
     pub fn add_paper(&mut self, paper: Paper<P>) {
         self.papers.push(paper);
     }
 
+    fn merge(&mut self, other: &Asset<P>) {
+        self.papers.extend(other.papers.iter().cloned());
+    }
+
     pub fn income(&self) -> Income {
-        self.fold(Income::zero, |mut acc, p| {
-            acc += p.income();
-            acc
-        })
+        Income::new(self.current(), self.balance())
     }
 
     pub fn total_income(&self) -> Income {
-        self.fold(Income::zero, |mut acc, p| {
-            acc += p.total_income();
-            acc
-        })
+        Income::new(self.total(|p| p.total_income().current()), self.balance())
     }
 
     pub fn current(&self) -> Money {
-        self.fold(Money::zero, |mut acc, p| {
-            acc += p.current();
-            acc
-        })
+        self.total(Paper::current)
     }
 
     pub fn balance(&self) -> Money {
-        self.fold(Money::zero, |mut acc, p| {
-            acc += p.balance();
-            acc
-        })
+        self.total(Paper::balance)
     }
 
     pub fn dividends(&self) -> Money {
-        self.fold(Money::zero, |mut acc, p| {
-            // IMPORTANT: We need absolute dividend value here but current is absolute + balance
-            // so we have to subtract
-            acc += p.dividends().current - p.dividends().balance;
-            acc
-        })
+        self.total(|p| p.totals.additional_profit)
+    }
+
+    /// Realized profit of sold units
+    pub fn realized(&self) -> Money {
+        self.total(Paper::realized)
     }
 
     #[must_use]
@@ -201,21 +333,19 @@ impl<P: Profit> Asset<P> {
         &self.papers
     }
 
-    fn fold<B, IF, F>(&self, mut init: IF, f: F) -> B
+    /// Sums papers' money in the reporting currency
+    fn total<F>(&self, f: F) -> Money
     where
-        IF: FnMut(Currency) -> B,
-        F: FnMut(B, &Paper<P>) -> B,
+        F: FnMut(&Paper<P>) -> Money,
     {
-        let currency = self.currency();
-        self.papers.iter().fold(init(currency), f)
+        let bag: MoneyBag = self.papers.iter().map(f).collect();
+        self.fx.total(&bag)
     }
 
-    fn currency(&self) -> Currency {
-        if self.papers.is_empty() {
-            iso_currency::Currency::RUB
-        } else {
-            self.papers[0].currency()
-        }
+    /// Currency all asset totals are expressed in
+    #[must_use]
+    pub fn currency(&self) -> Currency {
+        self.fx.reporting_currency()
     }
 }
 
@@ -257,6 +387,59 @@ mod tests {
         assert_eq!(dec!(1850), test_portfolio.total_income().current);
     }
 
+    #[test]
+    fn asset_totals_convert_currencies_and_skip_unknown() {
+        let mut fx = FxRates::new(Currency::RUB);
+        fx.insert(Currency::USD, dec!(90));
+        let mut portfolio = Portfolio::with_fx_rates(true, fx);
+        for (currency, price) in [
+            (Currency::RUB, dec!(100)),
+            (Currency::USD, dec!(2)),
+            (Currency::CNY, dec!(15)),
+        ] {
+            portfolio.shares.add_paper(Paper {
+                name: currency.code().to_string(),
+                ticker: Ticker::new(currency.code().to_string()),
+                figi: Figi::new(currency.code().to_string()),
+                position: Position {
+                    currency,
+                    average_buy_price: Money::from_value(price, currency),
+                    current_instrument_price: Money::from_value(price, currency),
+                    quantity: dec!(1),
+                },
+                totals: Totals::zero(currency),
+                profit: DividendProfit,
+            });
+        }
+
+        assert_eq!(
+            portfolio.current(),
+            Money::from_value(dec!(280), Currency::RUB)
+        );
+        assert_eq!(portfolio.unconverted_currencies(), [Currency::CNY]);
+    }
+
+    #[rstest]
+    fn merge_keeps_each_skipped_position_once(test_portfolio: Portfolio) {
+        let skipped = SkippedPosition::new(
+            Figi::new("BBG000000001"),
+            None,
+            "option",
+            "Unsupported instrument type 'option'",
+        );
+        let mut other = Portfolio::new(true);
+        other.add_skipped(skipped.clone());
+        let mut total = Portfolio::new(false);
+
+        total.merge(&test_portfolio);
+        total.merge(&other);
+        total.merge(&other);
+
+        assert_eq!(total.skipped(), [skipped]);
+        assert_eq!(total.balance(), test_portfolio.balance());
+        assert!(format!("{total}").contains("Skipped positions"));
+    }
+
     #[fixture]
     fn test_portfolio() -> Portfolio {
         let currency = Currency::RUB;
@@ -274,6 +457,7 @@ mod tests {
             totals: Totals {
                 additional_profit: Money::from_value(dec!(100), currency),
                 fees: Money::from_value(dec!(10), currency),
+                ..Totals::zero(currency)
             },
             profit: CouponProfit,
         });
@@ -291,6 +475,7 @@ mod tests {
             totals: Totals {
                 additional_profit: Money::from_value(dec!(50), currency),
                 fees: Money::from_value(dec!(10), currency),
+                ..Totals::zero(currency)
             },
             profit: DividendProfit,
         });
@@ -304,6 +489,9 @@ mod tests {
             etfs,
             currencies,
             futures,
+            fx: Arc::new(FxRates::default()),
+            skipped: Vec::new(),
+            operations: None,
         }
     }
 }
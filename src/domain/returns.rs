@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::Operation;
+
+use crate::{to_datetime_utc, to_money};
+
+use super::fx::FxRates;
+use super::journal::OperationKind;
+use super::money::{Money, MoneyBag};
+use super::operations::unique_operations;
+
+const DAYS_IN_YEAR: f64 = 365.0;
+
+/// Money put into an investment (negative) or taken out of it (positive) on a date
+#[derive(Clone, Copy, Debug)]
+pub struct DatedFlow {
+    pub date: DateTime<Utc>,
+    pub amount: Decimal,
+}
+
+/// Value of an investment at the end of a day and the money put into it that day
+#[derive(Clone, Copy, Debug)]
+pub struct Valuation {
+    pub date: DateTime<Utc>,
+    /// Value after the day's flows
+    pub value: Decimal,
+    /// Money put in, negative if taken out
+    pub flow: Decimal,
+}
+
+/// Money-weighted and time-weighted returns of a position or an account, percent.
+/// `None` if there is not enough history to compute a return.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Returns {
+    /// Annualized internal rate of return of dated cash flows (XIRR)
+    pub xirr: Option<Decimal>,
+    /// Cumulative time-weighted return, unaffected by the size and timing of flows
+    pub twr: Option<Decimal>,
+}
+
+impl Returns {
+    /// Returns of a position from its operations and `current` value.
+    /// Payments are converted with `fx` into the currency of `current`.
+    /// The position is valued on the days of its operations at the last trade price,
+    /// there are no market prices between operations.
+    #[must_use]
+    pub fn of_position(
+        operations: &[Operation],
+        current: Money,
+        fx: &FxRates,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut flows = Vec::new();
+        let mut valuations = Vec::new();
+        let mut units = 0i64;
+        let mut price = None;
+        for (date, ops) in by_day(operations) {
+            let mut paid = Decimal::ZERO;
+            for op in ops {
+                let Some(payment) = to_money(op.payment.as_ref()).and_then(|p| fx.convert(p))
+                else {
+                    continue;
+                };
+                paid += payment.value;
+                if let Some((quantity, unit_price)) = trade(op, payment) {
+                    units += quantity;
+                    price = Some(unit_price.value);
+                }
+            }
+            flows.push(DatedFlow { date, amount: paid });
+            let value = price.map_or(Decimal::ZERO, |p| p * Decimal::from(units.max(0)));
+            valuations.push(Valuation {
+                date,
+                value,
+                flow: -paid,
+            });
+        }
+        Self::with_current(flows, valuations, current.value, now)
+    }
+
+    /// Returns of an account from all its operations and `current` value.
+    /// Deposits and withdrawals are the only flows. The account is valued on the days
+    /// of its operations as the cash balance and holdings at their last trade price,
+    /// converted with `fx`. Currency bought on the exchange is a holding until it is spent,
+    /// so spending it leaves a negative balance in the currency that offsets the holding.
+    #[must_use]
+    pub fn of_account(
+        operations: &[Operation],
+        current: Money,
+        fx: &FxRates,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut flows = Vec::new();
+        let mut valuations = Vec::new();
+        let mut cash = MoneyBag::new();
+        let mut holdings: HashMap<&str, (i64, Money)> = HashMap::new();
+        for (date, ops) in by_day(operations) {
+            let mut deposited = Decimal::ZERO;
+            for op in ops {
+                let Some(payment) = to_money(op.payment.as_ref()) else {
+                    continue;
+                };
+                cash += payment;
+                match OperationKind::of(op.operation_type()) {
+                    OperationKind::Deposit | OperationKind::Withdrawal => {
+                        if let Some(payment) = fx.convert(payment) {
+                            deposited += payment.value;
+                        }
+                    }
+                    _ => {
+                        if let Some((quantity, unit_price)) = trade(op, payment) {
+                            let holding = holdings.entry(&op.figi).or_insert((0, unit_price));
+                            *holding = (holding.0 + quantity, unit_price);
+                        }
+                    }
+                }
+            }
+            if !deposited.is_zero() {
+                flows.push(DatedFlow {
+                    date,
+                    amount: -deposited,
+                });
+            }
+            let value: MoneyBag = cash
+                .iter()
+                .chain(
+                    holdings
+                        .values()
+                        .map(|(units, price)| *price * Decimal::from((*units).max(0))),
+                )
+                .collect();
+            valuations.push(Valuation {
+                date,
+                value: fx.total(&value).value,
+                flow: deposited,
+            });
+        }
+        Self::with_current(flows, valuations, current.value, now)
+    }
+
+    fn with_current(
+        mut flows: Vec<DatedFlow>,
+        mut valuations: Vec<Valuation>,
+        current: Decimal,
+        now: DateTime<Utc>,
+    ) -> Self {
+        flows.push(DatedFlow {
+            date: now,
+            amount: current,
+        });
+        valuations.push(Valuation {
+            date: now,
+            value: current,
+            flow: Decimal::ZERO,
+        });
+        Self {
+            xirr: xirr(&flows),
+            twr: twr(&valuations),
+        }
+    }
+}
+
+/// Annualized rate, percent, at which the flows' present values sum up to zero.
+/// `None` unless there is money both put in and taken out, or if the rate cannot be found.
+#[must_use]
+pub fn xirr(flows: &[DatedFlow]) -> Option<Decimal> {
+    let has_outflow = flows
+        .iter()
+        .any(|f| f.amount.is_sign_negative() && !f.amount.is_zero());
+    let has_inflow = flows
+        .iter()
+        .any(|f| f.amount.is_sign_positive() && !f.amount.is_zero());
+    if !has_outflow || !has_inflow {
+        return None;
+    }
+    let start = flows.iter().map(|f| f.date).min()?;
+    let points = flows
+        .iter()
+        .map(|f| {
+            let years = (f.date - start).num_days() as f64 / DAYS_IN_YEAR;
+            Some((years, f.amount.to_f64()?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let npv = |rate: f64| -> f64 {
+        points
+            .iter()
+            .map(|(years, amount)| amount / (1.0 + rate).powf(*years))
+            .sum()
+    };
+
+    // Bracket the root, then bisect: present value falls as the rate grows
+    let mut low = -0.999_999;
+    let mut high = 1.0;
+    let low_sign = npv(low).signum();
+    while npv(high).signum() == low_sign {
+        high *= 10.0;
+        if high > 1e6 {
+            return None;
+        }
+    }
+    for _ in 0..200 {
+        let middle = f64::midpoint(low, high);
+        let value = npv(middle);
+        if !value.is_finite() {
+            return None;
+        }
+        if value.signum() == low_sign {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let rate = f64::midpoint(low, high) * 100.0;
+    Decimal::try_from(rate).ok().map(|r| r.round_dp(2))
+}
+
+/// Cumulative return, percent, of sub-periods between valuations chained together.
+/// Sub-periods starting with no value are skipped. `None` if there is none left.
+#[must_use]
+pub fn twr(valuations: &[Valuation]) -> Option<Decimal> {
+    let mut growth = Decimal::ONE;
+    let mut periods = 0;
+    for (previous, next) in valuations.iter().tuple_windows() {
+        if previous.value <= Decimal::ZERO {
+            continue;
+        }
+        growth *= (next.value - next.flow) / previous.value;
+        periods += 1;
+    }
+    if periods == 0 {
+        None
+    } else {
+        Some(((growth - Decimal::ONE) * dec!(100)).round_dp(2))
+    }
+}
+
+/// Operations with a payment, each once, grouped by the day they were made
+fn by_day(operations: &[Operation]) -> Vec<(DateTime<Utc>, Vec<&Operation>)> {
+    let mut days: BTreeMap<NaiveDate, (DateTime<Utc>, Vec<&Operation>)> = BTreeMap::new();
+    for op in unique_operations(operations).filter(|op| op.payment.is_some()) {
+        let datetime = to_datetime_utc(op.date.as_ref());
+        let day = days
+            .entry(datetime.date_naive())
+            .or_insert((datetime, Vec::new()));
+        day.0 = day.0.max(datetime);
+        day.1.push(op);
+    }
+    days.into_values().collect()
+}
+
+/// Units a buy adds (or a sell takes) and the unit price, `None` for other operations
+fn trade(op: &Operation, payment: Money) -> Option<(i64, Money)> {
+    let quantity = op.quantity - op.quantity_rest;
+    if quantity == 0 {
+        return None;
+    }
+    let price = Money::from_value(
+        payment.value.abs() / Decimal::from(quantity),
+        payment.currency,
+    );
+    match OperationKind::of(op.operation_type()) {
+        OperationKind::Buy => Some((quantity, price)),
+        OperationKind::Sell => Some((-quantity, price)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iso_currency::Currency;
+    use tinkoff_invest_api::tcs::OperationType;
+
+    use super::*;
+    use crate::domain::test_support::{of, operation, paid, trade};
+
+    fn date(value: &str) -> DateTime<Utc> {
+        format!("{value}T10:00:00Z").parse().unwrap()
+    }
+
+    fn rub(value: Decimal) -> Money {
+        Money::from_value(value, Currency::RUB)
+    }
+
+    #[test]
+    fn xirr_of_a_year_long_investment_is_its_gain() {
+        let flows = [
+            DatedFlow {
+                date: date("2023-01-01"),
+                amount: dec!(-1000),
+            },
+            DatedFlow {
+                date: date("2024-01-01"),
+                amount: dec!(1100),
+            },
+        ];
+
+        assert_eq!(xirr(&flows), Some(dec!(10)));
+    }
+
+    #[test]
+    fn xirr_needs_money_in_and_out() {
+        let flows = [DatedFlow {
+            date: date("2023-01-01"),
+            amount: dec!(-1000),
+        }];
+
+        assert_eq!(xirr(&flows), None);
+        assert_eq!(xirr(&[]), None);
+    }
+
+    #[test]
+    fn twr_ignores_the_size_of_deposits() {
+        // +10% on 1000, then 10000 deposited and +10% on 11000
+        let valuations = [
+            Valuation {
+                date: date("2023-01-01"),
+                value: dec!(1000),
+                flow: dec!(1000),
+            },
+            Valuation {
+                date: date("2023-07-01"),
+                value: dec!(11100),
+                flow: dec!(10000),
+            },
+            Valuation {
+                date: date("2024-01-01"),
+                value: dec!(12210),
+                flow: dec!(0),
+            },
+        ];
+
+        assert_eq!(twr(&valuations), Some(dec!(21)));
+    }
+
+    #[test]
+    fn position_returns_count_income_and_trades() {
+        let operations = [
+            of(
+                "BBG004730N88",
+                trade("1", OperationType::Buy, "2023-01-01T10:00:00Z", 10, "-1000"),
+            ),
+            of(
+                "BBG004730N88",
+                operation("2", OperationType::Dividend, "2024-01-01T10:00:00Z", "100"),
+            ),
+            of(
+                "BBG004730N88",
+                operation("2", OperationType::Dividend, "2024-01-01T10:00:00Z", "100"),
+            ),
+        ];
+
+        let returns = Returns::of_position(
+            &operations,
+            rub(dec!(1000)),
+            &FxRates::default(),
+            date("2024-01-01"),
+        );
+
+        assert_eq!(returns.xirr, Some(dec!(10)));
+        assert_eq!(returns.twr, Some(dec!(10)));
+    }
+
+    #[test]
+    fn exchanging_currency_is_not_a_deposit() {
+        let operations = [
+            operation("1", OperationType::Input, "2023-01-01T10:00:00Z", "100000"),
+            of(
+                "BBG0013HGFT4",
+                trade(
+                    "2",
+                    OperationType::Buy,
+                    "2023-01-01T10:00:00Z",
+                    1000,
+                    "-90000",
+                ),
+            ),
+            paid(
+                of(
+                    "BBG004730N88",
+                    trade("3", OperationType::Buy, "2023-01-01T10:00:00Z", 10, "0"),
+                ),
+                dec!(-1000),
+                "usd",
+            ),
+        ];
+        let mut fx = FxRates::default();
+        fx.insert(Currency::USD, dec!(90));
+
+        let returns = Returns::of_account(&operations, rub(dec!(110000)), &fx, date("2024-01-01"));
+
+        assert_eq!(returns.xirr, Some(dec!(10)));
+        assert_eq!(returns.twr, Some(dec!(10)));
+    }
+
+    #[test]
+    fn account_returns_take_deposits_and_withdrawals_as_flows() {
+        let operations = [
+            operation("1", OperationType::Input, "2023-01-01T10:00:00Z", "1000"),
+            of(
+                "BBG004730N88",
+                trade("2", OperationType::Buy, "2023-01-01T10:00:00Z", 10, "-1000"),
+            ),
+            operation("3", OperationType::Input, "2023-07-01T10:00:00Z", "500"),
+            operation("4", OperationType::Output, "2024-01-01T10:00:00Z", "-200"),
+        ];
+
+        let returns = Returns::of_account(
+            &operations,
+            rub(dec!(1500)),
+            &FxRates::default(),
+            date("2024-01-01"),
+        );
+
+        // Units are valued at the purchase price until the current value is known
+        assert!(returns.xirr.unwrap() > dec!(15));
+        assert_eq!(returns.twr, Some(dec!(15.38)));
+    }
+}
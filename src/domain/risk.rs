@@ -4,14 +4,18 @@ use iso_currency::Currency;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal_macros::dec;
+use serde::Serialize;
 
+use super::fx::{FxRate, FxRates};
 use super::money::Money;
 use super::paper::Ticker;
 use super::portfolio::Portfolio;
+use super::returns::Returns;
+use super::skipped::SkippedPosition;
 use crate::domain::LoadedPaper;
 
 /// Risk analysis results for a portfolio
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RiskAnalysis {
     /// Asset allocation by type (bonds, shares, etfs, etc.)
     pub asset_allocation: AssetAllocation,
@@ -21,10 +25,16 @@ pub struct RiskAnalysis {
     pub position_concentration: PositionConcentration,
     /// Risk metrics summary
     pub risk_metrics: RiskMetrics,
+    /// XIRR and time-weighted return of the portfolio
+    pub returns: Returns,
+    /// Exchange rates used to convert values into the reporting currency
+    pub fx_rates: Vec<FxRate>,
+    /// Currencies of papers left out of the analysis because there is no exchange rate for them
+    pub unconverted_currencies: Vec<Currency>,
 }
 
 /// Asset allocation breakdown by instrument type
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AssetAllocation {
     pub bonds: AllocationItem,
     pub shares: AllocationItem,
@@ -35,7 +45,7 @@ pub struct AssetAllocation {
 }
 
 /// Single allocation item with value and percentage
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AllocationItem {
     pub name: &'static str,
     pub value: Money,
@@ -43,7 +53,7 @@ pub struct AllocationItem {
 }
 
 /// Currency diversification analysis
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CurrencyAllocation {
     pub allocations: Vec<CurrencyItem>,
     pub total_value: Money,
@@ -54,7 +64,7 @@ pub struct CurrencyAllocation {
 }
 
 /// Single currency allocation item
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CurrencyItem {
     pub currency: Currency,
     pub value: Money,
@@ -62,7 +72,7 @@ pub struct CurrencyItem {
 }
 
 /// Position concentration analysis
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PositionConcentration {
     /// Top 5 positions by value
     pub top_positions: Vec<PositionItem>,
@@ -77,7 +87,7 @@ pub struct PositionConcentration {
 }
 
 /// Single position in concentration analysis
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PositionItem {
     pub name: String,
     pub ticker: Ticker,
@@ -87,7 +97,7 @@ pub struct PositionItem {
 }
 
 /// Summary risk metrics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RiskMetrics {
     /// Overall diversification score (0-100, higher is better)
     pub diversification_score: Decimal,
@@ -114,7 +124,8 @@ pub struct RiskMetrics {
 }
 
 /// Risk level assessment
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -123,7 +134,7 @@ pub enum RiskLevel {
 }
 
 /// Target allocation for portfolio rebalancing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TargetAllocation {
     /// Target percentage for bonds (0-100)
     pub bonds: Decimal,
@@ -196,7 +207,7 @@ impl TargetAllocation {
 }
 
 /// Rebalancing recommendation for a single asset
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RebalanceRecommendation {
     /// Asset type name
     pub asset_type: &'static str,
@@ -213,7 +224,8 @@ pub struct RebalanceRecommendation {
 }
 
 /// Action to take for rebalancing
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RebalanceAction {
     /// Buy to increase position
     Buy,
@@ -223,8 +235,18 @@ pub enum RebalanceAction {
     Hold,
 }
 
+/// Risk analysis of a portfolio together with rebalancing recommendations
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskReport {
+    pub analysis: RiskAnalysis,
+    pub rebalancing: RebalancingAnalysis,
+    /// Positions of the portfolio that failed to load and are not analyzed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedPosition>,
+}
+
 /// Portfolio rebalancing analysis
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RebalancingAnalysis {
     /// Total portfolio value
     pub total_value: Money,
@@ -321,13 +343,28 @@ impl RebalancingAnalysis {
     }
 }
 
+impl RiskReport {
+    /// Analyze portfolio risks and rebalancing towards the `target` allocation
+    #[must_use]
+    pub fn new(portfolio: &Portfolio, target: &TargetAllocation) -> Self {
+        let analysis = RiskAnalysis::analyze(portfolio, &portfolio.loaded_papers());
+        let rebalancing = RebalancingAnalysis::analyze(&analysis.asset_allocation, target);
+        Self {
+            analysis,
+            rebalancing,
+            skipped: portfolio.skipped().to_vec(),
+        }
+    }
+}
+
 impl RiskAnalysis {
     /// Analyze portfolio risk metrics
     #[must_use]
     pub fn analyze(portfolio: &Portfolio, all_papers: &[LoadedPaper]) -> Self {
+        let fx = portfolio.fx_rates();
         let asset_allocation = AssetAllocation::from_portfolio(portfolio);
-        let currency_allocation = CurrencyAllocation::from_papers(all_papers);
-        let position_concentration = PositionConcentration::from_papers(all_papers);
+        let currency_allocation = CurrencyAllocation::from_papers(all_papers, fx);
+        let position_concentration = PositionConcentration::from_papers(all_papers, fx);
         let risk_metrics = RiskMetrics::calculate(
             &asset_allocation,
             &currency_allocation,
@@ -339,6 +376,9 @@ impl RiskAnalysis {
             currency_allocation,
             position_concentration,
             risk_metrics,
+            returns: portfolio.returns().unwrap_or_default(),
+            fx_rates: portfolio.used_fx_rates(),
+            unconverted_currencies: portfolio.unconverted_currencies(),
         }
     }
 }
@@ -352,8 +392,17 @@ impl AssetAllocation {
         let currencies_value = portfolio.currencies.current();
         let futures_value = portfolio.futures.current();
 
-        let total_value =
-            bonds_value + shares_value + etfs_value + currencies_value + futures_value;
+        let total_value = portfolio.fx_rates().total(
+            &[
+                bonds_value,
+                shares_value,
+                etfs_value,
+                currencies_value,
+                futures_value,
+            ]
+            .into_iter()
+            .collect(),
+        );
 
         let calc_item = |name: &'static str, value: Money| -> AllocationItem {
             let percentage = if total_value.value.is_zero() {
@@ -380,20 +429,25 @@ impl AssetAllocation {
 }
 
 impl CurrencyAllocation {
+    /// Groups papers by their currency. Values are converted into the `fx` reporting currency
+    /// so that shares of different currencies are comparable, papers without a rate are left out.
     #[must_use]
-    fn from_papers(papers: &[LoadedPaper]) -> Self {
+    fn from_papers(papers: &[LoadedPaper], fx: &FxRates) -> Self {
+        let reporting_currency = fx.reporting_currency();
         let mut currency_map: HashMap<Currency, Decimal> = HashMap::new();
         let mut total_value = Decimal::ZERO;
 
         for paper in papers {
-            let (value, currency) = match paper {
-                LoadedPaper::Bond(p) => (p.current().value, p.currency()),
-                LoadedPaper::Share(p) => (p.current().value, p.currency()),
-                LoadedPaper::Etf(p) => (p.current().value, p.currency()),
-                LoadedPaper::Currency(p) | LoadedPaper::Future(p) => {
-                    (p.current().value, p.currency())
-                }
+            let (current, currency) = match paper {
+                LoadedPaper::Bond(p) => (p.current(), p.currency()),
+                LoadedPaper::Share(p) => (p.current(), p.currency()),
+                LoadedPaper::Etf(p) => (p.current(), p.currency()),
+                LoadedPaper::Currency(p) | LoadedPaper::Future(p) => (p.current(), p.currency()),
+            };
+            let Some(value) = fx.convert(current) else {
+                continue;
             };
+            let value = value.value;
             *currency_map.entry(currency).or_default() += value;
             total_value += value;
         }
@@ -408,7 +462,7 @@ impl CurrencyAllocation {
                 };
                 CurrencyItem {
                     currency,
-                    value: Money::from_value(value, currency),
+                    value: Money::from_value(value, reporting_currency),
                     percentage,
                 }
             })
@@ -425,7 +479,7 @@ impl CurrencyAllocation {
             acc + share * share
         });
 
-        let total_money = Money::from_value(total_value, Currency::RUB);
+        let total_money = Money::from_value(total_value, reporting_currency);
 
         Self {
             allocations,
@@ -437,34 +491,17 @@ impl CurrencyAllocation {
 }
 
 impl PositionConcentration {
+    /// Values are converted into the `fx` reporting currency before being compared
     #[must_use]
-    fn from_papers(papers: &[LoadedPaper]) -> Self {
-        let mut position_values: Vec<(String, &Ticker, &'static str, Decimal, Currency)> =
-            Vec::new();
+    fn from_papers(papers: &[LoadedPaper], fx: &FxRates) -> Self {
+        let reporting_currency = fx.reporting_currency();
+        let mut position_values: Vec<(String, &Ticker, &'static str, Money)> = Vec::new();
 
         for paper in papers {
-            let (name, ticker, instrument_type, value, currency) = match paper {
-                LoadedPaper::Bond(p) => (
-                    p.name.clone(),
-                    &p.ticker,
-                    "Bond",
-                    p.current().value,
-                    p.currency(),
-                ),
-                LoadedPaper::Share(p) => (
-                    p.name.clone(),
-                    &p.ticker,
-                    "Share",
-                    p.current().value,
-                    p.currency(),
-                ),
-                LoadedPaper::Etf(p) => (
-                    p.name.clone(),
-                    &p.ticker,
-                    "ETF",
-                    p.current().value,
-                    p.currency(),
-                ),
+            let (name, ticker, instrument_type, current) = match paper {
+                LoadedPaper::Bond(p) => (p.name.clone(), &p.ticker, "Bond", p.current()),
+                LoadedPaper::Share(p) => (p.name.clone(), &p.ticker, "Share", p.current()),
+                LoadedPaper::Etf(p) => (p.name.clone(), &p.ticker, "ETF", p.current()),
                 LoadedPaper::Currency(p) | LoadedPaper::Future(p) => (
                     p.name.clone(),
                     &p.ticker,
@@ -474,33 +511,34 @@ impl PositionConcentration {
                         LoadedPaper::Future(_) => "Future",
                         _ => unreachable!(),
                     },
-                    p.current().value,
-                    p.currency(),
+                    p.current(),
                 ),
             };
-            position_values.push((name, ticker, instrument_type, value, currency));
+            if let Some(value) = fx.convert(current) {
+                position_values.push((name, ticker, instrument_type, value));
+            }
         }
 
-        let total_value: Decimal = position_values.iter().map(|(_, _, _, v, _)| v).sum();
+        let total_value: Decimal = position_values.iter().map(|(_, _, _, v)| v.value).sum();
         let total_positions = position_values.len();
 
         // Sort by value descending
-        position_values.sort_by_key(|b| std::cmp::Reverse(b.3));
+        position_values.sort_by_key(|b| std::cmp::Reverse(b.3.value));
 
         // Calculate percentages and create PositionItem list
         let mut items: Vec<PositionItem> = position_values
             .iter()
-            .map(|(name, ticker, instrument_type, value, currency)| {
+            .map(|(name, ticker, instrument_type, value)| {
                 let percentage = if total_value.is_zero() {
                     dec!(0)
                 } else {
-                    (*value / total_value) * dec!(100)
+                    (value.value / total_value) * dec!(100)
                 };
                 PositionItem {
                     name: name.clone(),
                     ticker: (*ticker).clone(),
                     instrument_type,
-                    value: Money::from_value(*value, *currency),
+                    value: *value,
                     percentage,
                 }
             })
@@ -519,7 +557,7 @@ impl PositionConcentration {
         // Keep only top 10 for display
         items.truncate(10);
 
-        let total_money = Money::from_value(total_value, Currency::RUB);
+        let total_money = Money::from_value(total_value, reporting_currency);
 
         Self {
             top_positions: items,
@@ -804,10 +842,7 @@ mod tests {
                 current_instrument_price: Money::from_value(dec!(5), currency),
                 quantity: dec!(100),
             },
-            totals: Totals {
-                additional_profit: Money::zero(currency),
-                fees: Money::zero(currency),
-            },
+            totals: Totals::zero(currency),
             profit: CouponProfit,
         });
         portfolio.bonds = bonds;
@@ -824,10 +859,7 @@ mod tests {
                 current_instrument_price: Money::from_value(dec!(5), currency),
                 quantity: dec!(100),
             },
-            totals: Totals {
-                additional_profit: Money::zero(currency),
-                fees: Money::zero(currency),
-            },
+            totals: Totals::zero(currency),
             profit: DividendProfit,
         });
         portfolio.shares = shares;
@@ -853,14 +885,11 @@ mod tests {
                 current_instrument_price: Money::from_value(dec!(100), Currency::RUB),
                 quantity: dec!(10),
             },
-            totals: Totals {
-                additional_profit: Money::zero(Currency::RUB),
-                fees: Money::zero(Currency::RUB),
-            },
+            totals: Totals::zero(Currency::RUB),
             profit: DividendProfit,
         })];
 
-        let allocation = CurrencyAllocation::from_papers(&papers);
+        let allocation = CurrencyAllocation::from_papers(&papers, &FxRates::default());
 
         assert_eq!(allocation.currency_count, 1);
         assert_eq!(allocation.hhi, dec!(1)); // HHI = 1.0 for single currency
@@ -879,10 +908,7 @@ mod tests {
                     current_instrument_price: Money::from_value(dec!(50), Currency::RUB),
                     quantity: dec!(10),
                 },
-                totals: Totals {
-                    additional_profit: Money::zero(Currency::RUB),
-                    fees: Money::zero(Currency::RUB),
-                },
+                totals: Totals::zero(Currency::RUB),
                 profit: DividendProfit,
             }),
             LoadedPaper::Share(Paper {
@@ -895,15 +921,14 @@ mod tests {
                     current_instrument_price: Money::from_value(dec!(50), Currency::USD),
                     quantity: dec!(10),
                 },
-                totals: Totals {
-                    additional_profit: Money::zero(Currency::USD),
-                    fees: Money::zero(Currency::USD),
-                },
+                totals: Totals::zero(Currency::USD),
                 profit: DividendProfit,
             }),
         ];
 
-        let allocation = CurrencyAllocation::from_papers(&papers);
+        let mut fx = FxRates::default();
+        fx.insert(Currency::USD, dec!(1));
+        let allocation = CurrencyAllocation::from_papers(&papers, &fx);
 
         assert_eq!(allocation.currency_count, 2);
         // HHI = 0.5^2 + 0.5^2 = 0.5
@@ -923,10 +948,7 @@ mod tests {
                     current_instrument_price: Money::from_value(dec!(100), Currency::RUB),
                     quantity: dec!(10),
                 },
-                totals: Totals {
-                    additional_profit: Money::zero(Currency::RUB),
-                    fees: Money::zero(Currency::RUB),
-                },
+                totals: Totals::zero(Currency::RUB),
                 profit: DividendProfit,
             }),
             LoadedPaper::Share(Paper {
@@ -939,15 +961,12 @@ mod tests {
                     current_instrument_price: Money::from_value(dec!(10), Currency::RUB),
                     quantity: dec!(10),
                 },
-                totals: Totals {
-                    additional_profit: Money::zero(Currency::RUB),
-                    fees: Money::zero(Currency::RUB),
-                },
+                totals: Totals::zero(Currency::RUB),
                 profit: DividendProfit,
             }),
         ];
 
-        let concentration = PositionConcentration::from_papers(&papers);
+        let concentration = PositionConcentration::from_papers(&papers, &FxRates::default());
 
         assert_eq!(concentration.total_positions, 2);
         // Large position is 1000/1100 = 90.91%
@@ -0,0 +1,388 @@
+//! Serialize implementations for domain types whose machine-readable form
+//! includes computed values (income, totals) rather than raw fields only.
+//!
+//! Plain data types derive `Serialize` next to their definitions.
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use super::cashflow::{CashFlow, CashFlowRow};
+use super::history::History;
+use super::iis::{IisReport, IisYear};
+use super::journal::Journal;
+use super::ldv::{LdvPosition, LdvReport};
+use super::money::Income;
+use super::paper::{Paper, Profit};
+use super::portfolio::{Asset, Portfolio};
+use super::simulate::{Allocation, SellSimulation};
+use super::tax::{InstrumentTax, TaxReport};
+
+impl Serialize for Income {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Income", 5)?;
+        s.serialize_field("currency", &self.currency)?;
+        s.serialize_field("current", &self.current)?;
+        s.serialize_field("balance", &self.balance)?;
+        s.serialize_field("income", &self.income())?;
+        s.serialize_field("percent", &self.percent())?;
+        s.end()
+    }
+}
+
+impl<P: Profit> Serialize for Paper<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = self.totals.today;
+        let mut s = serializer.serialize_struct("Paper", 17)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("ticker", &self.ticker)?;
+        s.serialize_field("figi", &self.figi)?;
+        s.serialize_field("currency", &self.currency())?;
+        s.serialize_field("quantity", &self.quantity())?;
+        s.serialize_field("average_buy_price", &self.average_buy_price())?;
+        s.serialize_field("current_instrument_price", &self.current_instrument_price())?;
+        s.serialize_field("balance", &self.balance())?;
+        s.serialize_field("current", &self.current())?;
+        s.serialize_field("totals", &self.totals)?;
+        s.serialize_field("income", &self.income())?;
+        if P::applicable() {
+            s.serialize_field("dividends", &self.dividends())?;
+            s.serialize_field("total_income", &self.total_income())?;
+        } else {
+            s.skip_field("dividends")?;
+            s.skip_field("total_income")?;
+        }
+        s.serialize_field("fees", &self.fees())?;
+        s.serialize_field("holding_days", &self.holding_days(now))?;
+        s.serialize_field("cagr", &self.cagr(now))?;
+        s.serialize_field("yield_on_cost", &self.yield_on_cost())?;
+        s.end()
+    }
+}
+
+impl<P: Profit> Serialize for Asset<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Asset", 10)?;
+        s.serialize_field("name", self.name)?;
+        s.serialize_field("instruments_count", &self.papers().len())?;
+        s.serialize_field("balance", &self.balance())?;
+        s.serialize_field("current", &self.current())?;
+        s.serialize_field("income", &self.income())?;
+        if P::applicable() {
+            s.serialize_field("total_income", &self.total_income())?;
+            s.serialize_field("dividends", &self.dividends())?;
+        } else {
+            s.skip_field("total_income")?;
+            s.skip_field("dividends")?;
+        }
+        s.serialize_field("realized", &self.realized())?;
+        if self.output_papers {
+            s.serialize_field("papers", self.papers())?;
+        } else {
+            s.skip_field("papers")?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for Portfolio {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Portfolio", 16)?;
+        s.serialize_field("reporting_currency", &self.reporting_currency())?;
+        s.serialize_field("bonds", &self.bonds)?;
+        s.serialize_field("shares", &self.shares)?;
+        s.serialize_field("etfs", &self.etfs)?;
+        s.serialize_field("currencies", &self.currencies)?;
+        s.serialize_field("futures", &self.futures)?;
+        s.serialize_field("balance", &self.balance())?;
+        s.serialize_field("current", &self.current())?;
+        s.serialize_field("income", &self.income())?;
+        s.serialize_field("total_income", &self.total_income())?;
+        s.serialize_field("dividends", &self.dividends())?;
+        s.serialize_field("realized", &self.realized())?;
+        s.serialize_field("returns", &self.returns())?;
+        s.serialize_field("fx_rates", &self.used_fx_rates())?;
+        s.serialize_field("unconverted_currencies", &self.unconverted_currencies())?;
+        if self.skipped.is_empty() {
+            s.skip_field("skipped")?;
+        } else {
+            s.serialize_field("skipped", &self.skipped)?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for History {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("History", 13)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("ticker", &self.ticker)?;
+        s.serialize_field("figi", &self.figi)?;
+        s.serialize_field("currency", &self.currency)?;
+        s.serialize_field("from", &self.period.from)?;
+        s.serialize_field("to", &self.period.to)?;
+        s.serialize_field("items", &self.items)?;
+        s.serialize_field("expenses", &self.expenses())?;
+        s.serialize_field("profit", &self.profit())?;
+        s.serialize_field("balance", &self.balance())?;
+        s.serialize_field("realized", &self.lots.realized())?;
+        s.serialize_field("sales", &self.lots.sales)?;
+        s.serialize_field("open_lots", &self.lots.open)?;
+        s.end()
+    }
+}
+
+impl Serialize for Journal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Journal", 5)?;
+        s.serialize_field("from", &self.period.from)?;
+        s.serialize_field("to", &self.period.to)?;
+        s.serialize_field("items", &self.items)?;
+        s.serialize_field("subtotals", &self.subtotals())?;
+        s.serialize_field("totals", &self.totals())?;
+        s.end()
+    }
+}
+
+impl Serialize for LdvPosition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("LdvPosition", 16)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("ticker", &self.ticker)?;
+        s.serialize_field("figi", &self.figi)?;
+        s.serialize_field("currency", &self.currency())?;
+        s.serialize_field("quantity", &self.quantity)?;
+        s.serialize_field("current_price", &self.current_price)?;
+        s.serialize_field("eligible_quantity", &self.eligible_quantity())?;
+        s.serialize_field("untracked_quantity", &self.untracked_quantity())?;
+        s.serialize_field("next_eligible", &self.next_eligible())?;
+        s.serialize_field("next_eligible_quantity", &self.next_eligible_quantity())?;
+        s.serialize_field("fully_eligible", &self.fully_eligible())?;
+        s.serialize_field("exempt_gain", &self.exempt_gain())?;
+        s.serialize_field("taxable_gain", &self.taxable_gain())?;
+        s.serialize_field("tax_today", &self.tax_today())?;
+        s.serialize_field("saved_by_waiting", &self.saved_by_waiting())?;
+        s.serialize_field("lots", &self.lots)?;
+        s.end()
+    }
+}
+
+impl Serialize for LdvReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("LdvReport", 6)?;
+        s.serialize_field("date", &self.today)?;
+        s.serialize_field("positions", &self.positions)?;
+        s.serialize_field("exempt_gain", &self.exempt_gain())?;
+        s.serialize_field("tax_today", &self.tax_today())?;
+        s.serialize_field("saved_by_waiting", &self.saved_by_waiting())?;
+        if self.skipped.is_empty() {
+            s.skip_field("skipped")?;
+        } else {
+            s.serialize_field("skipped", &self.skipped)?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for InstrumentTax {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("InstrumentTax", 8)?;
+        s.serialize_field("ticker", &self.ticker)?;
+        s.serialize_field("figi", &self.figi)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("realized", &self.realized)?;
+        s.serialize_field("dividends", &self.dividends)?;
+        s.serialize_field("coupons", &self.coupons)?;
+        s.serialize_field("withheld", &self.withheld)?;
+        s.serialize_field("self_declared", &self.self_declared())?;
+        s.end()
+    }
+}
+
+impl Serialize for TaxReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("TaxReport", 12)?;
+        s.serialize_field("year", &self.year)?;
+        s.serialize_field("instruments", &self.instruments)?;
+        s.serialize_field("realized", &self.realized())?;
+        s.serialize_field("dividends", &self.dividends())?;
+        s.serialize_field("coupons", &self.coupons())?;
+        s.serialize_field("taxable_income", &self.taxable_income())?;
+        s.serialize_field("tax_due", &self.tax_due())?;
+        s.serialize_field("withheld", &self.withheld())?;
+        s.serialize_field("outstanding", &self.outstanding())?;
+        s.serialize_field("self_declared", &self.self_declared())?;
+        let estimated = self.estimated();
+        if estimated.is_empty() {
+            s.skip_field("estimated_currencies")?;
+        } else {
+            s.serialize_field("estimated_currencies", &estimated)?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for IisYear {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("IisYear", 6)?;
+        s.serialize_field("year", &self.year)?;
+        s.serialize_field("contributions", &self.contributions)?;
+        s.serialize_field("limit", &self.limit)?;
+        s.serialize_field("excess", &self.excess())?;
+        s.serialize_field("remaining", &self.remaining())?;
+        s.serialize_field("deduction", &self.deduction())?;
+        s.end()
+    }
+}
+
+impl Serialize for IisReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("IisReport", 9)?;
+        s.serialize_field("opened", &self.opened)?;
+        s.serialize_field("kind", &self.kind())?;
+        s.serialize_field("holding_until", &self.holding_until())?;
+        s.serialize_field("held", &self.is_held())?;
+        s.serialize_field("years", &self.years)?;
+        s.serialize_field("contributions", &self.contributions())?;
+        s.serialize_field("deductions", &self.deductions())?;
+        s.serialize_field("withdrawals", &self.withdrawals)?;
+        s.serialize_field("voiding_withdrawals", &self.voiding_withdrawals())?;
+        s.end()
+    }
+}
+
+impl Serialize for Allocation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Allocation", 3)?;
+        s.serialize_field("portfolio", &self.portfolio)?;
+        s.serialize_field("position", &self.position)?;
+        s.serialize_field("weight", &self.weight())?;
+        s.end()
+    }
+}
+
+impl Serialize for SellSimulation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("SellSimulation", 23)?;
+        s.serialize_field("date", &self.today)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("ticker", &self.ticker)?;
+        s.serialize_field("figi", &self.figi)?;
+        s.serialize_field("quantity", &self.quantity)?;
+        s.serialize_field("held", &self.held)?;
+        s.serialize_field("remaining", &self.remaining())?;
+        s.serialize_field("price", &self.price)?;
+        s.serialize_field("fee_rate", &self.fee_rate)?;
+        s.serialize_field("lots", &self.lots)?;
+        s.serialize_field("unmatched", &self.unmatched)?;
+        s.serialize_field("proceeds", &self.proceeds())?;
+        s.serialize_field("fee", &self.fee())?;
+        s.serialize_field("realized", &self.realized())?;
+        s.serialize_field("exempt_quantity", &self.exempt_quantity())?;
+        s.serialize_field("exempt_gain", &self.exempt_gain())?;
+        s.serialize_field("taxable_gain", &self.taxable_gain())?;
+        s.serialize_field("tax", &self.tax())?;
+        s.serialize_field("exempt_tax", &self.exempt_tax())?;
+        s.serialize_field("net_cash", &self.net_cash())?;
+        s.serialize_field("before", &self.before())?;
+        s.serialize_field("after", &self.after())?;
+        if self.skipped.is_empty() {
+            s.skip_field("skipped")?;
+        } else {
+            s.serialize_field("skipped", &self.skipped)?;
+        }
+        s.end()
+    }
+}
+
+impl Serialize for CashFlowRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("CashFlowRow", 6)?;
+        s.serialize_field("period", &self.period)?;
+        s.serialize_field("deposits", &self.deposits)?;
+        s.serialize_field("withdrawals", &self.withdrawals)?;
+        s.serialize_field("net_contributions", &self.net_contributions())?;
+        s.serialize_field("income", &self.income)?;
+        s.serialize_field("costs", &self.costs)?;
+        s.end()
+    }
+}
+
+impl Serialize for CashFlow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("CashFlow", 9)?;
+        s.serialize_field("interval", &self.interval)?;
+        s.serialize_field("rows", &self.rows)?;
+        s.serialize_field("totals", &self.totals())?;
+        s.serialize_field("net_contributions", &self.net_contributions())?;
+        s.serialize_field("current", &self.current)?;
+        s.serialize_field("lifetime_gain", &self.lifetime_gain())?;
+        s.serialize_field("lifetime_return", &self.lifetime_return())?;
+        let unconverted = self.unconverted();
+        if unconverted.is_empty() {
+            s.skip_field("unconverted")?;
+        } else {
+            s.serialize_field("unconverted", &unconverted)?;
+        }
+        if self.skipped.is_empty() {
+            s.skip_field("skipped")?;
+        } else {
+            s.serialize_field("skipped", &self.skipped)?;
+        }
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iso_currency::Currency;
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::{DividendProfit, Figi, Money, Position, Ticker, Totals};
+
+    #[test]
+    fn income_serializes_exact_decimals() {
+        let income = Income::new(
+            Money::from_value(dec!(110.50), Currency::RUB),
+            Money::from_value(dec!(100), Currency::RUB),
+        );
+
+        let value = serde_json::to_value(income).unwrap();
+
+        assert_eq!(value["currency"], json!("RUB"));
+        assert_eq!(value["current"], json!("110.50"));
+        assert_eq!(value["income"], json!("10.50"));
+        assert_eq!(value["percent"], json!("10.5000"));
+    }
+
+    #[test]
+    fn paper_serializes_computed_values() {
+        let currency = Currency::RUB;
+        let paper = Paper {
+            name: "Sberbank".to_string(),
+            ticker: Ticker::new("SBER"),
+            figi: Figi::new("BBG004730N88"),
+            position: Position {
+                currency,
+                average_buy_price: Money::from_value(dec!(250.10), currency),
+                current_instrument_price: Money::from_value(dec!(300), currency),
+                quantity: dec!(10),
+            },
+            totals: Totals {
+                additional_profit: Money::from_value(dec!(33), currency),
+                fees: Money::from_value(dec!(-2.5), currency),
+                ..Totals::zero(currency)
+            },
+            profit: DividendProfit,
+        };
+
+        let value = serde_json::to_value(&paper).unwrap();
+
+        assert_eq!(value["ticker"], json!("SBER"));
+        assert_eq!(value["figi"], json!("BBG004730N88"));
+        assert_eq!(value["balance"]["value"], json!("2501.00"));
+        assert_eq!(value["current"]["value"], json!("3000"));
+        assert_eq!(value["totals"]["fees"]["value"], json!("-2.5"));
+        assert!(value.get("dividends").is_some());
+    }
+}
@@ -0,0 +1,398 @@
+use chrono::{DateTime, Datelike, Months, Utc};
+use iso_currency::Currency;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::to_money;
+
+use super::fx::FxRates;
+use super::journal::OperationKind;
+use super::ldv::{LDV_HOLDING_MONTHS, ldv_exempt_gain};
+use super::lots::TaxLots;
+use super::money::Money;
+use super::operations::unique_operations;
+use super::paper::Position;
+use super::skipped::SkippedPosition;
+use super::tax::ndfl;
+
+/// Part of an open lot a simulated sale would take
+#[derive(Clone, Serialize)]
+pub struct SoldLot {
+    pub acquired: DateTime<Utc>,
+    pub quantity: i64,
+    /// Cost of a unit
+    pub price: Money,
+    /// Gain of the units at the current price, a loss if negative
+    pub gain: Money,
+    /// Whether the units are held long enough for the long-term ownership benefit (LDV)
+    pub exempt: bool,
+}
+
+/// Value of the account and of the position in it, in the reporting currency
+#[derive(Clone, Copy)]
+pub struct Allocation {
+    pub portfolio: Money,
+    pub position: Money,
+}
+
+/// Outcome of selling units of a position at the current price, no order is placed
+pub struct SellSimulation {
+    pub name: String,
+    pub ticker: String,
+    pub figi: String,
+    /// Units to sell
+    pub quantity: i64,
+    /// Units held before the sale
+    pub held: Decimal,
+    pub price: Money,
+    /// Broker fee as a share of the trade amount
+    pub fee_rate: Decimal,
+    /// Lots the units are taken from, oldest first
+    pub lots: Vec<SoldLot>,
+    /// Units without a known purchase, their gain is unknown and left out
+    pub unmatched: i64,
+    pub today: DateTime<Utc>,
+    /// Positions left out of the value of the account
+    pub skipped: Vec<SkippedPosition>,
+    /// Value of the account before the sale, `None` if unknown
+    portfolio: Option<Money>,
+    fx: FxRates,
+}
+
+impl Allocation {
+    /// Share of the position in the account, percent
+    #[must_use]
+    pub fn weight(&self) -> Decimal {
+        if self.portfolio.value.is_zero() {
+            Decimal::ZERO
+        } else {
+            (self.position.value / self.portfolio.value * dec!(100)).round_dp(2)
+        }
+    }
+}
+
+impl SellSimulation {
+    /// Sale of `quantity` units of the `position` at its current price.
+    /// Units are taken from the open `lots` first in, first out.
+    #[must_use]
+    pub fn new(
+        name: String,
+        ticker: String,
+        figi: String,
+        position: &Position,
+        quantity: i64,
+        lots: &TaxLots,
+        today: DateTime<Utc>,
+    ) -> Self {
+        let price = position.current_instrument_price;
+        let mut left = quantity;
+        let mut sold = Vec::new();
+        // Lots bought in another currency cannot be matched with the sale
+        for lot in lots
+            .open
+            .iter()
+            .filter(|lot| lot.price.currency == price.currency)
+        {
+            if left == 0 {
+                break;
+            }
+            let taken = left.min(lot.quantity);
+            let exempt = lot
+                .acquired
+                .checked_add_months(Months::new(LDV_HOLDING_MONTHS))
+                .is_some_and(|eligible_from| eligible_from <= today);
+            sold.push(SoldLot {
+                acquired: lot.acquired,
+                quantity: taken,
+                price: lot.price,
+                gain: Money::from_value(
+                    (price.value - lot.price.value) * Decimal::from(taken),
+                    price.currency,
+                ),
+                exempt,
+            });
+            left -= taken;
+        }
+        Self {
+            name,
+            ticker,
+            figi,
+            quantity,
+            held: position.quantity,
+            price,
+            fee_rate: Decimal::ZERO,
+            lots: sold,
+            unmatched: left,
+            today,
+            skipped: Vec::new(),
+            portfolio: None,
+            fx: FxRates::default(),
+        }
+    }
+
+    /// Sets the broker fee rate, see [`observed_fee_rate`]
+    #[must_use]
+    pub fn with_fee_rate(mut self, fee_rate: Decimal) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Sets the value of the account before the sale, converted with `fx`
+    #[must_use]
+    pub fn with_portfolio(mut self, portfolio: Money, fx: FxRates) -> Self {
+        self.portfolio = Some(portfolio);
+        self.fx = fx;
+        self
+    }
+
+    /// Sets the positions left out of the value of the account
+    #[must_use]
+    pub fn with_skipped(mut self, skipped: Vec<SkippedPosition>) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// Units left after the sale
+    #[must_use]
+    pub fn remaining(&self) -> Decimal {
+        self.held - Decimal::from(self.quantity)
+    }
+
+    #[must_use]
+    pub fn proceeds(&self) -> Money {
+        self.price * Decimal::from(self.quantity)
+    }
+
+    #[must_use]
+    pub fn fee(&self) -> Money {
+        let fee = self.proceeds() * self.fee_rate;
+        Money::from_value(fee.value.round_dp(2), fee.currency)
+    }
+
+    /// Realized profit of the matched units less the fee, a loss if negative
+    #[must_use]
+    pub fn realized(&self) -> Money {
+        less(self.gain(|_| true), self.fee())
+    }
+
+    #[must_use]
+    pub fn exempt_quantity(&self) -> i64 {
+        self.lots
+            .iter()
+            .filter(|lot| lot.exempt)
+            .map(|lot| lot.quantity)
+            .sum()
+    }
+
+    /// Gain of units exempt from tax under the long-term ownership benefit,
+    /// up to its yearly cap as if no other securities were sold in the year
+    #[must_use]
+    pub fn exempt_gain(&self) -> Money {
+        ldv_exempt_gain(
+            self.gain(|lot| lot.exempt),
+            self.lots
+                .iter()
+                .filter(|lot| lot.exempt)
+                .map(|lot| (lot.acquired, lot.quantity)),
+            self.today,
+            &self.fx,
+        )
+    }
+
+    /// Realized profit the tax is due on, the fee is deducted from it in full
+    #[must_use]
+    pub fn taxable_gain(&self) -> Money {
+        less(self.realized(), self.exempt_gain())
+    }
+
+    /// Tax the broker withholds on the sale
+    #[must_use]
+    pub fn tax(&self) -> Money {
+        ndfl(self.taxable_gain(), self.today.year())
+    }
+
+    /// Tax the long-term ownership benefit saves
+    #[must_use]
+    pub fn exempt_tax(&self) -> Money {
+        less(ndfl(self.realized(), self.today.year()), self.tax())
+    }
+
+    /// Cash received: proceeds less the fee and withheld tax
+    #[must_use]
+    pub fn net_cash(&self) -> Money {
+        less(less(self.proceeds(), self.fee()), self.tax())
+    }
+
+    /// Allocation before the sale, `None` if the account value is unknown
+    #[must_use]
+    pub fn before(&self) -> Option<Allocation> {
+        self.allocation(self.held, Money::zero(self.price.currency))
+    }
+
+    /// Allocation after the sale, the net cash stays in the account
+    #[must_use]
+    pub fn after(&self) -> Option<Allocation> {
+        let fee = self.fee();
+        self.allocation(self.remaining(), fee.checked_add(self.tax()).unwrap_or(fee))
+    }
+
+    /// Allocation of `units` in the account which value is lowered by `costs`,
+    /// `None` if either cannot be converted into the currency of the account value
+    fn allocation(&self, units: Decimal, costs: Money) -> Option<Allocation> {
+        let portfolio = self.portfolio?;
+        let position = self.fx.convert(self.price * units)?;
+        let costs = self.fx.convert(costs)?;
+        Some(Allocation {
+            portfolio: portfolio.checked_sub(costs).ok()?,
+            position,
+        })
+    }
+
+    fn gain(&self, filter: impl Fn(&SoldLot) -> bool) -> Money {
+        self.lots
+            .iter()
+            .filter(|lot| filter(lot))
+            .fold(Money::zero(self.price.currency), |sum, lot| {
+                sum.checked_add(lot.gain).unwrap_or(sum)
+            })
+    }
+}
+
+/// `left` less `right`, just `left` if `right` is in another currency.
+/// Amounts of a simulation are all in the currency of the price.
+fn less(left: Money, right: Money) -> Money {
+    left.checked_sub(right).unwrap_or(left)
+}
+
+/// Broker fees of `operations` in `currency` relative to the amount of their buys and sells
+/// in it, zero if nothing was traded. Operations in other currencies are left out.
+#[must_use]
+pub fn observed_fee_rate<'a>(
+    operations: impl IntoIterator<Item = &'a Operation>,
+    currency: Currency,
+) -> Decimal {
+    let mut fees = Decimal::ZERO;
+    let mut traded = Decimal::ZERO;
+    for op in unique_operations(operations) {
+        let Some(payment) = to_money(op.payment.as_ref()) else {
+            continue;
+        };
+        if payment.currency != currency {
+            continue;
+        }
+        if op.operation_type() == OperationType::BrokerFee {
+            fees += payment.value.abs();
+        } else if matches!(
+            OperationKind::of(op.operation_type()),
+            OperationKind::Buy | OperationKind::Sell
+        ) {
+            traded += payment.value.abs();
+        }
+    }
+    if traded.is_zero() {
+        Decimal::ZERO
+    } else {
+        fees / traded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::test_support::{operation, paid, trade};
+
+    fn operations() -> Vec<Operation> {
+        vec![
+            trade("1", OperationType::Buy, "2021-03-01T10:00:00Z", 10, "-1000"),
+            operation("2", OperationType::BrokerFee, "2021-03-01T10:00:00Z", "-3"),
+            trade("3", OperationType::Buy, "2024-02-01T10:00:00Z", 10, "-2000"),
+            operation("4", OperationType::BrokerFee, "2024-02-01T10:00:00Z", "-6"),
+        ]
+    }
+
+    fn simulation(quantity: i64) -> SellSimulation {
+        let rub = |value| Money::from_value(value, Currency::RUB);
+        let position = Position {
+            currency: Currency::RUB,
+            average_buy_price: rub(dec!(150)),
+            current_instrument_price: rub(dec!(300)),
+            quantity: dec!(20),
+        };
+        SellSimulation::new(
+            "Sberbank".to_string(),
+            "SBER".to_string(),
+            "BBG004730N88".to_string(),
+            &position,
+            quantity,
+            &TaxLots::new(&operations()),
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        )
+        .with_fee_rate(observed_fee_rate(&operations(), Currency::RUB))
+        .with_portfolio(rub(dec!(30000)), FxRates::default())
+    }
+
+    #[test]
+    fn fee_rate_is_observed_from_past_trades() {
+        assert_eq!(observed_fee_rate(&operations(), Currency::RUB), dec!(0.003));
+        assert_eq!(observed_fee_rate(&[], Currency::RUB), dec!(0));
+    }
+
+    #[test]
+    fn fee_rate_is_observed_in_currency_of_position_only() {
+        let mut operations = operations();
+        let usd_fee = paid(
+            operation("5", OperationType::BrokerFee, "2024-03-01T10:00:00Z", "-0"),
+            dec!(-5),
+            "usd",
+        );
+        operations.push(usd_fee);
+        operations.push(trade(
+            "",
+            OperationType::Buy,
+            "2024-04-01T10:00:00Z",
+            10,
+            "-1000",
+        ));
+        operations.push(trade(
+            "",
+            OperationType::Buy,
+            "2024-05-01T10:00:00Z",
+            10,
+            "-1000",
+        ));
+
+        assert_eq!(observed_fee_rate(&operations, Currency::RUB), dec!(0.0018));
+        assert_eq!(observed_fee_rate(&operations, Currency::USD), dec!(0));
+    }
+
+    #[test]
+    fn sale_takes_oldest_lots_and_exempts_long_held_units() {
+        let simulation = simulation(15);
+
+        assert_eq!(simulation.proceeds().value, dec!(4500));
+        assert_eq!(simulation.fee().value, dec!(13.50));
+        assert_eq!(simulation.lots.len(), 2);
+        assert_eq!(simulation.exempt_quantity(), 10);
+        // 10 × (300 − 100) exempt, 5 × (300 − 200) − 13.50 taxable
+        assert_eq!(simulation.exempt_gain().value, dec!(2000));
+        assert_eq!(simulation.taxable_gain().value, dec!(486.50));
+        assert_eq!(simulation.tax().value, dec!(63.24));
+        assert_eq!(simulation.exempt_tax().value, dec!(260));
+        assert_eq!(simulation.net_cash().value, dec!(4423.26));
+    }
+
+    #[test]
+    fn allocation_changes_by_sold_units() {
+        let simulation = simulation(10);
+
+        let before = simulation.before().unwrap();
+        let after = simulation.after().unwrap();
+        assert_eq!(before.weight(), dec!(20));
+        assert_eq!(after.position.value, dec!(3000));
+        assert_eq!(after.portfolio.value, dec!(29991));
+        assert_eq!(after.weight(), dec!(10.00));
+        assert_eq!(simulation.unmatched, 0);
+    }
+}
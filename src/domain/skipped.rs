@@ -0,0 +1,40 @@
+use serde::Serialize;
+
+use super::paper::{Figi, Ticker};
+
+/// Portfolio position that could not be loaded and is left out of a report
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct SkippedPosition {
+    pub figi: Figi,
+    /// Known only when the instrument was resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticker: Option<Ticker>,
+    pub instrument_type: String,
+    pub reason: String,
+}
+
+impl SkippedPosition {
+    #[must_use]
+    pub fn new(
+        figi: Figi,
+        ticker: Option<Ticker>,
+        instrument_type: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self {
+            figi,
+            ticker,
+            instrument_type: instrument_type.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Appends `other` to `skipped` leaving out positions already there
+pub(crate) fn extend_unique(skipped: &mut Vec<SkippedPosition>, other: &[SkippedPosition]) {
+    for position in other {
+        if !skipped.contains(position) {
+            skipped.push(position.clone());
+        }
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+
+use chrono::Months;
+use iso_currency::Currency;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::{to_datetime_utc, to_money};
+
+use super::fx::FxRates;
+use super::journal::OperationKind;
+use super::ldv::{LDV_HOLDING_MONTHS, ldv_exempt_gain};
+use super::lots::{Sale, TaxLots};
+use super::money::{Money, MoneyBag};
+use super::operations::{AccountOperations, unique_operations};
+use super::paper::Instrument;
+use super::period::Period;
+
+/// Country of risk of domestic issuers
+const RUSSIA: &str = "RU";
+
+/// Personal income tax (NDFL) rate of residents on investment income
+pub const NDFL_RATE: Decimal = dec!(0.13);
+
+/// Rate of investment income above [`ndfl_threshold`]
+pub const NDFL_HIGH_RATE: Decimal = dec!(0.15);
+
+/// Yearly ruble investment income taxed at [`NDFL_RATE`], `None` before the progressive scale
+#[must_use]
+pub fn ndfl_threshold(year: i32) -> Option<Decimal> {
+    match year {
+        ..2021 => None,
+        2021..2025 => Some(dec!(5_000_000)),
+        _ => Some(dec!(2_400_000)),
+    }
+}
+
+/// Tax of the yearly `income`, nothing for a loss.
+///
+/// Ruble income above the threshold of the year is taxed at the higher rate.
+#[must_use]
+pub fn ndfl(income: Money, year: i32) -> Money {
+    let base = income.value.max(Decimal::ZERO);
+    let tax = match ndfl_threshold(year) {
+        Some(threshold) if income.currency == Currency::RUB && base > threshold => {
+            threshold * NDFL_RATE + (base - threshold) * NDFL_HIGH_RATE
+        }
+        _ => base * NDFL_RATE,
+    };
+    Money::from_value(tax.round_dp(2), income.currency)
+}
+
+/// Income and withheld tax of an instrument within a year
+#[derive(Clone)]
+pub struct InstrumentTax {
+    /// Empty for operations of the account itself, like tax withheld on all trades
+    pub ticker: String,
+    pub figi: String,
+    pub name: String,
+    /// Realized profit of sales matched with purchases first in, first out,
+    /// less broker fees of the sales and of the purchases of the units sold.
+    /// Gain exempt under the long-term ownership benefit is left out.
+    pub realized: MoneyBag,
+    pub dividends: MoneyBag,
+    pub coupons: MoneyBag,
+    /// Tax withheld by the broker, positive
+    pub withheld: MoneyBag,
+    /// Whether the issuer is foreign by its country of risk
+    pub foreign: bool,
+}
+
+/// Estimate of the personal income tax of a year
+pub struct TaxReport {
+    pub year: i32,
+    /// Instruments with income or withheld tax, ordered by ticker
+    pub instruments: Vec<InstrumentTax>,
+}
+
+impl InstrumentTax {
+    /// Income of `operations` with the instrument made in `year`.
+    /// Sales are matched with every purchase in `operations`, including earlier ones.
+    #[must_use]
+    pub fn new(
+        figi: &str,
+        operations: &[Operation],
+        instrument: Option<&Instrument>,
+        year: i32,
+    ) -> Self {
+        let period = Period::year(year).unwrap_or_default();
+        let mut tax = Self {
+            ticker: instrument.map(|i| i.ticker.to_string()).unwrap_or_default(),
+            figi: figi.to_string(),
+            name: instrument.map(|i| i.name.clone()).unwrap_or_default(),
+            realized: TaxLots::new(operations)
+                .within(period)
+                .sales
+                .iter()
+                .map(taxable_profit)
+                .collect(),
+            dividends: MoneyBag::new(),
+            coupons: MoneyBag::new(),
+            withheld: MoneyBag::new(),
+            foreign: instrument.is_some_and(|i| !i.country.is_empty() && i.country != RUSSIA),
+        };
+        for op in unique_operations(operations)
+            .filter(|op| period.contains(to_datetime_utc(op.date.as_ref())))
+        {
+            let Some(payment) = to_money(op.payment.as_ref()) else {
+                continue;
+            };
+            match OperationKind::of(op.operation_type()) {
+                OperationKind::Dividend => tax.dividends += payment,
+                OperationKind::Coupon => tax.coupons += payment,
+                OperationKind::Tax => tax.withheld += payment * dec!(-1),
+                _ => {}
+            }
+        }
+        tax
+    }
+
+    /// Whether there is nothing to report
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.realized,
+            &self.dividends,
+            &self.coupons,
+            &self.withheld,
+        ]
+        .iter()
+        .all(|bag| bag.iter().all(|money| money.value.is_zero()))
+    }
+
+    /// Dividends of foreign issuers to declare by the holder, even if the broker
+    /// withheld a part of the tax
+    #[must_use]
+    pub fn self_declared(&self) -> MoneyBag {
+        if self.foreign {
+            self.dividends.clone()
+        } else {
+            MoneyBag::new()
+        }
+    }
+}
+
+impl TaxReport {
+    /// Report of `year` from operations of an account up to the end of the year.
+    /// Tickers are taken from `instruments` by FIGI.
+    #[must_use]
+    pub fn new(
+        year: i32,
+        operations: &AccountOperations,
+        instruments: &HashMap<String, Instrument>,
+    ) -> Self {
+        let instruments = operations
+            .iter()
+            .map(|(figi, ops)| InstrumentTax::new(figi, ops, instruments.get(figi), year))
+            .filter(|tax| !tax.is_empty())
+            .sorted_by(|a, b| a.ticker.cmp(&b.ticker).then_with(|| a.figi.cmp(&b.figi)))
+            .collect();
+        Self { year, instruments }
+    }
+
+    /// Reports of several accounts together, income of an instrument held in several accounts summed
+    #[must_use]
+    pub fn merge<'a>(year: i32, reports: impl IntoIterator<Item = &'a TaxReport>) -> Self {
+        let mut instruments: Vec<InstrumentTax> = Vec::new();
+        for tax in reports.into_iter().flat_map(|report| &report.instruments) {
+            match instruments.iter_mut().find(|known| known.figi == tax.figi) {
+                Some(known) => {
+                    known.realized.extend(tax.realized.iter());
+                    known.dividends.extend(tax.dividends.iter());
+                    known.coupons.extend(tax.coupons.iter());
+                    known.withheld.extend(tax.withheld.iter());
+                }
+                None => instruments.push(tax.clone()),
+            }
+        }
+        instruments.sort_by(|a, b| a.ticker.cmp(&b.ticker).then_with(|| a.figi.cmp(&b.figi)));
+        Self { year, instruments }
+    }
+
+    #[must_use]
+    pub fn realized(&self) -> MoneyBag {
+        self.sum(|tax| &tax.realized)
+    }
+
+    #[must_use]
+    pub fn dividends(&self) -> MoneyBag {
+        self.sum(|tax| &tax.dividends)
+    }
+
+    #[must_use]
+    pub fn coupons(&self) -> MoneyBag {
+        self.sum(|tax| &tax.coupons)
+    }
+
+    #[must_use]
+    pub fn withheld(&self) -> MoneyBag {
+        self.sum(|tax| &tax.withheld)
+    }
+
+    /// Dividends of foreign issuers, see [`InstrumentTax::self_declared`]
+    #[must_use]
+    pub fn self_declared(&self) -> MoneyBag {
+        self.instruments
+            .iter()
+            .flat_map(|tax| tax.self_declared().iter().collect_vec())
+            .collect()
+    }
+
+    /// Taxable income per currency: dividends, coupons and realized profit unless it is a loss.
+    /// Losses of sales offset profits of other sales but not dividends or coupons.
+    #[must_use]
+    pub fn taxable_income(&self) -> MoneyBag {
+        let mut income: MoneyBag = self
+            .realized()
+            .iter()
+            .filter(|money| money.value.is_sign_positive())
+            .collect();
+        income.extend(self.dividends().iter());
+        income.extend(self.coupons().iter());
+        income
+    }
+
+    /// Tax of the taxable income per currency
+    #[must_use]
+    pub fn tax_due(&self) -> MoneyBag {
+        self.taxable_income()
+            .iter()
+            .map(|income| ndfl(income, self.year))
+            .collect()
+    }
+
+    /// Currencies of taxable income other than rubles. Their tax is estimated at [`NDFL_RATE`]
+    /// in the currency itself: it is not converted at the Central Bank rate and left out of
+    /// the ruble threshold of the higher rate.
+    #[must_use]
+    pub fn estimated(&self) -> Vec<Currency> {
+        self.taxable_income()
+            .iter()
+            .filter(|income| income.currency != Currency::RUB && !income.value.is_zero())
+            .map(|income| income.currency)
+            .collect()
+    }
+
+    /// Tax left to pay, an overpayment if negative
+    #[must_use]
+    pub fn outstanding(&self) -> MoneyBag {
+        let mut outstanding = self.tax_due();
+        outstanding.extend(self.withheld().iter().map(|money| money * dec!(-1)));
+        outstanding
+    }
+
+    fn sum<F>(&self, money: F) -> MoneyBag
+    where
+        F: Fn(&InstrumentTax) -> &MoneyBag,
+    {
+        self.instruments
+            .iter()
+            .flat_map(|tax| money(tax).iter())
+            .collect()
+    }
+}
+
+/// Realized profit of `sale` less its fees and the gain of units held long enough
+/// for the long-term ownership benefit, up to its cap as if no other securities were sold
+/// in the year. Sales in other currencies than rubles are not exempt, the cap is set in rubles
+/// and there are no Central Bank rates to convert it.
+fn taxable_profit(sale: &Sale) -> Money {
+    let realized = sale.realized();
+    let matched = sale.quantity - sale.unmatched;
+    if matched == 0 {
+        return realized.checked_sub(sale.fees).unwrap_or(realized);
+    }
+    let price = sale.proceeds / Decimal::from(matched);
+    let eligible = sale.lots.iter().filter(|lot| {
+        lot.acquired
+            .checked_add_months(Months::new(LDV_HOLDING_MONTHS))
+            .is_some_and(|eligible_from| eligible_from <= sale.datetime)
+    });
+    let gain = eligible
+        .clone()
+        .map(|lot| {
+            Money::from_value(
+                (price.value - lot.price.value) * Decimal::from(lot.quantity),
+                price.currency,
+            )
+        })
+        .fold(Money::zero(price.currency), |sum, gain| {
+            sum.checked_add(gain).unwrap_or(sum)
+        });
+    let exempt = ldv_exempt_gain(
+        gain,
+        eligible.map(|lot| (lot.acquired, lot.quantity)),
+        sale.datetime,
+        &FxRates::default(),
+    );
+    realized
+        .checked_sub(sale.fees)
+        .and_then(|profit| profit.checked_sub(exempt))
+        .unwrap_or(realized)
+}
+
+/// Operation types a tax report is made of
+#[must_use]
+pub fn tax_operation_types() -> Vec<OperationType> {
+    [
+        OperationKind::Buy,
+        OperationKind::Sell,
+        OperationKind::Dividend,
+        OperationKind::Coupon,
+        OperationKind::Tax,
+    ]
+    .into_iter()
+    .flat_map(|kind| kind.operation_types().iter().copied())
+    .chain([OperationType::BrokerFee])
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::domain::Ticker;
+    use crate::domain::test_support::{of, operation, paid, trade};
+
+    fn fee(id: &str, parent: &str, figi: &str, date: &str, payment: &str) -> Operation {
+        Operation {
+            parent_operation_id: parent.to_string(),
+            ..of(figi, operation(id, OperationType::BrokerFee, date, payment))
+        }
+    }
+
+    fn report() -> TaxReport {
+        let operations: AccountOperations = [
+            of(
+                "SBER",
+                trade("1", OperationType::Buy, "2023-03-01T10:00:00Z", 10, "-2500"),
+            ),
+            of(
+                "SBER",
+                trade("2", OperationType::Sell, "2024-05-01T10:00:00Z", 5, "1500"),
+            ),
+            fee("9", "1", "SBER", "2023-03-01T10:00:00Z", "-7.5"),
+            fee("10", "2", "SBER", "2024-05-01T10:00:00Z", "-5"),
+            // Bought within the year but not sold
+            of(
+                "OFZ",
+                trade("11", OperationType::Buy, "2024-02-01T10:00:00Z", 2, "-2000"),
+            ),
+            fee("12", "11", "OFZ", "2024-02-01T10:00:00Z", "-3"),
+            of(
+                "SBER",
+                operation("3", OperationType::Dividend, "2024-07-20T10:00:00Z", "330"),
+            ),
+            of(
+                "SBER",
+                operation(
+                    "4",
+                    OperationType::DividendTax,
+                    "2024-07-20T10:00:00Z",
+                    "-43",
+                ),
+            ),
+            of(
+                "SBER",
+                operation("5", OperationType::Dividend, "2023-07-20T10:00:00Z", "250"),
+            ),
+            of(
+                "AAPL",
+                operation("6", OperationType::Dividend, "2024-08-15T10:00:00Z", "90"),
+            ),
+            of(
+                "OFZ",
+                operation("7", OperationType::Coupon, "2024-09-13T10:00:00Z", "177"),
+            ),
+            of(
+                "",
+                operation("8", OperationType::Tax, "2024-12-31T10:00:00Z", "-32"),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        TaxReport::new(2024, &operations, &instruments())
+    }
+
+    fn instruments() -> HashMap<String, Instrument> {
+        HashMap::from([
+            (
+                "SBER".to_string(),
+                Instrument {
+                    name: "Сбер Банк".to_string(),
+                    ticker: Ticker::new("SBER"),
+                    country: "RU".to_string(),
+                },
+            ),
+            (
+                "AAPL".to_string(),
+                Instrument {
+                    name: "Apple".to_string(),
+                    ticker: Ticker::new("AAPL"),
+                    country: "US".to_string(),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn income_of_the_year_per_instrument() {
+        let report = report();
+
+        let sber = report
+            .instruments
+            .iter()
+            .find(|t| t.figi == "SBER")
+            .unwrap();
+        assert_eq!(sber.ticker, "SBER");
+        // 250 less the fee of the sale and the fee of the purchase of 5 units out of 10
+        assert_eq!(sber.realized.get(Currency::RUB).value, dec!(241.25));
+        let ofz = report.instruments.iter().find(|t| t.figi == "OFZ").unwrap();
+        assert!(ofz.realized.is_empty());
+        assert_eq!(sber.dividends.get(Currency::RUB).value, dec!(330));
+        assert_eq!(sber.withheld.get(Currency::RUB).value, dec!(43));
+        assert!(sber.self_declared().is_empty());
+        assert_eq!(report.instruments.len(), 4);
+    }
+
+    #[test]
+    fn outstanding_tax_is_due_minus_withheld() {
+        let report = report();
+
+        // 241.25 + 330 + 90 + 177 = 838.25
+        assert_eq!(
+            report.taxable_income().get(Currency::RUB).value,
+            dec!(838.25)
+        );
+        assert_eq!(report.tax_due().get(Currency::RUB).value, dec!(108.97));
+        assert_eq!(report.withheld().get(Currency::RUB).value, dec!(75));
+        assert_eq!(report.outstanding().get(Currency::RUB).value, dec!(33.97));
+        assert!(report.estimated().is_empty());
+        assert_eq!(report.self_declared().get(Currency::RUB).value, dec!(90));
+    }
+
+    #[test]
+    fn dividends_are_self_declared_by_issuer_country() {
+        let operations: AccountOperations = [
+            // Tax of a domestic dividend withheld the next year
+            of(
+                "SBER",
+                operation("1", OperationType::Dividend, "2024-12-28T10:00:00Z", "330"),
+            ),
+            of(
+                "SBER",
+                operation(
+                    "2",
+                    OperationType::DividendTax,
+                    "2025-01-10T10:00:00Z",
+                    "-43",
+                ),
+            ),
+            // Foreign dividend the broker withheld a part of the tax on
+            of(
+                "AAPL",
+                operation("3", OperationType::Dividend, "2024-08-15T10:00:00Z", "100"),
+            ),
+            of(
+                "AAPL",
+                operation(
+                    "4",
+                    OperationType::DividendTax,
+                    "2024-08-15T10:00:00Z",
+                    "-3",
+                ),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let report = TaxReport::new(2024, &operations, &instruments());
+
+        assert_eq!(report.self_declared().get(Currency::RUB).value, dec!(100));
+        // 13% of 430 less 3 withheld
+        assert_eq!(report.outstanding().get(Currency::RUB).value, dec!(52.90));
+    }
+
+    #[test]
+    fn gain_of_units_held_three_years_is_exempt() {
+        let operations: AccountOperations = [
+            of(
+                "SBER",
+                trade("1", OperationType::Buy, "2020-03-01T10:00:00Z", 10, "-1000"),
+            ),
+            of(
+                "SBER",
+                trade("2", OperationType::Buy, "2023-03-01T10:00:00Z", 10, "-2000"),
+            ),
+            of(
+                "SBER",
+                trade("3", OperationType::Sell, "2024-05-01T10:00:00Z", 15, "4500"),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let report = TaxReport::new(2024, &operations, &instruments());
+
+        // 10 × (300 − 100) exempt, 5 × (300 − 200) taxable
+        assert_eq!(report.realized().get(Currency::RUB).value, dec!(500));
+    }
+
+    #[test]
+    fn tax_in_other_currencies_is_estimated() {
+        let dividend = paid(
+            of(
+                "AAPL",
+                operation("1", OperationType::Dividend, "2024-08-15T10:00:00Z", "0"),
+            ),
+            dec!(100),
+            "usd",
+        );
+        let operations: AccountOperations = [dividend].into_iter().collect();
+
+        let report = TaxReport::new(2024, &operations, &instruments());
+
+        assert_eq!(report.estimated(), vec![Currency::USD]);
+        assert_eq!(report.tax_due().get(Currency::USD).value, dec!(13));
+    }
+
+    #[rstest]
+    #[case(2020, dec!(6_000_000), dec!(780_000))]
+    #[case(2024, dec!(6_000_000), dec!(800_000))]
+    #[case(2025, dec!(3_400_000), dec!(462_000))]
+    #[case(2025, dec!(-100), dec!(0))]
+    fn ndfl_is_progressive_since_2021(
+        #[case] year: i32,
+        #[case] income: Decimal,
+        #[case] expected: Decimal,
+    ) {
+        let tax = ndfl(Money::from_value(income, Currency::RUB), year);
+
+        assert_eq!(tax.value, expected);
+    }
+}
@@ -0,0 +1,54 @@
+//! Operations the unit tests of the domain are made of
+
+use rust_decimal::Decimal;
+use tinkoff_invest_api::tcs::{Operation, OperationType};
+
+use crate::{to_money_value, to_timestamp};
+
+/// Operation paid in rubles at `date` (RFC 3339)
+pub(crate) fn operation(
+    id: &str,
+    operation_type: OperationType,
+    date: &str,
+    payment: &str,
+) -> Operation {
+    Operation {
+        id: id.to_string(),
+        currency: "rub".to_string(),
+        operation_type: operation_type as i32,
+        date: Some(to_timestamp(date.parse().unwrap())),
+        payment: Some(to_money_value(payment.parse().unwrap(), "rub")),
+        ..Default::default()
+    }
+}
+
+/// Buy or sell of `quantity` units paid in rubles
+pub(crate) fn trade(
+    id: &str,
+    operation_type: OperationType,
+    date: &str,
+    quantity: i64,
+    payment: &str,
+) -> Operation {
+    Operation {
+        quantity,
+        ..operation(id, operation_type, date, payment)
+    }
+}
+
+/// `op` made with the instrument `figi`
+pub(crate) fn of(figi: &str, op: Operation) -> Operation {
+    Operation {
+        figi: figi.to_string(),
+        ..op
+    }
+}
+
+/// `op` paid `payment` in `currency` instead
+pub(crate) fn paid(op: Operation, payment: Decimal, currency: &str) -> Operation {
+    Operation {
+        currency: currency.to_string(),
+        payment: Some(to_money_value(payment, currency)),
+        ..op
+    }
+}
@@ -0,0 +1,799 @@
+//! In-memory broker API loaded from a fixture file.
+//!
+//! A fixture is a JSON document describing accounts, their portfolios and
+//! operations, instruments, dividends, coupons and last prices. [`FakeApi`]
+//! answers [`InvestApi`] requests from it, so every command can be run
+//! without a token or network access.
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, Context};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tinkoff_invest_api::tcs::{
+    AccessLevel, Account, AccountStatus, AccountType, Coupon, CouponType,
+    Currency as CurrencyInstrument, Dividend, InstrumentShort, LastPrice, MoneyValue, Operation,
+    OperationState, OperationType, PortfolioPosition, Quotation,
+};
+
+use crate::{
+    account_type_name,
+    api::{ApiFuture, InvestApi, OperationsPage, OperationsQuery},
+    client::InstrumentCatalog,
+    domain::{Instrument, Ticker},
+    parse_account_type, to_datetime_utc, to_decimal, to_money_value, to_quotation, to_timestamp,
+};
+
+/// Contents of a fixture file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Fixture {
+    pub accounts: Vec<FixtureAccount>,
+    pub instruments: Vec<FixtureInstrument>,
+    /// Positions by account id
+    pub portfolios: HashMap<String, Vec<FixturePosition>>,
+    /// Executed operations by account id
+    pub operations: HashMap<String, Vec<FixtureOperation>>,
+    /// Dividends by FIGI
+    pub dividends: HashMap<String, Vec<FixtureDividend>>,
+    /// Coupons by FIGI
+    pub coupons: HashMap<String, Vec<FixtureCoupon>>,
+    /// Last prices by FIGI
+    pub last_prices: HashMap<String, Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureAccount {
+    pub id: String,
+    pub name: String,
+    /// Account type as accepted by `--account`
+    #[serde(rename = "type", default = "default_account_type")]
+    pub account_type: String,
+    #[serde(default)]
+    pub status: FixtureAccountStatus,
+    #[serde(default)]
+    pub access_level: FixtureAccessLevel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureAccountStatus {
+    New,
+    #[default]
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FixtureAccessLevel {
+    #[default]
+    Full,
+    ReadOnly,
+    NoAccess,
+    Unspecified,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureInstrument {
+    pub figi: String,
+    pub ticker: String,
+    pub name: String,
+    /// `share`, `bond`, `etf`, `currency` or `futures`
+    pub instrument_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub class_code: String,
+    /// Country of risk of the issuer as an ISO code, unknown if not set
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub country_of_risk: String,
+    /// Settlement currency of a currency instrument, rubles if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Currency a currency instrument buys
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub iso_currency_name: String,
+    /// Amount of `iso_currency_name` the price is quoted for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nominal: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixturePosition {
+    pub figi: String,
+    pub instrument_type: String,
+    pub quantity: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_price: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_price: Option<Decimal>,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureOperation {
+    pub id: String,
+    /// Operation a broker fee was charged for
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub parent_operation_id: String,
+    pub figi: String,
+    /// Operation type without the `OPERATION_TYPE_` prefix, like `buy` or `dividend`
+    #[serde(rename = "type")]
+    pub operation_type: String,
+    /// Human readable operation name
+    #[serde(default)]
+    pub description: String,
+    pub date: DateTime<Utc>,
+    pub payment: Decimal,
+    #[serde(default)]
+    pub price: Decimal,
+    #[serde(default)]
+    pub quantity: i64,
+    #[serde(default)]
+    pub quantity_rest: i64,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureDividend {
+    pub dividend_net: Decimal,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub dividend_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureCoupon {
+    pub pay_one_bond: Decimal,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupon_date: Option<DateTime<Utc>>,
+    /// Coupon type without the `COUPON_TYPE_` prefix, like `constant`
+    #[serde(default)]
+    pub coupon_type: String,
+}
+
+fn default_account_type() -> String {
+    "tinkoff".to_string()
+}
+
+fn default_currency() -> String {
+    "rub".to_string()
+}
+
+/// Parses a lowercase protobuf enum name of `what` written without its `prefix`
+fn enum_value<T>(
+    what: &str,
+    prefix: &str,
+    name: &str,
+    parse: fn(&str) -> Option<T>,
+) -> eyre::Result<T> {
+    let full = format!("{prefix}{}", name.to_ascii_uppercase().replace('-', "_"));
+    parse(&full).ok_or_else(|| eyre::eyre!("Unknown {what} '{name}'"))
+}
+
+/// Lowercase protobuf enum `name` without its `prefix`, the inverse of [`enum_value`]
+fn enum_name(prefix: &str, name: &str) -> String {
+    name.strip_prefix(prefix)
+        .unwrap_or(name)
+        .to_ascii_lowercase()
+        .replace('_', "-")
+}
+
+fn decimal(value: Option<&MoneyValue>) -> Option<Decimal> {
+    value.map(|v| {
+        to_decimal(Some(&Quotation {
+            units: v.units,
+            nano: v.nano,
+        }))
+    })
+}
+
+fn currency_of(value: Option<&MoneyValue>) -> String {
+    value.map_or_else(default_currency, |v| v.currency.clone())
+}
+
+/// Broker API answering from a [`Fixture`]
+#[derive(Debug)]
+pub struct FakeApi {
+    accounts: Vec<Account>,
+    instruments: Vec<FixtureInstrument>,
+    portfolios: HashMap<String, Vec<PortfolioPosition>>,
+    operations: HashMap<String, Vec<Operation>>,
+    dividends: HashMap<String, Vec<Dividend>>,
+    coupons: HashMap<String, Vec<Coupon>>,
+    last_prices: HashMap<String, Decimal>,
+    /// Operations returned per page
+    page_size: usize,
+}
+
+/// Operations per page unless [`FakeApi::with_page_size`] is used
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+impl FakeApi {
+    /// Loads a fixture file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid fixture.
+    pub fn load(path: impl AsRef<Path>) -> color_eyre::Result<Self> {
+        let path = path.as_ref();
+        let content =
+            fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        let fixture: Fixture = serde_json::from_slice(&content)
+            .wrap_err_with(|| format!("Invalid fixture {}", path.display()))?;
+        Self::new(fixture)
+    }
+
+    /// Creates the API from `fixture`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fixture has unknown account, operation or coupon types.
+    pub fn new(fixture: Fixture) -> color_eyre::Result<Self> {
+        let accounts = fixture
+            .accounts
+            .into_iter()
+            .map(account)
+            .collect::<eyre::Result<_>>()?;
+        let portfolios = fixture
+            .portfolios
+            .into_iter()
+            .map(|(id, positions)| (id, positions.into_iter().map(position).collect()))
+            .collect();
+        let operations = fixture
+            .operations
+            .into_iter()
+            .map(|(id, ops)| {
+                let ops = ops
+                    .into_iter()
+                    .map(operation)
+                    .collect::<eyre::Result<_>>()?;
+                Ok((id, ops))
+            })
+            .collect::<eyre::Result<_>>()?;
+        let dividends = fixture
+            .dividends
+            .into_iter()
+            .map(|(figi, dividends)| (figi, dividends.into_iter().map(dividend).collect()))
+            .collect();
+        let coupons = fixture
+            .coupons
+            .into_iter()
+            .map(|(figi, coupons)| {
+                let coupons = coupons
+                    .into_iter()
+                    .map(|c| coupon(&figi, c))
+                    .collect::<eyre::Result<_>>()?;
+                Ok((figi, coupons))
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self {
+            accounts,
+            instruments: fixture.instruments,
+            portfolios,
+            operations,
+            dividends,
+            coupons,
+            last_prices: fixture.last_prices,
+            page_size: DEFAULT_PAGE_SIZE,
+        })
+    }
+
+    /// Splits operations into pages of `page_size` items
+    #[must_use]
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    fn instrument(&self, figi: &str) -> Option<&FixtureInstrument> {
+        self.instruments.iter().find(|i| i.figi == figi)
+    }
+}
+
+pub(crate) fn account(account: FixtureAccount) -> eyre::Result<Account> {
+    let account_type = match account.account_type.as_str() {
+        "unspecified" => AccountType::Unspecified,
+        name => parse_account_type(name).map_err(|e| eyre::eyre!(e))?,
+    };
+    let status = match account.status {
+        FixtureAccountStatus::New => AccountStatus::New,
+        FixtureAccountStatus::Open => AccountStatus::Open,
+        FixtureAccountStatus::Closed => AccountStatus::Closed,
+    };
+    let access_level = match account.access_level {
+        FixtureAccessLevel::Full => AccessLevel::AccountAccessLevelFullAccess,
+        FixtureAccessLevel::ReadOnly => AccessLevel::AccountAccessLevelReadOnly,
+        FixtureAccessLevel::NoAccess => AccessLevel::AccountAccessLevelNoAccess,
+        FixtureAccessLevel::Unspecified => AccessLevel::AccountAccessLevelUnspecified,
+    };
+    Ok(Account {
+        id: account.id,
+        name: account.name,
+        r#type: account_type as i32,
+        status: status as i32,
+        opened_date: account.opened.map(to_timestamp),
+        closed_date: account.closed.map(to_timestamp),
+        access_level: access_level as i32,
+    })
+}
+
+pub(crate) fn position(position: FixturePosition) -> PortfolioPosition {
+    let currency = position.currency;
+    PortfolioPosition {
+        figi: position.figi,
+        instrument_type: position.instrument_type,
+        quantity: Some(to_quotation(position.quantity)),
+        average_position_price: position.average_price.map(|p| to_money_value(p, &currency)),
+        current_price: position.current_price.map(|p| to_money_value(p, &currency)),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn operation(op: FixtureOperation) -> eyre::Result<Operation> {
+    let operation_type = enum_value(
+        "operation type",
+        "OPERATION_TYPE_",
+        &op.operation_type,
+        OperationType::from_str_name,
+    )?;
+    Ok(Operation {
+        id: op.id,
+        parent_operation_id: op.parent_operation_id,
+        currency: op.currency.clone(),
+        payment: Some(to_money_value(op.payment, &op.currency)),
+        price: Some(to_money_value(op.price, &op.currency)),
+        state: OperationState::Executed as i32,
+        quantity: op.quantity,
+        quantity_rest: op.quantity_rest,
+        figi: op.figi,
+        date: Some(to_timestamp(op.date)),
+        r#type: op.description,
+        operation_type: operation_type as i32,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn dividend(dividend: FixtureDividend) -> Dividend {
+    Dividend {
+        dividend_net: Some(to_money_value(dividend.dividend_net, &dividend.currency)),
+        payment_date: dividend.payment_date.map(to_timestamp),
+        record_date: dividend.record_date.map(to_timestamp),
+        dividend_type: dividend.dividend_type,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn coupon(figi: &str, coupon: FixtureCoupon) -> eyre::Result<Coupon> {
+    let coupon_type = if coupon.coupon_type.is_empty() {
+        CouponType::Unspecified
+    } else {
+        enum_value(
+            "coupon type",
+            "COUPON_TYPE_",
+            &coupon.coupon_type,
+            CouponType::from_str_name,
+        )?
+    };
+    Ok(Coupon {
+        figi: figi.to_string(),
+        coupon_date: coupon.coupon_date.map(to_timestamp),
+        pay_one_bond: Some(to_money_value(coupon.pay_one_bond, &coupon.currency)),
+        coupon_type: coupon_type as i32,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn currency_instrument(instrument: &FixtureInstrument) -> CurrencyInstrument {
+    CurrencyInstrument {
+        figi: instrument.figi.clone(),
+        ticker: instrument.ticker.clone(),
+        class_code: instrument.class_code.clone(),
+        name: instrument.name.clone(),
+        currency: instrument.currency.clone().unwrap_or_else(default_currency),
+        iso_currency_name: instrument.iso_currency_name.clone(),
+        country_of_risk: instrument.country_of_risk.clone(),
+        nominal: instrument
+            .nominal
+            .map(|n| to_money_value(n, &instrument.iso_currency_name)),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn instrument_short(instrument: &FixtureInstrument) -> InstrumentShort {
+    InstrumentShort {
+        figi: instrument.figi.clone(),
+        ticker: instrument.ticker.clone(),
+        class_code: instrument.class_code.clone(),
+        instrument_type: instrument.instrument_type.clone(),
+        name: instrument.name.clone(),
+        ..Default::default()
+    }
+}
+
+impl From<&Account> for FixtureAccount {
+    fn from(account: &Account) -> Self {
+        let date = |timestamp: Option<&prost_types::Timestamp>| {
+            timestamp
+                .filter(|t| t.seconds > 0)
+                .map(|t| to_datetime_utc(Some(t)))
+        };
+        let status = match account.status() {
+            AccountStatus::New => FixtureAccountStatus::New,
+            AccountStatus::Closed => FixtureAccountStatus::Closed,
+            AccountStatus::Open | AccountStatus::Unspecified => FixtureAccountStatus::Open,
+        };
+        let access_level = match account.access_level() {
+            AccessLevel::AccountAccessLevelFullAccess => FixtureAccessLevel::Full,
+            AccessLevel::AccountAccessLevelReadOnly => FixtureAccessLevel::ReadOnly,
+            AccessLevel::AccountAccessLevelNoAccess => FixtureAccessLevel::NoAccess,
+            AccessLevel::AccountAccessLevelUnspecified => FixtureAccessLevel::Unspecified,
+        };
+        Self {
+            id: account.id.clone(),
+            name: account.name.clone(),
+            account_type: account_type_name(account.r#type()).to_string(),
+            status,
+            access_level,
+            opened: date(account.opened_date.as_ref()),
+            closed: date(account.closed_date.as_ref()),
+        }
+    }
+}
+
+impl From<&PortfolioPosition> for FixturePosition {
+    fn from(position: &PortfolioPosition) -> Self {
+        Self {
+            figi: position.figi.clone(),
+            instrument_type: position.instrument_type.clone(),
+            quantity: to_decimal(position.quantity.as_ref()),
+            average_price: decimal(position.average_position_price.as_ref()),
+            current_price: decimal(position.current_price.as_ref()),
+            currency: currency_of(
+                position
+                    .current_price
+                    .as_ref()
+                    .or(position.average_position_price.as_ref()),
+            ),
+        }
+    }
+}
+
+impl From<&Operation> for FixtureOperation {
+    fn from(op: &Operation) -> Self {
+        Self {
+            id: op.id.clone(),
+            parent_operation_id: op.parent_operation_id.clone(),
+            figi: op.figi.clone(),
+            operation_type: enum_name("OPERATION_TYPE_", op.operation_type().as_str_name()),
+            description: op.r#type.clone(),
+            date: to_datetime_utc(op.date.as_ref()),
+            payment: decimal(op.payment.as_ref()).unwrap_or_default(),
+            price: decimal(op.price.as_ref()).unwrap_or_default(),
+            quantity: op.quantity,
+            quantity_rest: op.quantity_rest,
+            currency: op.currency.clone(),
+        }
+    }
+}
+
+impl From<&Dividend> for FixtureDividend {
+    fn from(dividend: &Dividend) -> Self {
+        Self {
+            dividend_net: decimal(dividend.dividend_net.as_ref()).unwrap_or_default(),
+            currency: currency_of(dividend.dividend_net.as_ref()),
+            payment_date: dividend
+                .payment_date
+                .as_ref()
+                .map(|d| to_datetime_utc(Some(d))),
+            record_date: dividend
+                .record_date
+                .as_ref()
+                .map(|d| to_datetime_utc(Some(d))),
+            dividend_type: dividend.dividend_type.clone(),
+        }
+    }
+}
+
+impl From<&Coupon> for FixtureCoupon {
+    fn from(coupon: &Coupon) -> Self {
+        Self {
+            pay_one_bond: decimal(coupon.pay_one_bond.as_ref()).unwrap_or_default(),
+            currency: currency_of(coupon.pay_one_bond.as_ref()),
+            coupon_date: coupon
+                .coupon_date
+                .as_ref()
+                .map(|d| to_datetime_utc(Some(d))),
+            coupon_type: enum_name("COUPON_TYPE_", coupon.coupon_type().as_str_name()),
+        }
+    }
+}
+
+impl From<&CurrencyInstrument> for FixtureInstrument {
+    fn from(currency: &CurrencyInstrument) -> Self {
+        Self {
+            figi: currency.figi.clone(),
+            ticker: currency.ticker.clone(),
+            name: currency.name.clone(),
+            instrument_type: InstrumentCatalog::Currencies.instrument_type().to_string(),
+            class_code: currency.class_code.clone(),
+            country_of_risk: currency.country_of_risk.clone(),
+            currency: Some(currency.currency.clone()),
+            iso_currency_name: currency.iso_currency_name.clone(),
+            nominal: decimal(currency.nominal.as_ref()),
+        }
+    }
+}
+
+impl From<&InstrumentShort> for FixtureInstrument {
+    fn from(instrument: &InstrumentShort) -> Self {
+        Self {
+            figi: instrument.figi.clone(),
+            ticker: instrument.ticker.clone(),
+            name: instrument.name.clone(),
+            instrument_type: instrument.instrument_type.clone(),
+            class_code: instrument.class_code.clone(),
+            country_of_risk: String::new(),
+            currency: None,
+            iso_currency_name: String::new(),
+            nominal: None,
+        }
+    }
+}
+
+impl InvestApi for FakeApi {
+    fn accounts(&self) -> ApiFuture<'_, Vec<Account>> {
+        Box::pin(async move { Ok(self.accounts.clone()) })
+    }
+
+    fn portfolio(&self, account_id: String) -> ApiFuture<'_, Vec<PortfolioPosition>> {
+        Box::pin(async move {
+            Ok(self
+                .portfolios
+                .get(&account_id)
+                .cloned()
+                .unwrap_or_default())
+        })
+    }
+
+    fn operations(
+        &self,
+        query: OperationsQuery,
+        cursor: Option<String>,
+    ) -> ApiFuture<'_, OperationsPage> {
+        Box::pin(async move {
+            // Cursors are offsets into the matching operations
+            let offset = match cursor {
+                Some(cursor) => cursor
+                    .parse::<usize>()
+                    .map_err(|_| eyre::eyre!("Invalid operations cursor '{cursor}'"))?,
+                None => 0,
+            };
+            let matching = self
+                .operations
+                .get(&query.account_id)
+                .into_iter()
+                .flatten()
+                .filter(|op| query.matches(op))
+                .collect::<Vec<_>>();
+            let end = matching.len().min(offset + self.page_size);
+            Ok(OperationsPage {
+                operations: matching
+                    .get(offset..end)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|&op| op.clone())
+                    .collect(),
+                next_cursor: (end < matching.len()).then(|| end.to_string()),
+            })
+        })
+    }
+
+    fn instrument_by_figi(&self, figi: String) -> ApiFuture<'_, Option<Instrument>> {
+        Box::pin(async move {
+            Ok(self.instrument(&figi).map(|i| Instrument {
+                name: i.name.clone(),
+                ticker: Ticker::new(i.ticker.clone()),
+                country: i.country_of_risk.clone(),
+            }))
+        })
+    }
+
+    fn instruments(
+        &self,
+        catalog: InstrumentCatalog,
+    ) -> ApiFuture<'_, HashMap<String, Instrument>> {
+        Box::pin(async move {
+            Ok(self
+                .instruments
+                .iter()
+                .filter(|i| i.instrument_type == catalog.instrument_type())
+                .map(|i| {
+                    (
+                        i.figi.clone(),
+                        Instrument {
+                            name: i.name.clone(),
+                            ticker: Ticker::new(i.ticker.clone()),
+                            country: i.country_of_risk.clone(),
+                        },
+                    )
+                })
+                .collect())
+        })
+    }
+
+    fn currencies(&self) -> ApiFuture<'_, Vec<CurrencyInstrument>> {
+        Box::pin(async move {
+            Ok(self
+                .instruments
+                .iter()
+                .filter(|i| i.instrument_type == InstrumentCatalog::Currencies.instrument_type())
+                .map(currency_instrument)
+                .collect())
+        })
+    }
+
+    fn find_instruments(&self, query: String) -> ApiFuture<'_, Vec<InstrumentShort>> {
+        Box::pin(async move {
+            let query = query.to_lowercase();
+            Ok(self
+                .instruments
+                .iter()
+                .filter(|i| {
+                    i.ticker.to_lowercase() == query
+                        || i.figi.to_lowercase() == query
+                        || i.name.to_lowercase().contains(&query)
+                })
+                .map(instrument_short)
+                .collect())
+        })
+    }
+
+    fn last_prices(&self, figis: Vec<String>) -> ApiFuture<'_, Vec<LastPrice>> {
+        Box::pin(async move {
+            Ok(figis
+                .into_iter()
+                .filter_map(|figi| {
+                    let price = *self.last_prices.get(&figi)?;
+                    Some(LastPrice {
+                        figi,
+                        price: Some(to_quotation(price)),
+                        ..Default::default()
+                    })
+                })
+                .collect())
+        })
+    }
+
+    fn dividends(&self, figi: String) -> ApiFuture<'_, Vec<Dividend>> {
+        Box::pin(async move { Ok(self.dividends.get(&figi).cloned().unwrap_or_default()) })
+    }
+
+    fn coupons(&self, figi: String) -> ApiFuture<'_, Vec<Coupon>> {
+        Box::pin(async move { Ok(self.coupons.get(&figi).cloned().unwrap_or_default()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_money;
+
+    fn fixture() -> Fixture {
+        serde_json::from_str(
+            r#"{
+                "accounts": [{"id": "1", "name": "Broker"}, {"id": "2", "name": "IIS", "type": "iis", "status": "closed"}],
+                "instruments": [
+                    {"figi": "BBG004730N88", "ticker": "SBER", "name": "Сбер Банк", "instrument_type": "share"},
+                    {"figi": "BBG0013HGFT4", "ticker": "USD000UTSTOM", "name": "Доллар США", "instrument_type": "currency", "iso_currency_name": "usd", "nominal": "1"}
+                ],
+                "operations": {"1": [
+                    {"id": "a", "figi": "BBG004730N88", "type": "buy", "date": "2024-01-10T10:00:00Z", "payment": "-2500.5", "price": "250.05", "quantity": 10},
+                    {"id": "b", "figi": "BBG004730N88", "type": "dividend-tax", "date": "2024-07-10T10:00:00Z", "payment": "-43"},
+                    {"id": "c", "figi": "BBG0013HGFT4", "type": "sell", "date": "2024-07-10T10:00:00Z", "payment": "900"}
+                ]},
+                "coupons": {"RU000A0JX0J2": [{"pay_one_bond": "35.4", "coupon_date": "2099-01-01T00:00:00Z", "coupon_type": "constant"}]}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn accounts_have_types_and_statuses() {
+        let api = FakeApi::new(fixture()).unwrap();
+
+        let accounts = api.accounts().await.unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].status(), AccountStatus::Open);
+        assert_eq!(
+            accounts[1].r#type(),
+            tinkoff_invest_api::tcs::AccountType::TinkoffIis
+        );
+        assert_eq!(accounts[1].status(), AccountStatus::Closed);
+    }
+
+    #[tokio::test]
+    async fn operations_are_filtered_by_figi() {
+        let api = FakeApi::new(fixture()).unwrap();
+
+        let page = api
+            .operations(OperationsQuery::new("1").with_figi("BBG004730N88"), None)
+            .await
+            .unwrap();
+        let ops = page.operations;
+
+        assert_eq!(ops.len(), 2);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(ops[0].operation_type(), OperationType::Buy);
+        assert_eq!(ops[1].operation_type(), OperationType::DividendTax);
+        assert_eq!(
+            to_money(ops[0].price.as_ref()).unwrap().value.to_string(),
+            "250.05"
+        );
+        assert!(
+            api.operations(OperationsQuery::new("2").with_figi("BBG004730N88"), None)
+                .await
+                .unwrap()
+                .operations
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn operations_are_paged() {
+        let api = FakeApi::new(fixture()).unwrap().with_page_size(2);
+        let query = OperationsQuery::new("1");
+
+        let first = api.operations(query.clone(), None).await.unwrap();
+        let second = api
+            .operations(query, first.next_cursor.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(first.operations.len(), 2);
+        assert_eq!(first.next_cursor.as_deref(), Some("2"));
+        assert_eq!(second.operations.len(), 1);
+        assert_eq!(second.operations[0].id, "c");
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn instruments_are_split_into_catalogs() {
+        let api = FakeApi::new(fixture()).unwrap();
+
+        let shares = api.instruments(InstrumentCatalog::Shares).await.unwrap();
+        let currencies = api.currencies().await.unwrap();
+        let found = api.find_instruments("sber".to_string()).await.unwrap();
+
+        assert_eq!(shares["BBG004730N88"].ticker.as_str(), "SBER");
+        assert_eq!(currencies.len(), 1);
+        assert_eq!(currencies[0].iso_currency_name, "usd");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].instrument_type, "share");
+    }
+
+    #[test]
+    fn unknown_operation_type_is_an_error() {
+        let mut fixture = fixture();
+        fixture.operations.get_mut("1").unwrap()[0].operation_type = "teleport".to_string();
+
+        let error = FakeApi::new(fixture).unwrap_err();
+
+        assert_eq!(error.to_string(), "Unknown operation type 'teleport'");
+    }
+}
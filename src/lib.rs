@@ -1,19 +1,29 @@
-use chrono::{DateTime, Utc};
-use domain::Money;
+use chrono::{DateTime, NaiveDate, Utc};
+use domain::{Lookback, Money, OperationKind, Period};
 use iso_currency::Currency;
 use prost_types::Timestamp;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
 use rust_decimal_macros::dec;
 use tinkoff_invest_api::tcs::{AccountType, MoneyValue, Quotation};
 
+pub mod api;
+pub mod cache;
 pub mod client;
 pub mod domain;
+pub mod fake;
+pub mod output;
 pub mod progress;
+pub mod record;
+pub mod retry;
 pub mod ux;
 
 pub use domain::risk;
+use output::OutputFormat;
 
 const ACCOUNT_TYPE_HELP: &str = "tinkoff (broker, default), iis, invest-box, invest-fund";
+const OUTPUT_FORMAT_HELP: &str = "text (default), json, csv";
+const OPERATION_KIND_HELP: &str =
+    "deposit, withdrawal, buy, sell, dividend, coupon, tax, fee, other";
 
 /// Parses a CLI account type name into [`AccountType`].
 ///
@@ -39,6 +49,112 @@ pub fn parse_account_type(value: &str) -> Result<AccountType, String> {
     ))
 }
 
+/// CLI name of an [`AccountType`], the inverse of [`parse_account_type`].
+#[must_use]
+pub const fn account_type_name(account_type: AccountType) -> &'static str {
+    match account_type {
+        AccountType::Tinkoff => "tinkoff",
+        AccountType::TinkoffIis => "iis",
+        AccountType::InvestBox => "invest-box",
+        AccountType::InvestFund => "invest-fund",
+        AccountType::Unspecified => "unspecified",
+    }
+}
+
+/// Parses a CLI output format name into [`OutputFormat`].
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a known output format name.
+pub fn parse_output_format(value: &str) -> Result<OutputFormat, String> {
+    if value.eq_ignore_ascii_case("text") || value.eq_ignore_ascii_case("table") {
+        return Ok(OutputFormat::Text);
+    }
+    if value.eq_ignore_ascii_case("json") {
+        return Ok(OutputFormat::Json);
+    }
+    if value.eq_ignore_ascii_case("csv") {
+        return Ok(OutputFormat::Csv);
+    }
+
+    Err(format!(
+        "unknown output format '{value}'; expected one of: {OUTPUT_FORMAT_HELP}"
+    ))
+}
+
+/// Parses an ISO 4217 currency code (case insensitive) into [`Currency`].
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a known currency code.
+pub fn parse_currency(value: &str) -> Result<Currency, String> {
+    Currency::from_code(&value.to_ascii_uppercase()).ok_or_else(|| {
+        format!("unknown currency '{value}'; expected an ISO 4217 code like rub or usd")
+    })
+}
+
+/// Parses an operation type group name, singular or plural (`fee` or `fees`), into [`OperationKind`].
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a known operation type group.
+pub fn parse_operation_kind(value: &str) -> Result<OperationKind, String> {
+    let name = value.to_ascii_lowercase();
+    let singular = match name.as_str() {
+        "taxes" => "tax",
+        other => other.strip_suffix('s').unwrap_or(other),
+    };
+    OperationKind::ALL
+        .into_iter()
+        .find(|kind| kind.name() == singular)
+        .ok_or_else(|| {
+            format!("unknown operation type '{value}'; expected one of: {OPERATION_KIND_HELP}")
+        })
+}
+
+/// Parses a `YYYY-MM-DD` date.
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a valid date.
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date '{value}'; expected YYYY-MM-DD like 2025-01-31"))
+}
+
+/// Parses a calendar year like `2025`.
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a year.
+pub fn parse_year(value: &str) -> Result<i32, String> {
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|year| Period::year(*year).is_some())
+        .ok_or_else(|| format!("invalid year '{value}'; expected a year like 2025"))
+}
+
+/// Parses a span like `30d`, `2w`, `6m` or `1y` (days, weeks, months, years).
+///
+/// # Errors
+///
+/// Returns an error if `value` is not a number followed by a unit.
+pub fn parse_lookback(value: &str) -> Result<Lookback, String> {
+    let error =
+        || format!("invalid duration '{value}'; expected a number and d, w, m or y like 30d");
+    let unit_at = value.len().checked_sub(1).ok_or_else(error)?;
+    let (count, unit) = value.split_at_checked(unit_at).ok_or_else(error)?;
+    let count = count.parse::<u32>().map_err(|_| error())?;
+    match unit.to_ascii_lowercase().as_str() {
+        "d" => Ok(Lookback::Days(count)),
+        "w" => Ok(Lookback::Weeks(count)),
+        "m" => Ok(Lookback::Months(count)),
+        "y" => Ok(Lookback::Years(count)),
+        _ => Err(error()),
+    }
+}
+
 /// Converts an `Option<&Quotation>` to `Decimal`.
 ///
 /// # Arguments
@@ -98,12 +214,44 @@ pub fn to_datetime_utc(opt_timespamp: Option<&Timestamp>) -> DateTime<Utc> {
     }
 }
 
+/// `Decimal` to `Quotation`, the inverse of [`to_decimal`]
+#[must_use]
+pub fn to_quotation(value: Decimal) -> Quotation {
+    let units = value.trunc();
+    let nano = ((value - units) * dec!(1_000_000_000)).round();
+    Quotation {
+        units: units.to_i64().unwrap_or_default(),
+        nano: nano.to_i32().unwrap_or_default(),
+    }
+}
+
+/// `Decimal` in `currency` to `MoneyValue`, the inverse of [`to_money`]
+#[must_use]
+pub fn to_money_value(value: Decimal, currency: &str) -> MoneyValue {
+    let Quotation { units, nano } = to_quotation(value);
+    MoneyValue {
+        currency: currency.to_string(),
+        units,
+        nano,
+    }
+}
+
+/// `DateTime<Utc>` to `Timestamp`, the inverse of [`to_datetime_utc`]
+#[must_use]
+pub fn to_timestamp(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use iso_currency::Currency;
     use tinkoff_invest_api::tcs::AccountType;
 
     use super::*;
+    use rstest::rstest;
 
     #[test]
     fn parse_account_type_defaults() {
@@ -115,11 +263,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn account_type_name_round_trips() {
+        for account_type in [
+            AccountType::Tinkoff,
+            AccountType::TinkoffIis,
+            AccountType::InvestBox,
+            AccountType::InvestFund,
+        ] {
+            assert_eq!(
+                parse_account_type(account_type_name(account_type)).unwrap(),
+                account_type
+            );
+        }
+    }
+
     #[test]
     fn parse_account_type_unknown() {
         assert!(parse_account_type("savings").is_err());
     }
 
+    #[test]
+    fn parse_output_format_known() {
+        assert_eq!(parse_output_format("text").unwrap(), OutputFormat::Text);
+        assert_eq!(parse_output_format("JSON").unwrap(), OutputFormat::Json);
+        assert_eq!(parse_output_format("csv").unwrap(), OutputFormat::Csv);
+        assert!(parse_output_format("xml").is_err());
+    }
+
+    #[rstest]
+    #[case("fee", OperationKind::Fee)]
+    #[case("Fees", OperationKind::Fee)]
+    #[case("taxes", OperationKind::Tax)]
+    #[case("deposits", OperationKind::Deposit)]
+    #[case("other", OperationKind::Other)]
+    fn parse_operation_kind_names(#[case] value: &str, #[case] expected: OperationKind) {
+        // Act
+        let actual = parse_operation_kind(value).unwrap();
+
+        // Assert
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_operation_kind_unknown() {
+        assert!(parse_operation_kind("transfer").is_err());
+        assert!(parse_operation_kind("s").is_err());
+    }
+
+    #[test]
+    fn parse_period_bounds() {
+        assert_eq!(
+            parse_date("2025-01-31").unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 31).unwrap()
+        );
+        assert!(parse_date("31.01.2025").is_err());
+        assert_eq!(parse_year("2025").unwrap(), 2025);
+        assert!(parse_year("twenty").is_err());
+    }
+
+    #[rstest]
+    #[case("30d", Some(Lookback::Days(30)))]
+    #[case("2w", Some(Lookback::Weeks(2)))]
+    #[case("6M", Some(Lookback::Months(6)))]
+    #[case("1y", Some(Lookback::Years(1)))]
+    #[case("y", None)]
+    #[case("10", None)]
+    #[case("", None)]
+    #[case("-1d", None)]
+    #[case("1д", None)]
+    fn parse_lookback_units(#[case] value: &str, #[case] expected: Option<Lookback>) {
+        // Act
+        let actual = parse_lookback(value).ok();
+
+        // Assert
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_currency_codes() {
+        assert_eq!(parse_currency("rub").unwrap(), Currency::RUB);
+        assert_eq!(parse_currency("USD").unwrap(), Currency::USD);
+        assert!(parse_currency("rubles").is_err());
+    }
+
     #[test]
     fn to_decimal_from_none() {
         // Arrange
@@ -282,4 +509,22 @@ mod tests {
         // Assert
         assert_eq!(r.unwrap().value.to_string(), String::from("-0.10"));
     }
+
+    #[rstest]
+    #[case("1.10")]
+    #[case("-0.10")]
+    #[case("-1234.000000001")]
+    #[case("0")]
+    fn to_money_value_round_trips(#[case] value: &str) {
+        // Arrange
+        let value: Decimal = value.parse().unwrap();
+
+        // Act
+        let r = to_money(Some(&to_money_value(value, "usd")));
+
+        // Assert
+        let m = r.unwrap();
+        assert_eq!(m.value, value);
+        assert_eq!(m.currency, Currency::USD);
+    }
 }
@@ -1,41 +1,186 @@
-use std::{collections::HashMap, env, future::Future, pin::Pin};
+use std::{collections::HashMap, env, fmt::Display, future::Future, path::PathBuf, pin::Pin};
 
-use clap::{ArgAction, ArgMatches, Command, command};
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc};
+use clap::{Arg, ArgAction, ArgMatches, Command, command, parser::ValueSource};
 use color_eyre::eyre::{self, Context, Result};
 use std::sync::Arc;
 use tokio::task::JoinSet;
 
+use iso_currency::Currency;
 use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::Serialize;
 use tinkoff::{
-    client::{AccountPortfolio, InstrumentCatalog, TinkoffInvestment},
-    domain::{History, Instrument, LoadedPaper},
-    parse_account_type,
-    progress::Progresser,
+    account_type_name,
+    api::{GrpcApi, InvestApi, OperationsQuery},
+    cache::{CatalogCache, DEFAULT_CATALOG_TTL},
+    client::{
+        AccountPortfolio, AccountSelector, InstrumentCatalog, InstrumentSource, Instruments,
+        TinkoffInvestment,
+    },
+    domain::{
+        AccountInfo, AccountList, AccountSection, AccountsView, CashFlow, CouponCalendar,
+        DividendCalendar, Figi, FxRates, History, IisReport, Journal, LdvReport, Lookback, Money,
+        MoneyBag, OperationKind, Period, Portfolio, Position, SellSimulation, SkippedPosition,
+        TaxLots, TaxReport, account_opened,
+        calendar::CombinedCalendar,
+        cashflow::{Interval, cashflow_operation_types},
+        risk::{RiskReport, TargetAllocation},
+        simulate::observed_fee_rate,
+        tax::tax_operation_types,
+    },
+    fake::FakeApi,
+    output::{self, OutputFormat, ToCsv},
+    parse_account_type, parse_currency, parse_date, parse_lookback, parse_operation_kind,
+    parse_output_format, parse_year,
+    progress::{Progress, Progresser},
+    record::{RecordingApi, ReplayApi},
     ux,
 };
-use tinkoff_invest_api::tcs::{AccountType, InstrumentShort, PortfolioPosition};
+use tinkoff_invest_api::tcs::{AccountType, InstrumentShort, Operation};
 
 struct AppConfig {
-    token: String,
-    account: AccountType,
+    api: Arc<dyn InvestApi>,
+    /// Whether instrument catalogs are kept in the user's cache directory
+    cache_catalogs: bool,
+    accounts: AccountSelector,
+    /// Whether `--account` was given on the command line rather than left at its default
+    account_type_given: bool,
+    format: OutputFormat,
+    currency: Currency,
+    refresh_catalog: bool,
+    instrument_source: InstrumentSource,
+    strict: bool,
 }
 
 impl AppConfig {
     fn from_matches(matches: &ArgMatches) -> Result<Self> {
-        let token = if let Some(t) = matches.get_one::<String>("token") {
-            t.clone()
+        let fixture = matches.get_one::<PathBuf>("fixture");
+        let replay = matches.get_one::<PathBuf>("replay");
+        let record = matches.get_one::<PathBuf>("record");
+        let api: Arc<dyn InvestApi> = if let Some(path) = fixture {
+            Arc::new(FakeApi::load(path)?)
+        } else if let Some(dir) = replay {
+            Arc::new(ReplayApi::new(dir)?)
+        } else if let Some(t) = matches.get_one::<String>("token") {
+            Arc::new(GrpcApi::new(t.clone()))
         } else {
-            env::var("TINKOFF_TOKEN_V2").wrap_err_with(|| {
+            let token = env::var("TINKOFF_TOKEN_V2").wrap_err_with(|| {
                 "API token required either from -t option or from TINKOFF_TOKEN_V2 environment variable"
-            })?
+            })?;
+            Arc::new(GrpcApi::new(token))
+        };
+        let api: Arc<dyn InvestApi> = match record {
+            Some(dir) => Arc::new(RecordingApi::new(api, dir)),
+            None => api,
         };
 
-        let account = matches
-            .get_one::<AccountType>("account")
+        let accounts = if matches.get_flag("all-accounts") {
+            AccountSelector::All
+        } else if let Some(id) = matches.get_one::<String>("account-id") {
+            AccountSelector::Id(id.clone())
+        } else {
+            AccountSelector::Type(
+                matches
+                    .get_one::<AccountType>("account")
+                    .copied()
+                    .expect("account has a default value"),
+            )
+        };
+
+        let format = matches
+            .get_one::<OutputFormat>("format")
             .copied()
-            .expect("account has a default value");
+            .expect("format has a default value");
 
-        Ok(Self { token, account })
+        let currency = matches
+            .get_one::<Currency>("currency")
+            .copied()
+            .expect("currency has a default value");
+
+        Ok(Self {
+            api,
+            // Catalogs must come from the API to be recorded and from the recording to be replayed
+            cache_catalogs: fixture.is_none() && replay.is_none() && record.is_none(),
+            accounts,
+            account_type_given: matches.value_source("account") == Some(ValueSource::CommandLine),
+            format,
+            currency,
+            refresh_catalog: matches.get_flag("refresh-catalog"),
+            instrument_source: if matches.get_flag("full-catalog") {
+                InstrumentSource::Catalogs
+            } else {
+                InstrumentSource::Positions
+            },
+            strict: matches.get_flag("strict"),
+        })
+    }
+
+    /// Creates an API client that keeps instrument catalogs in the user's cache directory
+    fn client(&self) -> TinkoffInvestment {
+        let client = TinkoffInvestment::with_api(Arc::clone(&self.api))
+            .with_instrument_source(self.instrument_source);
+        match CatalogCache::default_dir().filter(|_| self.cache_catalogs) {
+            Some(dir) => client.with_catalog_cache(
+                CatalogCache::new(dir, DEFAULT_CATALOG_TTL).refresh(self.refresh_catalog),
+            ),
+            None => client,
+        }
+    }
+
+    /// Creates a progress bar for `total` items unless the output is machine-readable
+    fn progress(&self, total: usize) -> Option<Arc<dyn Progress>> {
+        if self.format.is_interactive() {
+            Some(Arc::new(Progresser::new(total as u64)))
+        } else {
+            None
+        }
+    }
+
+    /// Prints a command result in the configured format
+    fn render<T: Display + Serialize + ToCsv>(&self, kind: &'static str, value: &T) -> Result<()> {
+        match self.format {
+            OutputFormat::Csv => print!("{}", output::to_csv(value)?),
+            _ => self.print(kind, value)?,
+        }
+        Ok(())
+    }
+
+    /// Prints a command result that has no CSV form
+    fn print<T: Display + Serialize>(&self, kind: &'static str, value: &T) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => println!("{value}"),
+            OutputFormat::Json => println!("{}", output::to_json(kind, value)?),
+            OutputFormat::Csv => eyre::bail!("CSV output is not supported by the {kind} command"),
+        }
+        Ok(())
+    }
+
+    /// Warns about positions left out of the output and fails in strict mode.
+    /// Text and JSON output list them themselves, CSV has no place for them.
+    fn check_skipped(&self, skipped: &[SkippedPosition]) -> Result<()> {
+        let skipped = skipped.iter().unique().collect_vec();
+        if self.format == OutputFormat::Csv {
+            for position in &skipped {
+                eprintln!("Skipped {position}");
+            }
+        }
+        if self.strict && !skipped.is_empty() {
+            eyre::bail!("{} position(s) failed to load", skipped.len());
+        }
+        Ok(())
+    }
+
+    /// Groups per account results into a single account or a consolidated view
+    fn accounts_view<T, F>(
+        &self,
+        accounts: Vec<AccountSection<T>>,
+        total: F,
+    ) -> Option<AccountsView<T>>
+    where
+        F: FnOnce(&[AccountSection<T>]) -> T,
+    {
+        AccountsView::new(accounts, self.accounts.is_all(), total)
     }
 }
 
@@ -60,14 +205,24 @@ const DIVIDENDS_CMD: &str = "d";
 const COUPONS_CMD: &str = "p";
 const COMBINED_CMD: &str = "j";
 const RISK_CMD: &str = "r";
+const ACCOUNTS_CMD: &str = "ac";
+const OPERATIONS_CMD: &str = "ops";
+const LDV_CMD: &str = "ldv";
+const TAX_CMD: &str = "tax";
+const IIS_CMD: &str = "iis";
+const SIMULATE_CMD: &str = "simulate";
+const SELL_CMD: &str = "sell";
+const CASHFLOW_CMD: &str = "cashflow";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    ux::clear_screen();
     let cli = build_cli().get_matches();
 
     let config = AppConfig::from_matches(&cli)?;
+    if config.format.is_interactive() {
+        ux::clear_screen();
+    }
 
     if let Some(sub) = cli.subcommand() {
         run_subcommand(&config, sub).await?;
@@ -91,84 +246,102 @@ fn run_subcommand<'a>(
         COUPONS_CMD => Box::pin(coupons(config)),
         COMBINED_CMD => Box::pin(combined(config)),
         RISK_CMD => Box::pin(risk(config)),
+        ACCOUNTS_CMD => Box::pin(accounts(config)),
+        OPERATIONS_CMD => Box::pin(operations(config, matches)),
+        LDV_CMD => Box::pin(ldv(config, matches)),
+        TAX_CMD => Box::pin(tax(config, matches)),
+        IIS_CMD => Box::pin(iis(config)),
+        SIMULATE_CMD => Box::pin(simulate(config, matches)),
+        CASHFLOW_CMD => Box::pin(cashflow(config, matches)),
         _ => Box::pin(async { Ok(()) }),
     }
 }
 
 async fn asset(config: &AppConfig, catalog: InstrumentCatalog) -> Result<()> {
-    let client = TinkoffInvestment::new(config.token.clone());
-    let (portfolio, instruments) = client
-        .get_portfolio_and_catalog(config.account, catalog)
+    let client = config.client();
+    let (mut portfolios, instruments) = client
+        .get_portfolio_and_catalog(&config.accounts, catalog)
         .await?;
 
-    let positions = portfolio
-        .positions
-        .into_iter()
-        .filter(|p| p.instrument_type == catalog.instrument_type())
-        .collect_vec();
+    for portfolio in &mut portfolios {
+        portfolio
+            .positions
+            .retain(|p| p.instrument_type == catalog.instrument_type());
+    }
 
+    // Positions of one kind only, returns of the account would not match them
     print_positions(
+        config,
         &client,
         Arc::new(instruments),
-        &positions,
-        &portfolio.account_id,
+        &portfolios,
         true,
+        false,
     )
-    .await;
-    Ok(())
+    .await
 }
 
 async fn all(config: &AppConfig, output_papers: bool) -> Result<()> {
-    let client = TinkoffInvestment::new(config.token.clone());
-    let (portfolio, instruments) = client.get_portfolio_and_instruments(config.account).await?;
+    let client = config.client();
+    let (portfolios, instruments) = client
+        .get_portfolio_and_instruments(&config.accounts)
+        .await?;
 
     print_positions(
+        config,
         &client,
         Arc::new(instruments),
-        &portfolio.positions,
-        &portfolio.account_id,
+        &portfolios,
         output_papers,
+        true,
     )
-    .await;
-    Ok(())
+    .await
 }
 
 async fn history(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
-    let client = TinkoffInvestment::new(config.token.clone());
+    let client = config.client();
     let ticker = cmd
         .get_one::<String>("TICKER")
         .ok_or_else(|| eyre::eyre!("No ticker passed"))?;
-    let (account, instruments) = tokio::join!(
-        client.get_account(config.account),
+    let period = period(cmd)?;
+    let (accounts, instruments) = tokio::join!(
+        client.get_accounts(&config.accounts),
         client.find_instruments_by_ticker(ticker.clone()),
     );
-    let account = account?;
+    let accounts = accounts?;
     let instruments = instruments?;
 
     let client = Arc::new(client);
-    let account_id = account.id.clone();
 
     let mut set = JoinSet::new();
     for instr in instruments.into_iter().filter(|i| i.ticker.eq(ticker)) {
-        let client = Arc::clone(&client);
-        let account_id = account_id.clone();
-        set.spawn(async move {
-            let ops = client
-                .get_operations_until_done(account_id, instr.figi.clone())
-                .await;
-            (instr, ops)
-        });
+        for account in &accounts {
+            let client = Arc::clone(&client);
+            let account_id = account.id.clone();
+            let instr = instr.clone();
+            set.spawn(async move {
+                // Sales of the period are matched with purchases made before it
+                let query = OperationsQuery::new(account_id.clone())
+                    .with_figi(instr.figi.clone())
+                    .with_period(Period {
+                        from: None,
+                        ..period
+                    });
+                let ops = client.get_operations(query).await;
+                (account_id, instr, ops)
+            });
+        }
     }
 
     let mut instruments_with_ops: HashMap<String, InstrumentShort> = HashMap::new();
-    let mut operations = vec![];
+    let mut operations: HashMap<String, Vec<Operation>> = HashMap::new();
     while let Some(res) = set.join_next().await {
         match res {
-            Ok((instr, Ok(ops))) if !ops.is_empty() => {
-                operations.extend(ops);
+            Ok((account_id, instr, Ok(ops))) if !ops.is_empty() => {
+                operations.entry(account_id).or_default().extend(ops);
                 instruments_with_ops.insert(instr.figi.clone(), instr);
             }
-            Ok((_, Err(e))) => eprintln!("Failed to load operations: {e:?}"),
+            Ok((_, _, Err(e))) => eprintln!("Failed to load operations: {e:#}"),
             Err(e) => eprintln!("Task panicked: {e}"),
             _ => {}
         }
@@ -188,167 +361,565 @@ async fn history(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
         return Ok(());
     };
 
-    if let Some(history) = History::new(&operations, instrument) {
-        println!("{history}");
+    let Some(total) = History::of_accounts(operations.values().map(Vec::as_slice), instrument)
+        .and_then(|h| h.within(period))
+    else {
+        return Ok(());
+    };
+    let sections = accounts
+        .iter()
+        .filter_map(|account| {
+            let history = History::new(operations.get(&account.id)?, instrument)?.within(period)?;
+            Some(AccountSection::new(&account.id, &account.name, history))
+        })
+        .collect_vec();
+
+    if let Some(view) = config.accounts_view(sections, |_| total) {
+        config.render("history", &view)?;
     }
     Ok(())
 }
 
-async fn dividends(config: &AppConfig) -> Result<()> {
-    let (client, portfolio, instruments) = Box::pin(portfolio_with_instruments(config)).await?;
-    let calendar = client
-        .calendar()
-        .dividends()
-        .fetch(&portfolio, Arc::new(instruments))
+async fn operations(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    let client = config.client();
+    let period = period(cmd)?;
+    let kinds = cmd
+        .get_many::<OperationKind>("type")
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect_vec();
+    // Other operations cannot be requested by type, so they are filtered here
+    let types = if kinds.contains(&OperationKind::Other) {
+        vec![]
+    } else {
+        kinds
+            .iter()
+            .flat_map(|k| k.operation_types().iter().copied())
+            .collect_vec()
+    };
+
+    let figis = match cmd.get_one::<String>("ticker") {
+        Some(ticker) => {
+            let figis = client
+                .find_instruments_by_ticker(ticker.clone())
+                .await?
+                .into_iter()
+                .filter(|i| i.ticker.eq(ticker))
+                .map(|i| Some(i.figi))
+                .collect_vec();
+            if figis.is_empty() {
+                eyre::bail!("No instrument with ticker {ticker} found");
+            }
+            figis
+        }
+        None => vec![None],
+    };
+
+    let accounts = client.get_accounts(&config.accounts).await?;
+    let mut operations: HashMap<String, Vec<Operation>> = HashMap::new();
+    for account in &accounts {
+        for figi in &figis {
+            let mut query = OperationsQuery::new(account.id.clone())
+                .with_period(period)
+                .with_types(types.iter().copied());
+            if let Some(figi) = figi {
+                query = query.with_figi(figi.clone());
+            }
+            let ops = client.get_operations(query).await?;
+            operations
+                .entry(account.id.clone())
+                .or_default()
+                .extend(ops);
+        }
+    }
+
+    let instruments = client
+        .get_instruments_by_figi(operations.values().flatten().map(|op| op.figi.clone()))
+        .await;
+    let journal = |ops: &[Operation]| Journal::new(ops, &instruments, period).with_kinds(&kinds);
+    let all_operations = operations.values().flatten().cloned().collect_vec();
+    let total = journal(&all_operations);
+    let sections = accounts
+        .iter()
+        .map(|account| {
+            let ops = operations.get(&account.id).map_or(&[][..], Vec::as_slice);
+            AccountSection::new(&account.id, &account.name, journal(ops))
+        })
+        .collect_vec();
+
+    render_view(
+        config,
+        "operations",
+        config.accounts_view(sections, |_| total),
+    )
+}
+
+async fn ldv(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    let client = config.client();
+    let (portfolios, instruments) = client
+        .get_portfolio_and_instruments(&config.accounts)
         .await?;
-    // Filter only dividend payments
-    let dividend_calendar = calendar
-        .upcoming
+
+    let today = cmd
+        .get_one::<NaiveDate>("on")
+        .map_or_else(Utc::now, |date| date.and_time(NaiveTime::MIN).and_utc());
+    let mut sections = Vec::with_capacity(portfolios.len());
+    for portfolio in &portfolios {
+        let report = client
+            .get_ldv_report(portfolio, &instruments, today)
+            .await?;
+        sections.push(AccountSection::new(
+            &portfolio.account_id,
+            &portfolio.account_name,
+            report,
+        ));
+    }
+
+    let skipped = skipped_of(&sections, |r| &r.skipped);
+    let view = config.accounts_view(sections, |s| {
+        LdvReport::merge(today, s.iter().map(|a| &a.data))
+    });
+    render_view(config, "ldv", view)?;
+    config.check_skipped(&skipped)
+}
+
+async fn tax(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    let client = config.client();
+    let year = cmd
+        .get_one::<i32>("year")
+        .copied()
+        .unwrap_or_else(|| Utc::now().year() - 1);
+    // Sales of the year are matched with purchases made before it
+    let until_year_end = Period {
+        from: None,
+        ..Period::year(year).unwrap_or_default()
+    };
+
+    let accounts = client.get_accounts(&config.accounts).await?;
+    let mut operations = Vec::with_capacity(accounts.len());
+    for account in &accounts {
+        let query = OperationsQuery::new(account.id.clone())
+            .with_period(until_year_end)
+            .with_types(tax_operation_types());
+        operations.push(client.get_account_operations(query).await?);
+    }
+
+    let instruments = client
+        .get_instruments_by_figi(
+            operations
+                .iter()
+                .flat_map(|ops| ops.iter().map(|(figi, _)| figi.to_string())),
+        )
+        .await;
+    let sections = accounts
         .iter()
-        .filter_map(|p| match p {
-            tinkoff::domain::calendar::CombinedPayment::Dividend(d) => Some(d.clone()),
-            tinkoff::domain::calendar::CombinedPayment::Coupon(_) => None,
+        .zip(&operations)
+        .map(|(account, ops)| {
+            AccountSection::new(
+                &account.id,
+                &account.name,
+                TaxReport::new(year, ops, &instruments),
+            )
         })
-        .collect::<Vec<_>>();
-    println!(
-        "{}",
-        tinkoff::domain::DividendCalendar {
-            upcoming: dividend_calendar
+        .collect_vec();
+
+    let view = config.accounts_view(sections, |s| {
+        TaxReport::merge(year, s.iter().map(|a| &a.data))
+    });
+    render_view(config, "tax", view)
+}
+
+async fn iis(config: &AppConfig) -> Result<()> {
+    let client = config.client();
+    // Every IIS of the user is read unless another account type is asked for explicitly
+    let selector = match &config.accounts {
+        AccountSelector::Type(account_type)
+            if config.account_type_given && *account_type != AccountType::TinkoffIis =>
+        {
+            eyre::bail!(
+                "--account {} is not an individual investment account (IIS)",
+                account_type_name(*account_type)
+            );
         }
-    );
-    Ok(())
+        AccountSelector::Type(_) => AccountSelector::All,
+        selector => selector.clone(),
+    };
+    let accounts = client
+        .get_accounts(&selector)
+        .await?
+        .into_iter()
+        .filter(|a| a.r#type() == AccountType::TinkoffIis)
+        .collect_vec();
+    if accounts.is_empty() {
+        eyre::bail!("No individual investment account (IIS) selected");
+    }
+
+    let today = Utc::now();
+    let types = [OperationKind::Deposit, OperationKind::Withdrawal]
+        .into_iter()
+        .flat_map(|kind| kind.operation_types().iter().copied())
+        .collect_vec();
+    let mut sections = Vec::with_capacity(accounts.len());
+    for account in &accounts {
+        let query = OperationsQuery::new(account.id.clone()).with_types(types.clone());
+        let operations = client.get_operations(query).await?;
+        sections.push(AccountSection::new(
+            &account.id,
+            &account.name,
+            IisReport::new(account_opened(account), &operations, today),
+        ));
+    }
+
+    // Several IIS of the user are shown together even without --all-accounts
+    let all = config.accounts.is_all() || sections.len() > 1;
+    let view = AccountsView::new(sections, all, |s| {
+        IisReport::merge(today, s.iter().map(|a| &a.data))
+    });
+    render_view(config, "iis", view)
 }
 
-async fn coupons(config: &AppConfig) -> Result<()> {
-    let (client, portfolio, instruments) = Box::pin(portfolio_with_instruments(config)).await?;
-    let calendar = client
-        .calendar()
-        .coupons()
-        .fetch(&portfolio, Arc::new(instruments))
+async fn simulate(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    match cmd.subcommand() {
+        Some((SELL_CMD, sell)) => simulate_sell(config, sell).await,
+        _ => Ok(()),
+    }
+}
+
+async fn simulate_sell(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    let ticker = cmd.get_one::<String>("TICKER").expect("ticker is required");
+    let quantity = cmd
+        .get_one::<i64>("QTY")
+        .copied()
+        .expect("quantity is required");
+    let today = cmd
+        .get_one::<NaiveDate>("on")
+        .map_or_else(Utc::now, |date| date.and_time(NaiveTime::MIN).and_utc());
+
+    let client = config.client();
+    let (portfolios, instruments) = client
+        .get_portfolio_and_instruments(&config.accounts)
         .await?;
-    // Filter only coupon payments
-    let coupon_calendar = calendar
-        .upcoming
+    let holding = portfolios
         .iter()
-        .filter_map(|p| match p {
-            tinkoff::domain::calendar::CombinedPayment::Coupon(c) => Some(c.clone()),
-            tinkoff::domain::calendar::CombinedPayment::Dividend(_) => None,
+        .flat_map(|p| p.positions.iter().map(move |position| (p, position)))
+        .filter(|(_, position)| {
+            instruments
+                .get(&position.figi)
+                .is_some_and(|i| i.ticker.as_str().eq_ignore_ascii_case(ticker))
         })
-        .collect::<Vec<_>>();
-    println!(
-        "{}",
-        tinkoff::domain::CouponCalendar {
-            upcoming: coupon_calendar
-        }
+        .collect_vec();
+    let (portfolio, position) = match holding.as_slice() {
+        [] => eyre::bail!("{ticker} is not held"),
+        [holding] => *holding,
+        _ => eyre::bail!("{ticker} is held in several accounts, pick one with --account-id"),
+    };
+    let instrument = &instruments[&position.figi];
+    let held = Position::try_from(position)?;
+    if Decimal::from(quantity) > held.quantity {
+        eyre::bail!(
+            "Cannot sell {quantity} units of {ticker}, {} held",
+            held.quantity.normalize()
+        );
+    }
+
+    let kinds = [OperationKind::Buy, OperationKind::Sell, OperationKind::Fee];
+    let query = OperationsQuery::new(portfolio.account_id.clone()).with_types(
+        kinds
+            .into_iter()
+            .flat_map(|kind| kind.operation_types().iter().copied()),
     );
-    Ok(())
-}
+    let operations = client.get_account_operations(query).await?;
+    let fee_rate = observed_fee_rate(operations.iter().flat_map(|(_, ops)| ops), held.currency);
 
-async fn combined(config: &AppConfig) -> Result<()> {
-    let (client, portfolio, instruments) = Box::pin(portfolio_with_instruments(config)).await?;
-    let calendar = client
-        .calendar()
-        .dividends()
-        .coupons()
-        .fetch(&portfolio, Arc::new(instruments))
+    let fx = client
+        .get_fx_rates(&portfolio.positions, config.currency)
         .await?;
-    println!("{calendar}");
-    Ok(())
+
+    let simulation = SellSimulation::new(
+        instrument.name.clone(),
+        instrument.ticker.to_string(),
+        position.figi.clone(),
+        &held,
+        quantity,
+        &TaxLots::new(operations.get(&position.figi)),
+        today,
+    )
+    .with_fee_rate(fee_rate);
+    let (value, skipped) = portfolio_value(portfolio, &fx);
+    let simulation = simulation.with_portfolio(value, fx).with_skipped(skipped);
+    config.print("sell_simulation", &simulation)?;
+    config.check_skipped(&simulation.skipped)
 }
 
-async fn risk(config: &AppConfig) -> Result<()> {
-    use tinkoff::domain::risk::{RebalancingAnalysis, RiskAnalysis, TargetAllocation};
+async fn cashflow(config: &AppConfig, cmd: &ArgMatches) -> Result<()> {
+    let interval = if cmd.get_flag("yearly") {
+        Interval::Year
+    } else {
+        Interval::Month
+    };
+    let client = config.client();
+    let portfolios = client.get_portfolios_until_done(&config.accounts).await?;
+    let positions = portfolios
+        .iter()
+        .flat_map(|p| p.positions.iter().cloned())
+        .collect_vec();
+    let fx = client.get_fx_rates(&positions, config.currency).await?;
 
-    let client = TinkoffInvestment::new(config.token.clone());
-    let (portfolio_data, instruments) =
-        client.get_portfolio_and_instruments(config.account).await?;
-    let instruments = Arc::new(instruments);
+    let mut sections = Vec::with_capacity(portfolios.len());
+    for portfolio in &portfolios {
+        let query = OperationsQuery::new(portfolio.account_id.clone())
+            .with_types(cashflow_operation_types());
+        let operations = client.get_operations(query).await?;
+        let (value, skipped) = portfolio_value(portfolio, &fx);
+        let flow = CashFlow::new(&operations, interval)
+            .with_current(value, fx.clone())
+            .with_skipped(skipped);
+        sections.push(AccountSection::new(
+            &portfolio.account_id,
+            &portfolio.account_name,
+            flow,
+        ));
+    }
 
-    let positions = &portfolio_data.positions;
-    let account_id = &portfolio_data.account_id;
-
-    // Build portfolio and collect all papers for risk analysis
-    let progress = Arc::new(tinkoff::progress::Progresser::new(positions.len() as u64));
-    let container = client
-        .build_portfolio(
-            instruments.clone(),
-            positions,
-            account_id,
-            false,
-            Some(progress),
-        )
-        .await;
+    let skipped = skipped_of(&sections, |f| &f.skipped);
+    let view = config.accounts_view(sections, |s| {
+        CashFlow::merge(interval, fx.clone(), s.iter().map(|a| &a.data))
+    });
+    render_view(config, "cashflow", view)?;
+    config.check_skipped(&skipped)
+}
+
+/// Current value of every position of the account in the reporting currency of `fx`
+/// and the positions left out of it for lacking a price or an exchange rate
+fn portfolio_value(portfolio: &AccountPortfolio, fx: &FxRates) -> (Money, Vec<SkippedPosition>) {
+    let mut value = MoneyBag::new();
+    let mut skipped = Vec::new();
+    for position in &portfolio.positions {
+        let reason = match Position::try_from(position) {
+            Ok(p) if fx.rate(p.currency).is_some() => {
+                value += p.current_instrument_price * p.quantity;
+                continue;
+            }
+            Ok(p) => format!("No exchange rate for {}", p.currency.code()),
+            Err(e) => format!("{e:#}"),
+        };
+        skipped.push(SkippedPosition::new(
+            Figi::new(position.figi.clone()),
+            None,
+            position.instrument_type.clone(),
+            reason,
+        ));
+    }
+    (fx.total(&value), skipped)
+}
 
-    // Collect all papers for risk analysis
-    let all_papers: Vec<LoadedPaper> = container
-        .bonds
-        .papers()
+async fn dividends(config: &AppConfig) -> Result<()> {
+    let calendars = Box::pin(calendars(config, true, false)).await?;
+    let sections = calendars
         .iter()
-        .cloned()
-        .map(LoadedPaper::Bond)
-        .chain(
-            container
-                .shares
-                .papers()
-                .iter()
-                .cloned()
-                .map(LoadedPaper::Share),
-        )
-        .chain(
-            container
-                .etfs
-                .papers()
-                .iter()
-                .cloned()
-                .map(LoadedPaper::Etf),
-        )
-        .chain(
-            container
-                .currencies
-                .papers()
-                .iter()
-                .cloned()
-                .map(LoadedPaper::Currency),
-        )
-        .chain(
-            container
-                .futures
-                .papers()
-                .iter()
-                .cloned()
-                .map(LoadedPaper::Future),
-        )
-        .collect();
+        .map(|c| c.map(CombinedCalendar::dividends))
+        .collect_vec();
+    let skipped = skipped_of(&sections, |c| &c.skipped);
+    let view = config.accounts_view(sections, |s| {
+        DividendCalendar::merge(s.iter().map(|a| &a.data))
+    });
+    render_view(config, "dividend_calendar", view)?;
+    config.check_skipped(&skipped)
+}
 
-    let risk_analysis = RiskAnalysis::analyze(&container, &all_papers);
-    println!("{risk_analysis}");
+async fn coupons(config: &AppConfig) -> Result<()> {
+    let calendars = Box::pin(calendars(config, false, true)).await?;
+    let sections = calendars
+        .iter()
+        .map(|c| c.map(CombinedCalendar::coupons))
+        .collect_vec();
+    let skipped = skipped_of(&sections, |c| &c.skipped);
+    let view = config.accounts_view(sections, |s| {
+        CouponCalendar::merge(s.iter().map(|a| &a.data))
+    });
+    render_view(config, "coupon_calendar", view)?;
+    config.check_skipped(&skipped)
+}
+
+async fn combined(config: &AppConfig) -> Result<()> {
+    let sections = Box::pin(calendars(config, true, true)).await?;
+    let skipped = skipped_of(&sections, |c| &c.skipped);
+    let view = config.accounts_view(sections, |s| {
+        CombinedCalendar::merge(s.iter().map(|a| &a.data))
+    });
+    render_view(config, "combined_calendar", view)?;
+    config.check_skipped(&skipped)
+}
+
+/// Loads calendars of every selected account
+async fn calendars(
+    config: &AppConfig,
+    dividends: bool,
+    coupons: bool,
+) -> Result<Vec<AccountSection<CombinedCalendar>>> {
+    let (client, portfolios, instruments) = Box::pin(portfolio_with_instruments(config)).await?;
+    let instruments = Arc::new(instruments);
+    let mut sections = Vec::with_capacity(portfolios.len());
+    for portfolio in &portfolios {
+        let mut builder = client.calendar();
+        if dividends {
+            builder = builder.dividends();
+        }
+        if coupons {
+            builder = builder.coupons();
+        }
+        let calendar = builder.fetch(portfolio, instruments.clone()).await?;
+        sections.push(AccountSection::new(
+            &portfolio.account_id,
+            &portfolio.account_name,
+            calendar,
+        ));
+    }
+    Ok(sections)
+}
+
+fn render_view<T: Display + Serialize + ToCsv>(
+    config: &AppConfig,
+    kind: &'static str,
+    view: Option<AccountsView<T>>,
+) -> Result<()> {
+    match view {
+        Some(view) => config.render(kind, &view),
+        None => Ok(()),
+    }
+}
+
+async fn risk(config: &AppConfig) -> Result<()> {
+    if config.format == OutputFormat::Csv {
+        eyre::bail!("CSV output is not supported by the risk command");
+    }
+
+    let client = config.client();
+    let (portfolios, instruments) = client
+        .get_portfolio_and_instruments(&config.accounts)
+        .await?;
+
+    let (sections, fx) = build_portfolios(
+        config,
+        &client,
+        Arc::new(instruments),
+        &portfolios,
+        false,
+        true,
+    )
+    .await?;
 
     // Generate rebalancing recommendations using balanced allocation as target
     let target = TargetAllocation::balanced();
-    let rebalancing = RebalancingAnalysis::analyze(&risk_analysis.asset_allocation, &target);
-    println!("{rebalancing}");
+    let reports = sections
+        .iter()
+        .map(|s| s.map(|portfolio| RiskReport::new(portfolio, &target)))
+        .collect_vec();
+    let view = config.accounts_view(reports, |_| {
+        RiskReport::new(&consolidate_portfolios(&sections, fx), &target)
+    });
 
-    Ok(())
+    if let Some(view) = view {
+        config.print("risk", &view)?;
+    }
+    config.check_skipped(&skipped_of(&sections, Portfolio::skipped))
+}
+
+async fn accounts(config: &AppConfig) -> Result<()> {
+    let client = config.client();
+    let accounts = client.get_all_accounts().await?;
+    let selected = config.accounts.select(accounts.clone())?;
+
+    let list = AccountList {
+        accounts: accounts
+            .iter()
+            .map(|a| AccountInfo::new(a, selected.accounts.iter().any(|s| s.id == a.id)))
+            .collect(),
+        fallback: selected.fallback,
+    };
+    config.render("accounts", &list)
 }
 
 async fn print_positions(
+    config: &AppConfig,
     client: &TinkoffInvestment,
-    instruments: Arc<HashMap<String, Instrument>>,
-    positions: &[PortfolioPosition],
-    account_id: &str,
+    instruments: Arc<Instruments>,
+    portfolios: &[AccountPortfolio],
     output_papers: bool,
-) {
-    let progress = Arc::new(Progresser::new(positions.len() as u64));
-    let container = client
-        .build_portfolio(
-            instruments,
-            positions,
-            account_id,
-            output_papers,
-            Some(progress),
-        )
-        .await;
-    print!("{container}");
+    account_returns: bool,
+) -> Result<()> {
+    let (sections, fx) = build_portfolios(
+        config,
+        client,
+        instruments,
+        portfolios,
+        output_papers,
+        account_returns,
+    )
+    .await?;
+    let skipped = skipped_of(&sections, Portfolio::skipped);
+    let view = config.accounts_view(sections, |s| consolidate_portfolios(s, fx));
+    render_view(config, "portfolio", view)?;
+    config.check_skipped(&skipped)
+}
+
+/// Positions left out of any of the account `sections`
+fn skipped_of<T>(
+    sections: &[AccountSection<T>],
+    skipped: impl Fn(&T) -> &[SkippedPosition],
+) -> Vec<SkippedPosition> {
+    sections
+        .iter()
+        .flat_map(|s| skipped(&s.data).iter().cloned())
+        .collect()
+}
+
+/// Builds a portfolio per account with totals in the configured currency.
+/// Returns of the accounts are computed with `account_returns` only, when `portfolios`
+/// hold every position of the accounts.
+async fn build_portfolios(
+    config: &AppConfig,
+    client: &TinkoffInvestment,
+    instruments: Arc<Instruments>,
+    portfolios: &[AccountPortfolio],
+    output_papers: bool,
+    account_returns: bool,
+) -> Result<(Vec<AccountSection<Portfolio>>, FxRates)> {
+    let positions = portfolios
+        .iter()
+        .flat_map(|p| p.positions.iter().cloned())
+        .collect_vec();
+    let fx = client.get_fx_rates(&positions, config.currency).await?;
+
+    let mut sections = Vec::with_capacity(portfolios.len());
+    for portfolio in portfolios {
+        let container = client
+            .build_portfolio(
+                instruments.clone(),
+                portfolio,
+                output_papers,
+                account_returns,
+                fx.clone(),
+                config.progress(portfolio.positions.len()),
+            )
+            .await;
+        sections.push(AccountSection::new(
+            &portfolio.account_id,
+            &portfolio.account_name,
+            container,
+        ));
+    }
+    Ok((sections, fx))
+}
+
+/// Portfolio of all accounts together, showing totals only
+fn consolidate_portfolios(accounts: &[AccountSection<Portfolio>], fx: FxRates) -> Portfolio {
+    let mut total = Portfolio::with_fx_rates(false, fx);
+    for account in accounts {
+        total.merge(&account.data);
+    }
+    total
 }
 
 fn build_cli() -> Command {
@@ -368,6 +939,78 @@ fn build_cli() -> Command {
                 .value_parser(parse_account_type)
                 .help("Account type: tinkoff (broker, default), iis, invest-box, invest-fund"),
         )
+        .arg(
+            arg!(--"account-id" <ID>)
+                .required(false)
+                .help("Use the account with the given id instead of the --account type"),
+        )
+        .arg(
+            arg!(--"all-accounts")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("account-id")
+                .help("Show every account separately and consolidated over all accounts"),
+        )
+        .arg(
+            arg!(--format <FORMAT>)
+                .required(false)
+                .global(true)
+                .default_value("text")
+                .value_parser(parse_output_format)
+                .help("Output format: text (tables, default), json (versioned document) or csv"),
+        )
+        .arg(
+            arg!(--currency <CODE>)
+                .required(false)
+                .global(true)
+                .default_value("rub")
+                .value_parser(parse_currency)
+                .help("Currency portfolio totals are reported in (rub by default, e.g. usd, eur, cny)"),
+        )
+        .arg(
+            arg!(--strict)
+                .required(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Exit with an error if any position failed to load"),
+        )
+        .arg(
+            arg!(--"full-catalog")
+                .required(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Download whole instrument catalogs instead of looking up held instruments only"),
+        )
+        .arg(
+            arg!(--"refresh-catalog")
+                .required(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Download instrument catalogs again instead of using the local cache"),
+        )
+        .arg(
+            arg!(--fixture <FILE>)
+                .required(false)
+                .global(true)
+                .hide(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Read data from a fixture file instead of the Tinkoff API"),
+        )
+        .arg(
+            arg!(--record <DIR>)
+                .required(false)
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Save every API response into the directory to replay it later"),
+        )
+        .arg(
+            arg!(--replay <DIR>)
+                .required(false)
+                .global(true)
+                .conflicts_with_all(["record", "fixture"])
+                .value_parser(value_parser!(PathBuf))
+                .help("Read API responses saved by --record instead of calling the API (no token needed)"),
+        )
         .subcommand(all_cmd())
         .subcommand(shares_cmd())
         .subcommand(bonds_cmd())
@@ -379,6 +1022,13 @@ fn build_cli() -> Command {
         .subcommand(coupons_cmd())
         .subcommand(combined_cmd())
         .subcommand(risk_cmd())
+        .subcommand(accounts_cmd())
+        .subcommand(operations_cmd())
+        .subcommand(ldv_cmd())
+        .subcommand(tax_cmd())
+        .subcommand(iis_cmd())
+        .subcommand(simulate_cmd())
+        .subcommand(cashflow_cmd())
 }
 
 fn all_cmd() -> Command {
@@ -428,6 +1078,133 @@ fn history_cmd() -> Command {
         .aliases(["history"])
         .about("Get an instrument history")
         .arg(arg!([TICKER]).help("Instrument's tiker").required(true))
+        .args(period_args())
+}
+
+fn operations_cmd() -> Command {
+    Command::new(OPERATIONS_CMD)
+        .aliases(["operations"])
+        .about("List executed operations of all instruments with subtotals per type and month")
+        .arg(
+            arg!(--type <TYPE>)
+                .required(false)
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_parser(parse_operation_kind)
+                .help("Operations of the types only: deposit, withdrawal, buy, sell, dividend, coupon, tax, fee, other"),
+        )
+        .arg(
+            arg!(--ticker <TICKER>)
+                .required(false)
+                .help("Operations with the instrument only"),
+        )
+        .args(period_args())
+}
+
+fn ldv_cmd() -> Command {
+    Command::new(LDV_CMD)
+        .about(
+            "Show which held securities are eligible for the long-term ownership tax benefit (LDV)",
+        )
+        .arg(
+            arg!(--on <DATE>)
+                .required(false)
+                .value_parser(parse_date)
+                .help("Check eligibility on the date (YYYY-MM-DD) instead of today, gains use current prices"),
+        )
+}
+
+fn tax_cmd() -> Command {
+    Command::new(TAX_CMD)
+        .about("Estimate personal income tax (NDFL) of a year from account operations")
+        .arg(
+            arg!(--year <YEAR>)
+                .required(false)
+                .value_parser(parse_year)
+                .help("Year to estimate the tax of, the previous one by default"),
+        )
+}
+
+fn iis_cmd() -> Command {
+    Command::new(IIS_CMD)
+        .about("Show contributions, tax deductions and holding period of individual investment accounts (IIS)")
+}
+
+fn cashflow_cmd() -> Command {
+    Command::new(CASHFLOW_CMD)
+        .about("Show deposits, withdrawals, income and costs per month and the lifetime gain of the account")
+        .arg(
+            arg!(--yearly)
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .help("Sum cash flows per year instead of per month"),
+        )
+}
+
+fn simulate_cmd() -> Command {
+    Command::new(SIMULATE_CMD)
+        .about("Simulate trades without placing any order")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(SELL_CMD)
+                .about("Show proceeds, fee, tax and allocation changes of selling units of a position")
+                .arg(arg!([TICKER]).help("Instrument's ticker").required(true))
+                .arg(
+                    arg!([QTY])
+                        .help("Units to sell")
+                        .required(true)
+                        .value_parser(value_parser!(i64).range(1..)),
+                )
+                .arg(
+                    arg!(--on <DATE>)
+                        .required(false)
+                        .value_parser(parse_date)
+                        .help("Check LDV eligibility and tax rates on the date (YYYY-MM-DD) instead of today"),
+                ),
+        )
+}
+
+/// Options limiting operations to a period, read by [`period`]
+fn period_args() -> [Arg; 4] {
+    [
+        arg!(--from <DATE>)
+            .required(false)
+            .value_parser(parse_date)
+            .help("Operations made on or after the date (YYYY-MM-DD)"),
+        arg!(--to <DATE>)
+            .required(false)
+            .value_parser(parse_date)
+            .help("Operations made on or before the date (YYYY-MM-DD)"),
+        arg!(--year <YEAR>)
+            .required(false)
+            .value_parser(parse_year)
+            .conflicts_with_all(["from", "to"])
+            .help("Operations made in the calendar year"),
+        arg!(--last <DURATION>)
+            .required(false)
+            .value_parser(parse_lookback)
+            .conflicts_with_all(["from", "to", "year"])
+            .help("Operations made within the duration until now, e.g. 30d, 2w, 6m or 1y"),
+    ]
+}
+
+/// Period selected by [`period_args`], all time if none of them is set
+fn period(cmd: &ArgMatches) -> Result<Period> {
+    if let Some(year) = cmd.get_one::<i32>("year") {
+        // The year was validated when parsed
+        return Ok(Period::year(*year).unwrap_or_default());
+    }
+    if let Some(lookback) = cmd.get_one::<Lookback>("last") {
+        return Ok(Period::last(*lookback, Utc::now()));
+    }
+    let from = cmd.get_one::<NaiveDate>("from").copied();
+    let to = cmd.get_one::<NaiveDate>("to").copied();
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        eyre::bail!("--from {from} is later than --to {to}");
+    }
+    Ok(Period::between(from, to))
 }
 
 fn dividends_cmd() -> Command {
@@ -454,14 +1231,18 @@ fn risk_cmd() -> Command {
         .about("Analyze portfolio risk metrics")
 }
 
+fn accounts_cmd() -> Command {
+    Command::new(ACCOUNTS_CMD)
+        .aliases(["accounts"])
+        .about("List accounts available with the token")
+}
+
 async fn portfolio_with_instruments(
     config: &AppConfig,
-) -> Result<(
-    TinkoffInvestment,
-    AccountPortfolio,
-    HashMap<String, Instrument>,
-)> {
-    let client = TinkoffInvestment::new(config.token.clone());
-    let (portfolio, instruments) = client.get_portfolio_and_instruments(config.account).await?;
-    Ok((client, portfolio, instruments))
+) -> Result<(TinkoffInvestment, Vec<AccountPortfolio>, Instruments)> {
+    let client = config.client();
+    let (portfolios, instruments) = client
+        .get_portfolio_and_instruments(&config.accounts)
+        .await?;
+    Ok((client, portfolios, instruments))
 }
@@ -0,0 +1,121 @@
+//! Machine-readable rendering of command results.
+
+use std::io;
+
+use serde::Serialize;
+
+/// Version of the JSON document layout.
+/// Must be incremented on any incompatible change of field names or structure.
+pub const JSON_FORMAT_VERSION: u32 = 2;
+
+/// How command results are rendered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable tables
+    #[default]
+    Text,
+    /// Versioned JSON document
+    Json,
+    /// Comma separated values, one row per item
+    Csv,
+}
+
+impl OutputFormat {
+    /// Whether the output is meant for a terminal, i.e. screen clearing
+    /// and progress bars are allowed
+    #[must_use]
+    pub const fn is_interactive(self) -> bool {
+        matches!(self, Self::Text)
+    }
+}
+
+/// Data that can be exported as CSV rows.
+///
+/// Numbers are written with a plain `.` decimal separator and without
+/// thousands separators so that spreadsheets can parse them.
+pub trait ToCsv {
+    /// Writes a header and one record per item into `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be serialized or written.
+    fn write_csv<W: io::Write>(&self, writer: &mut csv::Writer<W>) -> csv::Result<()> {
+        self.write_account_csv(None, writer)
+    }
+
+    /// Same as [`ToCsv::write_csv`] but every record starts with
+    /// an `account_id` column if `account_id` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be serialized or written.
+    fn write_account_csv<W: io::Write>(
+        &self,
+        account_id: Option<&str>,
+        writer: &mut csv::Writer<W>,
+    ) -> csv::Result<()>;
+}
+
+/// Envelope every JSON document is wrapped into
+#[derive(Serialize)]
+struct Document<'a, T: Serialize> {
+    version: u32,
+    kind: &'static str,
+    data: &'a T,
+}
+
+/// Serializes `data` into a versioned JSON document of the given `kind`.
+///
+/// # Errors
+///
+/// Returns an error if `data` cannot be serialized.
+pub fn to_json<T: Serialize>(kind: &'static str, data: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&Document {
+        version: JSON_FORMAT_VERSION,
+        kind,
+        data,
+    })
+}
+
+/// Renders `data` as CSV text.
+///
+/// # Errors
+///
+/// Returns an error if `data` cannot be serialized.
+pub fn to_csv<T: ToCsv>(data: &T) -> csv::Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    data.write_csv(&mut writer)?;
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use iso_currency::Currency;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::domain::Money;
+
+    #[test]
+    fn to_json_wraps_data_into_versioned_document() {
+        let money = Money::from_value(dec!(1234.5600), Currency::USD);
+
+        let json = to_json("money", &money).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["version"], JSON_FORMAT_VERSION);
+        assert_eq!(value["kind"], "money");
+        assert_eq!(value["data"]["value"], "1234.5600");
+        assert_eq!(value["data"]["currency"], "USD");
+    }
+
+    #[test]
+    fn only_text_is_interactive() {
+        assert!(OutputFormat::Text.is_interactive());
+        assert!(!OutputFormat::Json.is_interactive());
+        assert!(!OutputFormat::Csv.is_interactive());
+    }
+}
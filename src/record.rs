@@ -0,0 +1,528 @@
+//! Recording broker API responses and replaying them offline.
+//!
+//! [`RecordingApi`] passes requests on to another [`InvestApi`] and saves every
+//! successful response into a directory, one JSON file per request.
+//! [`ReplayApi`] answers the same requests from such a directory, so a run
+//! can be reproduced without a token or network access.
+
+use chrono::SecondsFormat;
+use color_eyre::eyre::{self, Context};
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tinkoff_invest_api::tcs::{
+    Account, Coupon, Currency as CurrencyInstrument, Dividend, InstrumentShort, LastPrice,
+    PortfolioPosition,
+};
+use tonic::{Code, Status};
+
+use crate::{
+    api::{ApiFuture, InvestApi, OperationsPage, OperationsQuery},
+    cache::write_atomically,
+    client::InstrumentCatalog,
+    domain::Instrument,
+    fake::{
+        self, FixtureAccount, FixtureCoupon, FixtureDividend, FixtureInstrument, FixtureOperation,
+        FixturePosition,
+    },
+    retry::ApiError,
+    to_decimal, to_quotation,
+};
+
+/// Request to the broker API, identifying the file its response is kept in
+enum Request<'a> {
+    Accounts,
+    Portfolio(&'a str),
+    Operations(&'a OperationsQuery, Option<&'a str>),
+    InstrumentByFigi(&'a str),
+    Instruments(InstrumentCatalog),
+    Currencies,
+    FindInstruments(&'a str),
+    LastPrices(&'a [String]),
+    Dividends(&'a str),
+    Coupons(&'a str),
+}
+
+impl Request<'_> {
+    /// Path of the response file relative to the recording directory
+    fn path(&self) -> PathBuf {
+        match self {
+            Self::Accounts => PathBuf::from("accounts.json"),
+            Self::Portfolio(account_id) => Path::new("portfolio").join(file_name(account_id)),
+            Self::Operations(query, cursor) => Path::new("operations")
+                .join(escape(&query.account_id))
+                .join(escape(query.figi.as_deref().unwrap_or("all")))
+                .join(file_name(&operations_page_key(query, *cursor))),
+            Self::InstrumentByFigi(figi) => Path::new("instrument").join(file_name(figi)),
+            Self::Instruments(catalog) => Path::new("instruments").join(file_name(catalog.name())),
+            Self::Currencies => PathBuf::from("currencies.json"),
+            Self::FindInstruments(query) => Path::new("find").join(file_name(query)),
+            Self::LastPrices(figis) => {
+                let mut figis = figis.to_vec();
+                figis.sort_unstable();
+                Path::new("last-prices").join(file_name(&figis.join("+")))
+            }
+            Self::Dividends(figi) => Path::new("dividends").join(file_name(figi)),
+            Self::Coupons(figi) => Path::new("coupons").join(file_name(figi)),
+        }
+    }
+}
+
+/// Date bounds, type codes and cursor of an operations page
+fn operations_page_key(query: &OperationsQuery, cursor: Option<&str>) -> String {
+    let mut parts = Vec::new();
+    if let Some(from) = query.from {
+        parts.push(format!(
+            "from-{}",
+            from.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+    if let Some(to) = query.to {
+        parts.push(format!(
+            "to-{}",
+            to.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+    if !query.types.is_empty() {
+        // Numeric codes keep file names short when many types are requested
+        let types = query
+            .types
+            .iter()
+            .map(|&t| t as i32)
+            .sorted_unstable()
+            .join("-");
+        parts.push(format!("types-{types}"));
+    }
+    parts.push(cursor.map_or_else(|| "first".to_string(), |c| format!("cursor-{c}")));
+    parts.join("_")
+}
+
+/// Recorded page of operations
+#[derive(Serialize, Deserialize)]
+struct RecordedOperations {
+    operations: Vec<FixtureOperation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// Keeps ASCII letters, digits, `-` and `_`, other bytes are written as `%XX`
+fn escape(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            escaped.push(char::from(byte));
+        } else {
+            escaped.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    escaped
+}
+
+fn file_name(key: &str) -> String {
+    format!("{}.json", escape(key))
+}
+
+/// Directory with recorded responses
+struct Recording {
+    dir: PathBuf,
+}
+
+impl Recording {
+    fn store<T: Serialize>(&self, request: &Request, response: &T) -> color_eyre::Result<()> {
+        let content = serde_json::to_vec_pretty(response)?;
+        write_atomically(&self.dir.join(request.path()), &content)
+    }
+
+    fn load<T: DeserializeOwned>(&self, request: &Request) -> color_eyre::Result<T> {
+        let path = self.dir.join(request.path());
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // Not found is permanent, so that the request is not retried
+                let status = Status::new(Code::NotFound, format!("{} is missing", path.display()));
+                return Err(ApiError::new("No recorded response", &status).into());
+            }
+            Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_slice(&content)
+            .wrap_err_with(|| format!("Invalid recorded response {}", path.display()))
+    }
+}
+
+/// Broker API saving responses of another API
+pub struct RecordingApi {
+    inner: Arc<dyn InvestApi>,
+    recording: Recording,
+}
+
+impl RecordingApi {
+    #[must_use]
+    pub fn new(inner: Arc<dyn InvestApi>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            recording: Recording { dir: dir.into() },
+        }
+    }
+
+    /// Saves `response`. A failed write is reported but does not fail the request.
+    fn record<T: Serialize>(&self, request: &Request, response: &T) {
+        if let Err(e) = self.recording.store(request, response) {
+            eprintln!("Failed to record {}: {e:#}", request.path().display());
+        }
+    }
+}
+
+impl InvestApi for RecordingApi {
+    fn accounts(&self) -> ApiFuture<'_, Vec<Account>> {
+        Box::pin(async move {
+            let accounts = self.inner.accounts().await?;
+            let recorded: Vec<_> = accounts.iter().map(FixtureAccount::from).collect();
+            self.record(&Request::Accounts, &recorded);
+            Ok(accounts)
+        })
+    }
+
+    fn portfolio(&self, account_id: String) -> ApiFuture<'_, Vec<PortfolioPosition>> {
+        Box::pin(async move {
+            let positions = self.inner.portfolio(account_id.clone()).await?;
+            let recorded: Vec<_> = positions.iter().map(FixturePosition::from).collect();
+            self.record(&Request::Portfolio(&account_id), &recorded);
+            Ok(positions)
+        })
+    }
+
+    fn operations(
+        &self,
+        query: OperationsQuery,
+        cursor: Option<String>,
+    ) -> ApiFuture<'_, OperationsPage> {
+        Box::pin(async move {
+            let page = self.inner.operations(query.clone(), cursor.clone()).await?;
+            let recorded = RecordedOperations {
+                operations: page.operations.iter().map(FixtureOperation::from).collect(),
+                next_cursor: page.next_cursor.clone(),
+            };
+            self.record(&Request::Operations(&query, cursor.as_deref()), &recorded);
+            Ok(page)
+        })
+    }
+
+    fn instrument_by_figi(&self, figi: String) -> ApiFuture<'_, Option<Instrument>> {
+        Box::pin(async move {
+            let instrument = self.inner.instrument_by_figi(figi.clone()).await?;
+            self.record(&Request::InstrumentByFigi(&figi), &instrument);
+            Ok(instrument)
+        })
+    }
+
+    fn instruments(
+        &self,
+        catalog: InstrumentCatalog,
+    ) -> ApiFuture<'_, HashMap<String, Instrument>> {
+        Box::pin(async move {
+            let instruments = self.inner.instruments(catalog).await?;
+            let recorded: BTreeMap<_, _> = instruments.iter().collect();
+            self.record(&Request::Instruments(catalog), &recorded);
+            Ok(instruments)
+        })
+    }
+
+    fn currencies(&self) -> ApiFuture<'_, Vec<CurrencyInstrument>> {
+        Box::pin(async move {
+            let currencies = self.inner.currencies().await?;
+            let recorded: Vec<_> = currencies.iter().map(FixtureInstrument::from).collect();
+            self.record(&Request::Currencies, &recorded);
+            Ok(currencies)
+        })
+    }
+
+    fn find_instruments(&self, query: String) -> ApiFuture<'_, Vec<InstrumentShort>> {
+        Box::pin(async move {
+            let instruments = self.inner.find_instruments(query.clone()).await?;
+            let recorded: Vec<_> = instruments.iter().map(FixtureInstrument::from).collect();
+            self.record(&Request::FindInstruments(&query), &recorded);
+            Ok(instruments)
+        })
+    }
+
+    fn last_prices(&self, figis: Vec<String>) -> ApiFuture<'_, Vec<LastPrice>> {
+        Box::pin(async move {
+            let prices = self.inner.last_prices(figis.clone()).await?;
+            let recorded: BTreeMap<_, _> = prices
+                .iter()
+                .map(|p| (&p.figi, to_decimal(p.price.as_ref())))
+                .collect();
+            self.record(&Request::LastPrices(&figis), &recorded);
+            Ok(prices)
+        })
+    }
+
+    fn dividends(&self, figi: String) -> ApiFuture<'_, Vec<Dividend>> {
+        Box::pin(async move {
+            let dividends = self.inner.dividends(figi.clone()).await?;
+            let recorded: Vec<_> = dividends.iter().map(FixtureDividend::from).collect();
+            self.record(&Request::Dividends(&figi), &recorded);
+            Ok(dividends)
+        })
+    }
+
+    fn coupons(&self, figi: String) -> ApiFuture<'_, Vec<Coupon>> {
+        Box::pin(async move {
+            let coupons = self.inner.coupons(figi.clone()).await?;
+            let recorded: Vec<_> = coupons.iter().map(FixtureCoupon::from).collect();
+            self.record(&Request::Coupons(&figi), &recorded);
+            Ok(coupons)
+        })
+    }
+}
+
+/// Broker API answering from responses saved by [`RecordingApi`].
+///
+/// Requests that were not recorded fail as not found.
+pub struct ReplayApi {
+    recording: Recording,
+}
+
+impl ReplayApi {
+    /// Replays responses recorded into `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` is not a directory.
+    pub fn new(dir: impl Into<PathBuf>) -> color_eyre::Result<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            eyre::bail!("Replay directory {} not found", dir.display());
+        }
+        Ok(Self {
+            recording: Recording { dir },
+        })
+    }
+}
+
+impl InvestApi for ReplayApi {
+    fn accounts(&self) -> ApiFuture<'_, Vec<Account>> {
+        Box::pin(async move {
+            let accounts: Vec<FixtureAccount> = self.recording.load(&Request::Accounts)?;
+            accounts.into_iter().map(fake::account).collect()
+        })
+    }
+
+    fn portfolio(&self, account_id: String) -> ApiFuture<'_, Vec<PortfolioPosition>> {
+        Box::pin(async move {
+            let positions: Vec<FixturePosition> =
+                self.recording.load(&Request::Portfolio(&account_id))?;
+            Ok(positions.into_iter().map(fake::position).collect())
+        })
+    }
+
+    fn operations(
+        &self,
+        query: OperationsQuery,
+        cursor: Option<String>,
+    ) -> ApiFuture<'_, OperationsPage> {
+        Box::pin(async move {
+            let recorded: RecordedOperations = self
+                .recording
+                .load(&Request::Operations(&query, cursor.as_deref()))?;
+            Ok(OperationsPage {
+                operations: recorded
+                    .operations
+                    .into_iter()
+                    .map(fake::operation)
+                    .collect::<eyre::Result<_>>()?,
+                next_cursor: recorded.next_cursor,
+            })
+        })
+    }
+
+    fn instrument_by_figi(&self, figi: String) -> ApiFuture<'_, Option<Instrument>> {
+        Box::pin(async move { self.recording.load(&Request::InstrumentByFigi(&figi)) })
+    }
+
+    fn instruments(
+        &self,
+        catalog: InstrumentCatalog,
+    ) -> ApiFuture<'_, HashMap<String, Instrument>> {
+        Box::pin(async move { self.recording.load(&Request::Instruments(catalog)) })
+    }
+
+    fn currencies(&self) -> ApiFuture<'_, Vec<CurrencyInstrument>> {
+        Box::pin(async move {
+            let currencies: Vec<FixtureInstrument> = self.recording.load(&Request::Currencies)?;
+            Ok(currencies.iter().map(fake::currency_instrument).collect())
+        })
+    }
+
+    fn find_instruments(&self, query: String) -> ApiFuture<'_, Vec<InstrumentShort>> {
+        Box::pin(async move {
+            let instruments: Vec<FixtureInstrument> =
+                self.recording.load(&Request::FindInstruments(&query))?;
+            Ok(instruments.iter().map(fake::instrument_short).collect())
+        })
+    }
+
+    fn last_prices(&self, figis: Vec<String>) -> ApiFuture<'_, Vec<LastPrice>> {
+        Box::pin(async move {
+            let prices: BTreeMap<String, Decimal> =
+                self.recording.load(&Request::LastPrices(&figis))?;
+            Ok(prices
+                .into_iter()
+                .map(|(figi, price)| LastPrice {
+                    figi,
+                    price: Some(to_quotation(price)),
+                    ..Default::default()
+                })
+                .collect())
+        })
+    }
+
+    fn dividends(&self, figi: String) -> ApiFuture<'_, Vec<Dividend>> {
+        Box::pin(async move {
+            let dividends: Vec<FixtureDividend> =
+                self.recording.load(&Request::Dividends(&figi))?;
+            Ok(dividends.into_iter().map(fake::dividend).collect())
+        })
+    }
+
+    fn coupons(&self, figi: String) -> ApiFuture<'_, Vec<Coupon>> {
+        Box::pin(async move {
+            let coupons: Vec<FixtureCoupon> = self.recording.load(&Request::Coupons(&figi))?;
+            coupons
+                .into_iter()
+                .map(|c| fake::coupon(&figi, c))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{FakeApi, Fixture};
+    use crate::retry::ApiError;
+    use tinkoff_invest_api::tcs::OperationType;
+
+    fn recording_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tinkoff-record-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn fake() -> Arc<dyn InvestApi> {
+        let fixture: Fixture = serde_json::from_str(
+            r#"{
+                "accounts": [{"id": "1", "name": "Broker", "opened": "2020-03-02T00:00:00Z"}],
+                "instruments": [
+                    {"figi": "BBG004730N88", "ticker": "SBER", "name": "Сбер Банк", "instrument_type": "share"},
+                    {"figi": "BBG0013HGFT4", "ticker": "USD000UTSTOM", "name": "Доллар США", "instrument_type": "currency", "iso_currency_name": "usd", "nominal": "1"}
+                ],
+                "portfolios": {"1": [{"figi": "BBG004730N88", "instrument_type": "share", "quantity": "10", "average_price": "250.5", "current_price": "280"}]},
+                "operations": {"1": [
+                    {"id": "a", "figi": "BBG004730N88", "type": "buy", "date": "2024-01-10T10:00:00Z", "payment": "-2505", "price": "250.5", "quantity": 10}
+                ]},
+                "dividends": {"BBG004730N88": [{"dividend_net": "33.3", "payment_date": "2099-07-20T00:00:00Z", "record_date": "2099-07-11T00:00:00Z"}]},
+                "last_prices": {"BBG0013HGFT4": "95.25"}
+            }"#,
+        )
+        .unwrap();
+        Arc::new(FakeApi::new(fixture).unwrap())
+    }
+
+    #[test]
+    fn escape_keeps_file_names_safe() {
+        assert_eq!(escape("BBG004730N88"), "BBG004730N88");
+        assert_eq!(escape("../a b"), "%2E%2E%2Fa%20b");
+        assert_eq!(escape("Сбер"), "%D0%A1%D0%B1%D0%B5%D1%80");
+    }
+
+    #[tokio::test]
+    async fn replay_returns_recorded_responses() {
+        let dir = recording_dir("round-trip");
+        let recorder = RecordingApi::new(fake(), &dir);
+        let accounts = recorder.accounts().await.unwrap();
+        let positions = recorder.portfolio("1".to_string()).await.unwrap();
+        let query = OperationsQuery::new("1").with_figi("BBG004730N88");
+        let operations = recorder.operations(query.clone(), None).await.unwrap();
+        let dividends = recorder
+            .dividends("BBG004730N88".to_string())
+            .await
+            .unwrap();
+        let currencies = recorder.currencies().await.unwrap();
+        let prices = recorder
+            .last_prices(vec!["BBG0013HGFT4".to_string()])
+            .await
+            .unwrap();
+        let found = recorder.find_instruments("Сбер".to_string()).await.unwrap();
+
+        let replay = ReplayApi::new(&dir).unwrap();
+
+        assert_eq!(replay.accounts().await.unwrap(), accounts);
+        assert_eq!(replay.portfolio("1".to_string()).await.unwrap(), positions);
+        assert_eq!(replay.operations(query, None).await.unwrap(), operations);
+        assert_eq!(
+            replay.dividends("BBG004730N88".to_string()).await.unwrap(),
+            dividends
+        );
+        assert_eq!(replay.currencies().await.unwrap(), currencies);
+        assert_eq!(
+            replay
+                .last_prices(vec!["BBG0013HGFT4".to_string()])
+                .await
+                .unwrap(),
+            prices
+        );
+        assert_eq!(
+            replay.find_instruments("Сбер".to_string()).await.unwrap(),
+            found
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn operations_pages_have_own_files() {
+        let query = OperationsQuery::new("1");
+        let filtered = OperationsQuery::new("1")
+            .with_figi("BBG004730N88")
+            .with_from("2024-01-01T00:00:00Z".parse().unwrap())
+            .with_types([OperationType::Dividend, OperationType::Buy]);
+
+        assert_eq!(
+            Request::Operations(&query, None).path(),
+            Path::new("operations/1/all/first.json")
+        );
+        assert_eq!(
+            Request::Operations(&filtered, Some("42")).path(),
+            Path::new(
+                "operations/1/BBG004730N88/from-2024-01-01T00%3A00%3A00Z_types-15-21_cursor-42.json"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_response_is_not_found() {
+        let dir = recording_dir("missing");
+        fs::create_dir_all(&dir).unwrap();
+        let replay = ReplayApi::new(&dir).unwrap();
+
+        let error = replay
+            .coupons("BBG00R0SHYW6".to_string())
+            .await
+            .unwrap_err();
+
+        let api = error.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(api.code(), Code::NotFound);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replay_needs_a_directory() {
+        assert!(ReplayApi::new(recording_dir("absent")).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Retrying API calls.
+//!
+//! Failed calls are classified by their gRPC status: transient failures
+//! (server unavailable, rate limit exceeded) are retried with exponential
+//! backoff and jitter, while permanent ones (authentication, missing
+//! instruments, invalid requests) and errors without a gRPC status
+//! (replayed or fake responses, file I/O) are reported right away.
+
+use color_eyre::eyre;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use tinkoff_invest_api::TIError;
+use tokio::time::{Duration, sleep};
+use tonic::{Code, Status};
+
+/// Metadata key with the number of seconds until the API rate limit is reset
+const RATE_LIMIT_RESET: &str = "x-ratelimit-reset";
+
+/// Failed API call with its gRPC status
+#[derive(Debug)]
+pub struct ApiError {
+    context: String,
+    code: Code,
+    message: String,
+    /// Time until the rate limit is reset, if the server reported it
+    rate_limit_reset: Option<Duration>,
+}
+
+impl ApiError {
+    #[must_use]
+    pub fn new(context: impl Into<String>, status: &Status) -> Self {
+        let rate_limit_reset = status
+            .metadata()
+            .get(RATE_LIMIT_RESET)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        Self {
+            context: context.into(),
+            code: status.code(),
+            message: status.message().to_owned(),
+            rate_limit_reset,
+        }
+    }
+
+    #[must_use]
+    pub const fn code(&self) -> Code {
+        self.code
+    }
+
+    #[must_use]
+    pub const fn rate_limit_reset(&self) -> Option<Duration> {
+        self.rate_limit_reset
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.code.description())?;
+        if !self.message.is_empty() {
+            write!(f, " ({})", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Converts an error of the API crate into a report with `context`.
+/// Connection failures are reported as the server being unavailable so that they are retried.
+pub fn api_error(context: &str, error: TIError) -> eyre::Report {
+    match error {
+        TIError::TransportError(e) => {
+            ApiError::new(context, &Status::unavailable(e.to_string())).into()
+        }
+        TIError::StatusError(status) => ApiError::new(context, &status).into(),
+    }
+}
+
+/// What to do after a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// Try again, waiting at least the given time if set
+    Retry(Option<Duration>),
+    /// The error is permanent
+    Fail,
+}
+
+fn classify(error: &eyre::Report) -> Outcome {
+    let Some(api) = error.downcast_ref::<ApiError>() else {
+        // Not a call to the server, trying again gives the same result
+        return Outcome::Fail;
+    };
+    match api.code {
+        Code::ResourceExhausted => Outcome::Retry(api.rate_limit_reset),
+        Code::Unavailable | Code::DeadlineExceeded | Code::Aborted => Outcome::Retry(None),
+        _ => Outcome::Fail,
+    }
+}
+
+/// How failed API calls are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Total number of attempts including the first one (at least one)
+    #[must_use]
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the second attempt, doubled for every next one
+    #[must_use]
+    pub const fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Upper bound of the backoff delay. Rate limit resets reported by the server may exceed it.
+    #[must_use]
+    pub const fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Runs `f` until it succeeds, fails permanently or attempts are exhausted.
+    ///
+    /// # Errors
+    ///
+    /// Returns the last error of `f`.
+    pub async fn run<T, F, Fut>(&self, f: F) -> color_eyre::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = color_eyre::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let error = match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) => e,
+            };
+            let Outcome::Retry(at_least) = classify(&error) else {
+                return Err(error);
+            };
+            if attempt >= self.attempts {
+                return Err(error.wrap_err(format!("Operation failed after {attempt} attempts")));
+            }
+            sleep(self.delay(attempt, at_least)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Delay after the failed `attempt` (counted from one): exponential backoff
+    /// with "equal jitter", or the rate limit reset if it is longer
+    fn delay(&self, attempt: u32, at_least: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        let half = backoff / 2;
+        let jittered = half + half.mul_f64(random_fraction());
+        at_least.map_or(jittered, |reset| reset.max(jittered))
+    }
+}
+
+/// Random number in `[0, 1)` good enough to spread retries of parallel requests
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    #[allow(clippy::cast_precision_loss)]
+    let value = (hasher.finish() >> 11) as f64;
+    value / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tonic::metadata::MetadataValue;
+
+    fn api(code: Code) -> eyre::Report {
+        ApiError::new("Failed to get portfolio", &Status::new(code, "boom")).into()
+    }
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy::default()
+            .base_delay(Duration::from_millis(1))
+            .max_delay(Duration::from_millis(2))
+    }
+
+    #[rstest]
+    #[case::unavailable(Code::Unavailable, Outcome::Retry(None))]
+    #[case::rate_limit(Code::ResourceExhausted, Outcome::Retry(None))]
+    #[case::deadline(Code::DeadlineExceeded, Outcome::Retry(None))]
+    #[case::unauthenticated(Code::Unauthenticated, Outcome::Fail)]
+    #[case::permission(Code::PermissionDenied, Outcome::Fail)]
+    #[case::not_found(Code::NotFound, Outcome::Fail)]
+    #[case::invalid(Code::InvalidArgument, Outcome::Fail)]
+    fn classify_status_codes(#[case] code: Code, #[case] expected: Outcome) {
+        assert_eq!(classify(&api(code)), expected);
+    }
+
+    #[test]
+    fn classify_other_errors_as_permanent() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+
+        assert_eq!(classify(&eyre::eyre!("invalid fixture")), Outcome::Fail);
+        assert_eq!(classify(&eyre::Report::new(io)), Outcome::Fail);
+    }
+
+    #[test]
+    fn rate_limit_reset_is_read_from_metadata() {
+        let mut status = Status::new(Code::ResourceExhausted, "");
+        status
+            .metadata_mut()
+            .insert(RATE_LIMIT_RESET, MetadataValue::from_static("7"));
+
+        let error = ApiError::new("Failed to get operations", &status);
+
+        assert_eq!(error.rate_limit_reset(), Some(Duration::from_secs(7)));
+        assert_eq!(
+            classify(&error.into()),
+            Outcome::Retry(Some(Duration::from_secs(7)))
+        );
+    }
+
+    #[test]
+    fn display_has_no_debug_dump() {
+        let error = ApiError::new(
+            "Failed to get portfolio",
+            &Status::new(Code::NotFound, "boom"),
+        );
+
+        assert_eq!(
+            error.to_string(),
+            "Failed to get portfolio: Some requested entity was not found (boom)"
+        );
+    }
+
+    #[rstest]
+    #[case(1, 50, 100)]
+    #[case(2, 100, 200)]
+    #[case(3, 200, 400)]
+    #[case(10, 5000, 10000)]
+    fn delay_is_jittered_exponential_backoff(
+        #[case] attempt: u32,
+        #[case] min: u64,
+        #[case] max: u64,
+    ) {
+        let delay = RetryPolicy::default().delay(attempt, None);
+
+        assert!(delay >= Duration::from_millis(min), "{delay:?}");
+        assert!(delay <= Duration::from_millis(max), "{delay:?}");
+    }
+
+    #[test]
+    fn delay_waits_for_rate_limit_reset() {
+        let delay = RetryPolicy::default().delay(1, Some(Duration::from_secs(30)));
+
+        assert_eq!(delay, Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn run_fails_fast_on_permanent_errors() {
+        let calls = AtomicU32::new(0);
+
+        let result: color_eyre::Result<()> = fast()
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(api(Code::Unauthenticated))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn run_fails_fast_on_errors_without_status() {
+        let calls = AtomicU32::new(0);
+
+        let result: color_eyre::Result<()> = fast()
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(eyre::eyre!("Invalid operations cursor 'x'"))
+            })
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid operations cursor 'x'"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn run_retries_transient_errors() {
+        let calls = AtomicU32::new(0);
+
+        let result = fast()
+            .run(|| async {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(api(Code::Unavailable))
+                } else {
+                    Ok(42)
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn run_gives_up_after_attempts() {
+        let calls = AtomicU32::new(0);
+
+        let result: color_eyre::Result<()> = fast()
+            .attempts(3)
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(api(Code::Unavailable))
+            })
+            .await;
+
+        let error = result.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(error.to_string(), "Operation failed after 3 attempts");
+        assert!(error.downcast_ref::<ApiError>().is_some());
+    }
+}
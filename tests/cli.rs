@@ -0,0 +1,695 @@
+//! Runs every command against the fixture broker API.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/portfolio.json")
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tinkoff"))
+        .arg("--fixture")
+        .arg(fixture())
+        .args(args)
+        .env_remove("TINKOFF_TOKEN_V2")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap()
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = run(args);
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs a command with JSON output and returns the document for `kind`
+fn json(kind: &str, args: &[&str]) -> Value {
+    let args = [&["--format", "json"], args].concat();
+    let document: Value = serde_json::from_str(&stdout(&args)).unwrap();
+    assert_eq!(document["version"], 2);
+    assert_eq!(document["kind"], kind);
+    document["data"].clone()
+}
+
+fn tickers(papers: &Value) -> Vec<&str> {
+    let mut tickers = papers
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["ticker"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    tickers.sort_unstable();
+    tickers
+}
+
+#[test]
+fn all_instruments() {
+    let data = json("portfolio", &["a"]);
+
+    assert_eq!(data["reporting_currency"], "RUB");
+    assert_eq!(tickers(&data["shares"]["papers"]), ["SBER"]);
+    assert_eq!(tickers(&data["bonds"]["papers"]), ["SU26238RMFS4"]);
+    assert_eq!(tickers(&data["etfs"]["papers"]), ["TMOS"]);
+    assert_eq!(
+        tickers(&data["currencies"]["papers"]),
+        ["RUB000UTSTOM", "USD000UTSTOM"]
+    );
+    assert_eq!(tickers(&data["futures"]["papers"]), ["SiZ4"]);
+    assert_eq!(data["current"]["value"], "120450");
+    assert_eq!(data["skipped"][0]["figi"], "BBG000DELIST");
+    assert_eq!(data["skipped"][0]["reason"], "Instrument not found");
+}
+
+#[test]
+fn all_instruments_totals_only() {
+    let data = json("portfolio", &["a", "--aggregate"]);
+
+    assert!(data["shares"]["papers"].is_null());
+    assert_eq!(data["current"]["value"], "120450");
+}
+
+#[test]
+fn income_includes_dividends_and_fees() {
+    let data = json("portfolio", &["s"]);
+
+    let sber = &data["shares"]["papers"][0];
+    assert_eq!(sber["totals"]["additional_profit"]["value"], "218.37");
+    assert_eq!(sber["totals"]["fees"]["value"], "-7.50");
+}
+
+#[test]
+fn realized_income_of_sold_units() {
+    let data = json("portfolio", &["e"]);
+
+    let tmos = &data["etfs"]["papers"][0];
+    assert_eq!(tmos["totals"]["realized"]["value"], "100");
+    assert_eq!(data["etfs"]["realized"]["value"], "100");
+}
+
+#[test]
+fn all_instruments_in_other_currency() {
+    let data = json("portfolio", &["--currency", "usd", "a"]);
+
+    assert_eq!(data["reporting_currency"], "USD");
+    assert_eq!(data["current"]["currency"], "USD");
+    assert!(
+        data["current"]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("1267.89")
+    );
+}
+
+#[test]
+fn full_catalog_finds_the_same_instruments() {
+    let data = json("portfolio", &["--full-catalog", "a"]);
+
+    assert_eq!(data["current"]["value"], "120450");
+    assert_eq!(tickers(&data["shares"]["papers"]), ["SBER"]);
+}
+
+#[test]
+fn holding_period_and_annualized_income_of_positions() {
+    let before = Utc::now();
+    let data = json("portfolio", &["s"]);
+    let after = Utc::now();
+
+    let sber = &data["shares"]["papers"][0];
+    // Held since 2023-02-10 up to the moment the totals were computed
+    let bought: DateTime<Utc> = "2023-02-10T07:00:00Z".parse().unwrap();
+    assert_eq!(sber["totals"]["first_bought"], "2023-02-10T07:00:00Z");
+    let days = sber["holding_days"].as_i64().unwrap();
+    assert!(
+        ((before - bought).num_days()..=(after - bought).num_days()).contains(&days),
+        "{sber}"
+    );
+    assert!(sber["cagr"].is_string(), "{sber}");
+    // The only dividend was paid in 2023
+    assert_eq!(sber["yield_on_cost"], "0");
+
+    let out = stdout(&["s"]);
+    assert!(out.contains("since 2023-02-10"), "{out}");
+    assert!(out.contains("Yield on cost (12 months), %"), "{out}");
+}
+
+#[test]
+fn all_instruments_as_table() {
+    let out = stdout(&["a"]);
+
+    assert!(out.contains("Сбер Банк"), "{out}");
+    assert!(out.contains("SU26238RMFS4"), "{out}");
+    assert!(out.contains("Skipped positions"), "{out}");
+    assert!(out.contains("BBG000DELIST"), "{out}");
+}
+
+#[test]
+fn all_instruments_as_csv() {
+    let out = stdout(&["--format", "csv", "a"]);
+
+    let mut lines = out.lines();
+    assert_eq!(
+        lines.next(),
+        Some(
+            "asset,ticker,figi,name,currency,quantity,average_buy_price,current_price,balance,current,dividends,fees,income,realized,xirr,twr,holding_days,cagr,yield_on_cost"
+        )
+    );
+    assert_eq!(lines.count(), 6);
+}
+
+#[test]
+fn one_asset_type_per_command() {
+    for (command, asset, ticker) in [
+        ("s", "shares", "SBER"),
+        ("b", "bonds", "SU26238RMFS4"),
+        ("e", "etfs", "TMOS"),
+        ("c", "currencies", "USD000UTSTOM"),
+        ("f", "futures", "SiZ4"),
+    ] {
+        let data = json("portfolio", &[command]);
+
+        let papers = tickers(&data[asset]["papers"]);
+        assert!(papers.contains(&ticker), "{command}: {papers:?}");
+        for other in ["shares", "bonds", "etfs", "currencies", "futures"] {
+            if other != asset {
+                assert_eq!(data[other]["instruments_count"], 0, "{command}: {other}");
+            }
+        }
+    }
+}
+
+#[test]
+fn iis_account() {
+    let data = json("portfolio", &["--account", "iis", "s"]);
+
+    assert_eq!(data["shares"]["papers"][0]["quantity"], "5");
+    assert!(data["skipped"].is_null());
+}
+
+#[test]
+fn all_accounts_are_consolidated() {
+    let data = json("portfolio", &["--all-accounts", "s"]);
+
+    let accounts = data["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0]["account_id"], "2000000001");
+    assert_eq!(accounts[1]["account_id"], "2000000002");
+    assert_eq!(data["total"]["shares"]["current"]["value"], "4200");
+}
+
+#[test]
+fn period_ending_before_it_starts_fails() {
+    let output = run(&["hi", "SBER", "--from", "2023-07-20", "--to", "2023-07-01"]);
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("--from 2023-07-20 is later than --to 2023-07-01")
+    );
+}
+
+#[test]
+fn unknown_account_id_fails() {
+    let output = run(&["--account-id", "42", "a"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Account '42' not found"));
+}
+
+#[test]
+fn history() {
+    let data = json("history", &["hi", "SBER"]);
+
+    assert_eq!(data["ticker"], "SBER");
+    assert_eq!(data["items"].as_array().unwrap().len(), 4);
+    assert_eq!(data["items"][0]["description"], "Покупка ценных бумаг");
+}
+
+#[test]
+fn history_of_all_accounts() {
+    let data = json("history", &["--all-accounts", "hi", "SBER"]);
+
+    assert_eq!(data["accounts"].as_array().unwrap().len(), 2);
+    assert_eq!(data["total"]["items"].as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn history_of_period() {
+    let data = json(
+        "history",
+        &["hi", "SBER", "--from", "2023-07-01", "--to", "2023-07-20"],
+    );
+
+    assert_eq!(data["from"], "2023-06-30T21:00:00Z");
+    assert_eq!(data["to"], "2023-07-20T21:00:00Z");
+    assert_eq!(data["items"].as_array().unwrap().len(), 2);
+    assert_eq!(data["expenses"][0]["value"], "-32.63");
+    assert_eq!(data["balance"][0]["value"], "218.37");
+}
+
+#[test]
+fn history_of_year() {
+    let in_year = json(
+        "history",
+        &["--all-accounts", "hi", "SBER", "--year", "2023"],
+    );
+    let before = stdout(&["--format", "json", "hi", "SBER", "--year", "2022"]);
+
+    assert_eq!(in_year["total"]["items"].as_array().unwrap().len(), 5);
+    assert_eq!(in_year["total"]["to"], "2023-12-31T21:00:00Z");
+    assert!(before.is_empty());
+}
+
+#[test]
+fn history_period_options_conflict() {
+    let output = run(&["hi", "SBER", "--year", "2023", "--last", "30d"]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn history_matches_sales_with_oldest_lots() {
+    let data = json("history", &["hi", "TMOS"]);
+
+    assert_eq!(data["realized"][0]["value"], "100");
+    assert_eq!(data["sales"][0]["cost"]["value"], "250");
+    assert_eq!(data["open_lots"].as_array().unwrap().len(), 1);
+    assert_eq!(data["open_lots"][0]["acquired"], "2023-04-05T07:00:00Z");
+    assert_eq!(data["open_lots"][0]["quantity"], 100);
+}
+
+#[test]
+fn history_of_period_matches_sales_with_earlier_lots() {
+    let data = json("history", &["hi", "TMOS", "--from", "2023-06-01"]);
+
+    assert_eq!(data["items"].as_array().unwrap().len(), 1);
+    assert_eq!(data["realized"][0]["value"], "100");
+}
+
+#[test]
+fn history_as_csv() {
+    let out = stdout(&["--format", "csv", "hi", "SBER"]);
+
+    assert_eq!(out.lines().count(), 5);
+    assert!(out.starts_with("ticker,figi,datetime"));
+}
+
+#[test]
+fn operations_journal() {
+    let data = json("operations", &["ops"]);
+
+    assert_eq!(data["items"].as_array().unwrap().len(), 10);
+    assert_eq!(data["items"][0]["kind"], "deposit");
+    assert_eq!(data["items"][1]["kind"], "buy");
+    assert_eq!(data["items"][1]["ticker"], "SBER");
+    assert_eq!(data["subtotals"][0]["month"], "2023-02");
+    assert_eq!(data["totals"][1]["kind"], "buy");
+    assert_eq!(data["totals"][1]["count"], 4);
+    assert_eq!(data["totals"][1]["total"][0]["value"], "-8250");
+}
+
+#[test]
+fn operations_of_types_and_instrument() {
+    let data = json(
+        "operations",
+        &["ops", "--type", "buys,dividend", "--ticker", "SBER"],
+    );
+
+    let kinds = data["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["buy", "dividend"]);
+}
+
+#[test]
+fn operations_of_period_and_all_accounts() {
+    let data = json(
+        "operations",
+        &["--all-accounts", "ops", "--from", "2023-07-01"],
+    );
+
+    assert_eq!(data["accounts"].as_array().unwrap().len(), 2);
+    assert_eq!(data["total"]["from"], "2023-06-30T21:00:00Z");
+    assert_eq!(data["total"]["items"].as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn operations_with_unknown_type_fail() {
+    let output = run(&["ops", "--type", "transfer"]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn operations_as_csv() {
+    let out = stdout(&["--format", "csv", "ops"]);
+
+    assert_eq!(out.lines().count(), 11);
+    assert!(out.starts_with("datetime,kind,ticker"));
+}
+
+#[test]
+fn ldv_eligibility() {
+    let data = json("ldv", &["ldv", "--on", "2026-03-10"]);
+
+    let positions = data["positions"].as_array().unwrap();
+    assert_eq!(positions.len(), 3);
+    let tmos = positions.iter().find(|p| p["ticker"] == "TMOS").unwrap();
+    assert_eq!(tmos["eligible_quantity"], 0);
+    assert_eq!(tmos["next_eligible"], "2026-04-05T07:00:00Z");
+    assert_eq!(tmos["next_eligible_quantity"], 100);
+    assert_eq!(tmos["tax_today"]["value"], "13.00");
+    assert_eq!(tmos["saved_by_waiting"]["value"], "13.00");
+    let sber = positions.iter().find(|p| p["ticker"] == "SBER").unwrap();
+    assert_eq!(sber["eligible_quantity"], 10);
+    assert_eq!(sber["exempt_gain"]["value"], "300");
+    assert_eq!(data["skipped"][0]["figi"], "BBG000DELIST");
+}
+
+#[test]
+fn ldv_of_all_accounts() {
+    let data = json("ldv", &["--all-accounts", "ldv", "--on", "2026-03-10"]);
+
+    assert_eq!(data["accounts"].as_array().unwrap().len(), 2);
+    assert_eq!(data["total"]["positions"].as_array().unwrap().len(), 4);
+    assert_eq!(data["total"]["tax_today"][0]["value"], "26.00");
+}
+
+#[test]
+fn ldv_as_csv() {
+    let out = stdout(&["--format", "csv", "ldv", "--on", "2026-03-10"]);
+
+    assert_eq!(out.lines().count(), 4);
+    assert!(out.starts_with("ticker,figi,name,currency,quantity,eligible_quantity"));
+}
+
+#[test]
+fn tax_estimate_of_year() {
+    let data = json("tax", &["tax", "--year", "2023"]);
+
+    assert_eq!(data["year"], 2023);
+    assert_eq!(data["instruments"].as_array().unwrap().len(), 3);
+    assert_eq!(data["instruments"][0]["ticker"], "SBER");
+    assert_eq!(data["instruments"][0]["withheld"][0]["value"], "32.63");
+    assert_eq!(data["instruments"][2]["realized"][0]["value"], "100");
+    // The fee of a purchase is deducted only once its units are sold
+    assert!(
+        data["instruments"][0]["realized"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(data["taxable_income"][0]["value"], "528");
+    assert_eq!(data["tax_due"][0]["value"], "68.64");
+    assert_eq!(data["outstanding"][0]["value"], "36.01");
+    assert!(data.get("estimated_currencies").is_none());
+}
+
+#[test]
+fn tax_of_year_without_operations() {
+    let data = json("tax", &["tax", "--year", "2022"]);
+
+    assert!(data["instruments"].as_array().unwrap().is_empty());
+    assert!(data["tax_due"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn tax_as_csv() {
+    let out = stdout(&["--format", "csv", "--all-accounts", "tax", "--year", "2023"]);
+
+    assert_eq!(out.lines().count(), 4);
+    assert!(out.starts_with("account_id,year,ticker,figi"));
+}
+
+#[test]
+fn iis_contributions_and_holding_period() {
+    let data = json("iis", &["iis"]);
+
+    assert_eq!(data["kind"], "old");
+    assert_eq!(data["holding_until"], "2024-06-15T00:00:00Z");
+    let years = data["years"].as_array().unwrap();
+    assert_eq!(years.len(), 3);
+    assert_eq!(years[1]["year"], 2022);
+    assert_eq!(years[1]["excess"]["value"], "100000");
+    assert_eq!(data["deductions"]["value"], "130000.00");
+    assert_eq!(data["voiding_withdrawals"][0]["amount"]["value"], "50000");
+}
+
+#[test]
+fn iis_of_broker_account_fails() {
+    let output = run(&["--account-id", "2000000001", "iis"]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn iis_of_explicit_broker_account_type_fails() {
+    let output = run(&["--account", "tinkoff", "iis"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--account tinkoff"));
+}
+
+#[test]
+fn iis_as_csv() {
+    let out = stdout(&["--format", "csv", "iis"]);
+
+    assert_eq!(out.lines().count(), 4);
+    assert!(out.starts_with("year,contributions,limit"));
+}
+
+#[test]
+fn simulate_sell_of_lots_held_for_ldv() {
+    let data = json(
+        "sell_simulation",
+        &["simulate", "sell", "SBER", "5", "--on", "2026-03-10"],
+    );
+
+    assert_eq!(data["proceeds"]["value"], "1400");
+    assert_eq!(data["fee"]["value"], "1.22");
+    assert_eq!(data["exempt_quantity"], 5);
+    assert_eq!(data["tax"]["value"], "0");
+    assert_eq!(data["net_cash"]["value"], "1398.78");
+    assert_eq!(data["before"]["weight"], "2.32");
+    assert_eq!(data["after"]["weight"], "1.16");
+}
+
+#[test]
+fn simulate_sell_before_ldv_withholds_tax() {
+    let data = json(
+        "sell_simulation",
+        &["simulate", "sell", "SBER", "5", "--on", "2025-01-01"],
+    );
+
+    assert_eq!(data["exempt_quantity"], 0);
+    assert_eq!(data["taxable_gain"]["value"], "148.78");
+    assert_eq!(data["tax"]["value"], "19.34");
+    assert_eq!(data["net_cash"]["value"], "1379.44");
+}
+
+#[test]
+fn simulate_sell_of_more_than_held_fails() {
+    let output = run(&["simulate", "sell", "SBER", "11"]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cashflow_by_month() {
+    let data = json("cashflow", &["cashflow"]);
+
+    assert_eq!(data["interval"], "month");
+    let rows = data["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1]["period"], "2023-07");
+    assert_eq!(rows[1]["income"][0]["value"], "251");
+    assert_eq!(data["totals"]["costs"][0]["value"], "-40.13");
+}
+
+#[test]
+fn cashflow_compares_current_value_with_net_contributions() {
+    let data = json("cashflow", &["--account", "iis", "cashflow", "--yearly"]);
+
+    let rows = data["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[2]["net_contributions"][0]["value"], "150000");
+    assert_eq!(data["net_contributions"]["value"], "1650000");
+    // Cash left after the purchase of SBER and its shares bought at 260, now at 280
+    assert_eq!(data["current"]["value"], "1650100");
+    assert_eq!(data["lifetime_gain"]["value"], "100");
+    assert_eq!(data["lifetime_return"], "0.01");
+    assert!(data["skipped"].is_null());
+}
+
+#[test]
+fn cashflow_as_csv() {
+    let out = stdout(&["--format", "csv", "--all-accounts", "cashflow", "--yearly"]);
+
+    assert_eq!(out.lines().count(), 5);
+    assert!(out.starts_with("account_id,period,currency,deposits"));
+}
+
+#[test]
+fn dividends() {
+    let data = json("dividend_calendar", &["d"]);
+
+    let upcoming = data["upcoming"].as_array().unwrap();
+    assert_eq!(upcoming.len(), 1);
+    assert_eq!(upcoming[0]["ticker"], "SBER");
+    assert_eq!(upcoming[0]["total_dividend"]["value"], "333.00");
+    assert_eq!(data["skipped"][0]["figi"], "BBG000DELIST");
+}
+
+#[test]
+fn coupons() {
+    let data = json("coupon_calendar", &["p"]);
+
+    let upcoming = data["upcoming"].as_array().unwrap();
+    assert_eq!(upcoming.len(), 1);
+    assert_eq!(upcoming[0]["ticker"], "SU26238RMFS4");
+    assert_eq!(upcoming[0]["coupon_type"], "Constant");
+    assert!(data["skipped"].is_null());
+}
+
+#[test]
+fn combined_calendar() {
+    let data = json("combined_calendar", &["j"]);
+
+    let kinds = data["upcoming"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["coupon", "dividend"]);
+}
+
+#[test]
+fn calendar_as_csv_reports_skipped_positions_to_stderr() {
+    let output = run(&["--format", "csv", "j"]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 3);
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Skipped BBG000DELIST (share): Instrument not found")
+    );
+}
+
+#[test]
+fn strict_fails_on_skipped_positions() {
+    let output = run(&["--strict", "--format", "json", "d"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 position(s) failed to load"));
+    assert!(run(&["--strict", "--format", "json", "p"]).status.success());
+}
+
+#[test]
+fn risk() {
+    let data = json("risk", &["r"]);
+
+    assert_eq!(
+        data["analysis"]["asset_allocation"]["shares"]["value"]["value"],
+        "2800"
+    );
+    assert!(data["skipped"].is_array());
+}
+
+#[test]
+fn returns_of_positions_and_account() {
+    let data = json("portfolio", &["--account", "iis", "a"]);
+
+    let sber = &data["shares"]["papers"][0];
+    assert!(sber["totals"]["returns"]["xirr"].is_string(), "{sber}");
+    assert!(sber["totals"]["returns"]["twr"].is_string(), "{sber}");
+    // The account gained only the rise of SBER, 100 on 1.65 million contributed
+    assert_eq!(data["returns"]["xirr"], "0.00");
+
+    let out = stdout(&["r"]);
+    assert!(out.contains("XIRR, % per year"), "{out}");
+    assert!(out.contains("Time-weighted return, %"), "{out}");
+}
+
+#[test]
+fn account_returns_are_the_same_in_every_view() {
+    let all = json("portfolio", &["a"]);
+    let risk = json("risk", &["r"]);
+
+    assert!(all["returns"]["xirr"].is_string(), "{all}");
+    assert_eq!(all["returns"], risk["analysis"]["returns"]);
+    // Assets alone are a part of the account, its returns are left out
+    for cmd in ["s", "b", "e", "f", "c"] {
+        let asset = json("portfolio", &[cmd]);
+        assert!(asset["returns"].is_null(), "{cmd}: {asset}");
+    }
+}
+
+#[test]
+fn risk_has_no_csv() {
+    let output = run(&["--format", "csv", "r"]);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn accounts() {
+    let data = json("accounts", &["--account", "iis", "ac"]);
+
+    let accounts = data["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 3);
+    assert_eq!(accounts[1]["account_type"], "iis");
+    assert_eq!(accounts[1]["selected"], true);
+    assert_eq!(accounts[2]["status"], "closed");
+    assert_eq!(accounts[2]["access_level"], "full");
+}
+
+#[test]
+fn replay_reproduces_recorded_runs() {
+    let dir = std::env::temp_dir().join(format!("tinkoff-cli-record-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir_arg = dir.to_str().unwrap();
+
+    for command in [
+        &["a"][..],
+        &["--all-accounts", "j"],
+        &["hi", "SBER"],
+        &["ac"],
+    ] {
+        let recorded = stdout(&[&["--format", "json", "--record", dir_arg], command].concat());
+
+        let output = Command::new(env!("CARGO_BIN_EXE_tinkoff"))
+            .args(["--format", "json", "--replay", dir_arg])
+            .args(command)
+            .env_remove("TINKOFF_TOKEN_V2")
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{command:?}");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), recorded);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn replay_fails_without_recording() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tinkoff"))
+        .args(["--replay", "/nonexistent/tinkoff-recording", "a"])
+        .env_remove("TINKOFF_TOKEN_V2")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Replay directory"));
+}